@@ -0,0 +1,387 @@
+/// A global symbol of the language, or a member of a built-in type.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Builtin {
+    /// Where the symbol lives: empty for globals, otherwise the name of the namespace or type,
+    /// like `msg`, `address` or `array`.
+    pub namespace: &'static str,
+    pub name: &'static str,
+    pub signature: &'static str,
+    /// Type of the variable, or of the result of the function. Empty when unknown.
+    pub ty: &'static str,
+    pub function: bool,
+    pub doc: &'static str,
+}
+
+const fn var(
+    namespace: &'static str,
+    name: &'static str,
+    ty: &'static str,
+    signature: &'static str,
+    doc: &'static str,
+) -> Builtin {
+    Builtin {
+        namespace,
+        name,
+        signature,
+        ty,
+        function: false,
+        doc,
+    }
+}
+
+const fn function(
+    namespace: &'static str,
+    name: &'static str,
+    ty: &'static str,
+    signature: &'static str,
+    doc: &'static str,
+) -> Builtin {
+    Builtin {
+        namespace,
+        name,
+        signature,
+        ty,
+        function: true,
+        doc,
+    }
+}
+
+/// Namespaces that are values of their own, rather than members of a type.
+pub const MAGIC: &[&str] = &[
+    "msg",
+    "block",
+    "tx",
+    "abi",
+    "type(contract)",
+    "type(interface)",
+    "type(integer)",
+];
+
+pub static BUILTINS: &[Builtin] = &[
+    var("", "msg", "msg", "msg", "Properties of the current call."),
+    var("", "block", "block", "block", "Properties of the current block."),
+    var("", "tx", "tx", "tx", "Properties of the current transaction."),
+    var("", "abi", "abi", "abi", "ABI encoding and decoding functions."),
+    var("", "now", "uint256", "uint256 now", "Alias of `block.timestamp`, removed in 0.7.0."),
+    function("", "gasleft", "uint256", "function gasleft() returns (uint256)", "Remaining gas."),
+    function(
+        "",
+        "blockhash",
+        "bytes32",
+        "function blockhash(uint256 blockNumber) returns (bytes32)",
+        "Hash of the given block, only available for the 256 most recent blocks.",
+    ),
+    function(
+        "",
+        "blobhash",
+        "bytes32",
+        "function blobhash(uint256 index) returns (bytes32)",
+        "Versioned hash of the `index`-th blob of the current transaction.",
+    ),
+    function(
+        "",
+        "keccak256",
+        "bytes32",
+        "function keccak256(bytes memory) returns (bytes32)",
+        "Keccak-256 hash of the input.",
+    ),
+    function(
+        "",
+        "sha256",
+        "bytes32",
+        "function sha256(bytes memory) returns (bytes32)",
+        "SHA-256 hash of the input.",
+    ),
+    function(
+        "",
+        "ripemd160",
+        "bytes20",
+        "function ripemd160(bytes memory) returns (bytes20)",
+        "RIPEMD-160 hash of the input.",
+    ),
+    function(
+        "",
+        "ecrecover",
+        "address",
+        "function ecrecover(bytes32 hash, uint8 v, bytes32 r, bytes32 s) returns (address)",
+        "Recovers the address associated with the public key from an elliptic curve signature, or returns zero on error.",
+    ),
+    function(
+        "",
+        "addmod",
+        "uint256",
+        "function addmod(uint256 x, uint256 y, uint256 k) returns (uint256)",
+        "Computes `(x + y) % k` with arbitrary precision.",
+    ),
+    function(
+        "",
+        "mulmod",
+        "uint256",
+        "function mulmod(uint256 x, uint256 y, uint256 k) returns (uint256)",
+        "Computes `(x * y) % k` with arbitrary precision.",
+    ),
+    function(
+        "",
+        "require",
+        "",
+        "function require(bool condition)",
+        "Reverts if the condition is not met.",
+    ),
+    function(
+        "",
+        "require",
+        "",
+        "function require(bool condition, string memory message)",
+        "Reverts with `message` if the condition is not met.",
+    ),
+    function(
+        "",
+        "assert",
+        "",
+        "function assert(bool condition)",
+        "Panics if the condition is not met, for internal errors.",
+    ),
+    function("", "revert", "", "function revert()", "Aborts execution and reverts state changes."),
+    function(
+        "",
+        "revert",
+        "",
+        "function revert(string memory reason)",
+        "Aborts execution and reverts state changes, with an explanatory string.",
+    ),
+    function(
+        "",
+        "selfdestruct",
+        "",
+        "function selfdestruct(address payable recipient)",
+        "Sends all funds to `recipient`. Since Cancun, only destroys contracts created in the same transaction.",
+    ),
+    function(
+        "",
+        "type",
+        "",
+        "function type(T)",
+        "Information about the type `T`.",
+    ),
+    var("", "this", "", "this", "The current contract, explicitly convertible to `address`."),
+    var("", "super", "", "super", "The contract one level higher in the inheritance hierarchy."),
+    // msg
+    var("msg", "data", "bytes", "bytes calldata msg.data", "Complete calldata."),
+    var("msg", "sender", "address", "address msg.sender", "Sender of the message (current call)."),
+    var("msg", "sig", "bytes4", "bytes4 msg.sig", "First four bytes of the calldata, the function identifier."),
+    var("msg", "value", "uint256", "uint256 msg.value", "Number of wei sent with the message."),
+    // block
+    var("block", "basefee", "uint256", "uint256 block.basefee", "Current block's base fee."),
+    var("block", "blobbasefee", "uint256", "uint256 block.blobbasefee", "Current block's blob base fee."),
+    var("block", "chainid", "uint256", "uint256 block.chainid", "Current chain id."),
+    var(
+        "block",
+        "coinbase",
+        "address payable",
+        "address payable block.coinbase",
+        "Current block miner's address.",
+    ),
+    var(
+        "block",
+        "difficulty",
+        "uint256",
+        "uint256 block.difficulty",
+        "Current block difficulty, an alias of `block.prevrandao` since Paris.",
+    ),
+    var("block", "gaslimit", "uint256", "uint256 block.gaslimit", "Current block gas limit."),
+    var("block", "number", "uint256", "uint256 block.number", "Current block number."),
+    var(
+        "block",
+        "prevrandao",
+        "uint256",
+        "uint256 block.prevrandao",
+        "Random number provided by the beacon chain.",
+    ),
+    var(
+        "block",
+        "timestamp",
+        "uint256",
+        "uint256 block.timestamp",
+        "Current block timestamp as seconds since unix epoch.",
+    ),
+    // tx
+    var("tx", "gasprice", "uint256", "uint256 tx.gasprice", "Gas price of the transaction."),
+    var("tx", "origin", "address", "address tx.origin", "Sender of the transaction (full call chain)."),
+    // abi
+    function(
+        "abi",
+        "decode",
+        "",
+        "function abi.decode(bytes memory encodedData, (...)) returns (...)",
+        "ABI-decodes the data into the types given as second argument.",
+    ),
+    function(
+        "abi",
+        "encode",
+        "bytes",
+        "function abi.encode(...) returns (bytes memory)",
+        "ABI-encodes the arguments.",
+    ),
+    function(
+        "abi",
+        "encodePacked",
+        "bytes",
+        "function abi.encodePacked(...) returns (bytes memory)",
+        "Performs packed encoding of the arguments. Packed encoding can be ambiguous.",
+    ),
+    function(
+        "abi",
+        "encodeWithSelector",
+        "bytes",
+        "function abi.encodeWithSelector(bytes4 selector, ...) returns (bytes memory)",
+        "ABI-encodes the arguments after the given four-byte selector.",
+    ),
+    function(
+        "abi",
+        "encodeWithSignature",
+        "bytes",
+        "function abi.encodeWithSignature(string memory signature, ...) returns (bytes memory)",
+        "Equivalent to `abi.encodeWithSelector(bytes4(keccak256(bytes(signature))), ...)`.",
+    ),
+    function(
+        "abi",
+        "encodeCall",
+        "bytes",
+        "function abi.encodeCall(function functionPointer, (...)) returns (bytes memory)",
+        "ABI-encodes a call to `functionPointer` with the arguments in the tuple, checking their types.",
+    ),
+    // address
+    var("address", "balance", "uint256", "uint256 balance", "Balance of the address in wei."),
+    var("address", "code", "bytes", "bytes memory code", "Code at the address, can be empty."),
+    var("address", "codehash", "bytes32", "bytes32 codehash", "Hash of the code at the address."),
+    function(
+        "address",
+        "call",
+        "",
+        "function call(bytes memory) returns (bool, bytes memory)",
+        "Issues a low-level `CALL` with the given payload, returns success and return data.",
+    ),
+    function(
+        "address",
+        "delegatecall",
+        "",
+        "function delegatecall(bytes memory) returns (bool, bytes memory)",
+        "Issues a low-level `DELEGATECALL` with the given payload, returns success and return data.",
+    ),
+    function(
+        "address",
+        "staticcall",
+        "",
+        "function staticcall(bytes memory) returns (bool, bytes memory)",
+        "Issues a low-level `STATICCALL` with the given payload, returns success and return data.",
+    ),
+    function(
+        "address payable",
+        "transfer",
+        "",
+        "function transfer(uint256 amount)",
+        "Sends `amount` wei to the address, reverts on failure, forwards a 2300 gas stipend.",
+    ),
+    function(
+        "address payable",
+        "send",
+        "bool",
+        "function send(uint256 amount) returns (bool)",
+        "Sends `amount` wei to the address, returns `false` on failure, forwards a 2300 gas stipend.",
+    ),
+    // arrays and `bytes`
+    var("array", "length", "uint256", "uint256 length", "Number of elements."),
+    function(
+        "array",
+        "push",
+        "",
+        "function push(T value)",
+        "Appends an element to a dynamic storage array. Without argument, appends a zero-initialized element and returns a reference to it.",
+    ),
+    function(
+        "array",
+        "pop",
+        "",
+        "function pop()",
+        "Removes the last element of a dynamic storage array.",
+    ),
+    var("bytesN", "length", "uint8", "uint8 length", "Number of bytes."),
+    function(
+        "bytes",
+        "concat",
+        "bytes",
+        "function bytes.concat(...) returns (bytes memory)",
+        "Concatenates a variable number of `bytes` and `bytesN` arguments.",
+    ),
+    function(
+        "string",
+        "concat",
+        "string",
+        "function string.concat(...) returns (string memory)",
+        "Concatenates a variable number of `string` arguments.",
+    ),
+    // functions
+    var("function", "selector", "bytes4", "bytes4 selector", "ABI function selector."),
+    var(
+        "function",
+        "address",
+        "address",
+        "address address",
+        "Address of the contract of an external function.",
+    ),
+    // user-defined value types
+    function(
+        "value type",
+        "wrap",
+        "",
+        "function wrap(U value) returns (T)",
+        "Converts the underlying type to the user-defined value type.",
+    ),
+    function(
+        "value type",
+        "unwrap",
+        "",
+        "function unwrap(T value) returns (U)",
+        "Converts the user-defined value type to its underlying type.",
+    ),
+    // type(...)
+    var("type(contract)", "name", "string", "string name", "Name of the contract."),
+    var(
+        "type(contract)",
+        "creationCode",
+        "bytes",
+        "bytes memory creationCode",
+        "Creation bytecode of the contract.",
+    ),
+    var(
+        "type(contract)",
+        "runtimeCode",
+        "bytes",
+        "bytes memory runtimeCode",
+        "Runtime bytecode of the contract.",
+    ),
+    var("type(interface)", "name", "string", "string name", "Name of the interface."),
+    var(
+        "type(interface)",
+        "interfaceId",
+        "bytes4",
+        "bytes4 interfaceId",
+        "EIP-165 interface identifier of the interface.",
+    ),
+    var("type(integer)", "min", "", "T min", "Smallest value of the integer type."),
+    var("type(integer)", "max", "", "T max", "Largest value of the integer type."),
+];
+
+/// Builtins named `name` in `namespace`, several for overloaded functions.
+pub fn lookup<'a>(
+    namespace: &'a str,
+    name: &'a str,
+) -> impl Iterator<Item = &'static Builtin> + 'a {
+    members(namespace).filter(move |builtin| builtin.name == name)
+}
+
+pub fn members(namespace: &str) -> impl Iterator<Item = &'static Builtin> + '_ {
+    BUILTINS
+        .iter()
+        .filter(move |builtin| builtin.namespace == namespace)
+}
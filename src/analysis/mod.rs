@@ -0,0 +1,342 @@
+use crate::{
+    config::Config,
+    document::{DocumentStore, PositionEncoding},
+    index::{self, IndexOptions},
+    project::{self, Project},
+};
+use semver::Version;
+use slang_solidity::cst::NonterminalKind;
+use slang_solidity::parser::{ParseOutput, Parser};
+use source::{ParsedSource, SourceFile};
+use std::{
+    any::Any,
+    collections::HashMap,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    rc::Rc,
+    sync::{mpsc, Arc},
+    thread,
+    time::SystemTime,
+};
+use tokio::sync::oneshot;
+use tower_lsp::lsp_types::Url;
+
+pub mod builtins;
+pub mod model;
+pub mod natspec;
+pub mod semantics;
+pub mod source;
+pub mod syntax;
+pub mod version;
+
+type Task = Box<dyn FnOnce(&mut Analysis) + Send>;
+
+/// Cheap handle to the analysis thread, which owns every parsed tree.
+///
+/// Slang trees can't be sent across threads, so anything that needs a CST is shipped to that
+/// thread as a closure and only `Send` results come back.
+#[derive(Debug, Clone)]
+pub struct AnalysisHandle {
+    sender: mpsc::Sender<Task>,
+}
+
+impl AnalysisHandle {
+    pub fn spawn(documents: Arc<DocumentStore>) -> Self {
+        let (sender, receiver) = mpsc::channel::<Task>();
+        thread::Builder::new()
+            .name("slap-analysis".to_string())
+            .spawn(move || {
+                let mut analysis = Analysis::new(documents);
+                for task in receiver {
+                    // A bug in one request mustn't take the others down with the thread.
+                    if let Err(payload) =
+                        panic::catch_unwind(AssertUnwindSafe(|| task(&mut analysis)))
+                    {
+                        log::error!("analysis task panicked: {}", panic_message(&*payload));
+                    }
+                }
+            })
+            .expect("failed to spawn the analysis thread");
+        Self { sender }
+    }
+
+    /// Runs `f` on the analysis thread and returns its result, `None` if it panicked.
+    pub async fn with<R, F>(&self, f: F) -> Option<R>
+    where
+        F: FnOnce(&mut Analysis) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let (tx, rx) = oneshot::channel();
+        let task: Task = Box::new(move |analysis| {
+            let _ = tx.send(f(analysis));
+        });
+        if self.sender.send(task).is_err() {
+            log::error!("the analysis thread is gone");
+            return None;
+        }
+        rx.await.ok()
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    match payload.downcast_ref::<&str>() {
+        Some(message) => message,
+        None => payload
+            .downcast_ref::<String>()
+            .map_or("unknown cause", String::as_str),
+    }
+}
+
+/// State of the analysis thread: the parse of the latest version of every document.
+#[derive(Debug)]
+pub struct Analysis {
+    encoding: PositionEncoding,
+    documents: Arc<DocumentStore>,
+    sources: HashMap<Url, Rc<ParsedSource>>,
+    /// Files that aren't open, by modification time of the content they were read from.
+    disk: HashMap<PathBuf, (Option<SystemTime>, Rc<SourceFile>)>,
+    /// Project of every directory seen so far, `None` outside of a Foundry project.
+    projects: HashMap<PathBuf, Option<Project>>,
+    /// Detector configuration of every directory seen so far.
+    configs: HashMap<PathBuf, Config>,
+    /// The `slap` settings of the editor, over the configuration files.
+    settings: toml::Table,
+    workspace_folders: Vec<PathBuf>,
+}
+
+impl Analysis {
+    pub fn new(documents: Arc<DocumentStore>) -> Self {
+        Self {
+            encoding: PositionEncoding::default(),
+            documents,
+            sources: HashMap::new(),
+            disk: HashMap::new(),
+            projects: HashMap::new(),
+            configs: HashMap::new(),
+            settings: toml::Table::new(),
+            workspace_folders: Vec::new(),
+        }
+    }
+
+    pub fn set_workspace_folders(&mut self, folders: Vec<PathBuf>) {
+        self.workspace_folders = folders;
+    }
+
+    pub fn change_workspace_folders(&mut self, added: Vec<PathBuf>, removed: Vec<PathBuf>) {
+        self.workspace_folders
+            .retain(|folder| !removed.contains(folder));
+        self.workspace_folders.extend(added);
+    }
+
+    pub fn set_position_encoding(&mut self, encoding: PositionEncoding) {
+        self.encoding = encoding;
+        self.sources.clear();
+        self.disk.clear();
+    }
+
+    /// Returns the parse of `text`, reusing the cached one if this version was already parsed.
+    pub fn parse(
+        &mut self,
+        uri: &Url,
+        path: PathBuf,
+        version: Option<i32>,
+        text: String,
+    ) -> Rc<ParsedSource> {
+        if let Some(source) = self.sources.get(uri) {
+            // Content read from disk has no version, so compare the text itself.
+            if source.version == version && (version.is_some() || source.text == text) {
+                return Rc::clone(source);
+            }
+        }
+
+        // Pragmas rarely change between edits, so the previous version is a good first guess.
+        let guess = self
+            .sources
+            .get(uri)
+            .map(|source| source.solidity_version.clone());
+        let source = Rc::new(self.parse_uncached(path, version, text, guess));
+        self.sources.insert(uri.clone(), Rc::clone(&source));
+        source
+    }
+
+    fn parse_uncached(
+        &mut self,
+        path: PathBuf,
+        version: Option<i32>,
+        text: String,
+        guess: Option<Version>,
+    ) -> ParsedSource {
+        let guess = guess.unwrap_or_else(|| Parser::SUPPORTED_VERSIONS.last().unwrap().clone());
+        let configured = self.project(&path).and_then(|project| project.solc.clone());
+
+        let mut output = Self::parse_with(&guess, &text);
+        let pragmas = version::version_pragmas(output.tree());
+        let solidity_version = version::resolve(&pragmas, configured.as_ref());
+        if solidity_version != guess {
+            output = Self::parse_with(&solidity_version, &text);
+        }
+
+        ParsedSource::new(path, version, text, solidity_version, output, self.encoding)
+    }
+
+    /// Parses `text` as if it were the content of `file`, without caching it.
+    pub fn parse_text(&mut self, file: &SourceFile, text: String) -> ParsedSource {
+        let version = Some(file.solidity_version.clone());
+        self.parse_uncached(file.path.clone(), file.version, text, version)
+    }
+
+    /// The file at `path`: the buffer content if it's open, otherwise the content on disk.
+    ///
+    /// Only the model of files that aren't open is kept, not their tree.
+    pub fn file(&mut self, path: &Path) -> Option<Rc<SourceFile>> {
+        if let Some(file) = self.open_file(path) {
+            return Some(file);
+        }
+
+        let modified = std::fs::metadata(path).and_then(|m| m.modified()).ok();
+        if let Some((time, file)) = self.disk.get(path) {
+            if *time == modified {
+                return Some(Rc::clone(file));
+            }
+        }
+        let text = std::fs::read_to_string(path)
+            .inspect_err(|err| log::warn!("failed to read {}: {err}", path.display()))
+            .ok()?;
+        let file = self
+            .parse_uncached(path.to_path_buf(), None, text, None)
+            .file;
+        self.disk
+            .insert(path.to_path_buf(), (modified, Rc::clone(&file)));
+        Some(file)
+    }
+
+    /// Like [`Self::file`], without keeping the files that aren't open, for one-off reads.
+    pub fn file_uncached(&mut self, path: &Path) -> Option<Rc<SourceFile>> {
+        if let Some(file) = self.open_file(path) {
+            return Some(file);
+        }
+        let text = std::fs::read_to_string(path)
+            .inspect_err(|err| log::warn!("failed to read {}: {err}", path.display()))
+            .ok()?;
+        Some(
+            self.parse_uncached(path.to_path_buf(), None, text, None)
+                .file,
+        )
+    }
+
+    fn open_file(&mut self, path: &Path) -> Option<Rc<SourceFile>> {
+        let uri = Url::from_file_path(path).ok()?;
+        let document = self.documents.get(&uri)?;
+        let source = self.parse(
+            &uri,
+            path.to_path_buf(),
+            Some(document.version),
+            document.text,
+        );
+        Some(Rc::clone(&source.file))
+    }
+
+    /// Resolves an import of the file at `from`, through the remappings of its project if any,
+    /// and `node_modules` like Hardhat does.
+    pub fn resolve_import(&mut self, from: &Path, import: &str) -> Option<PathBuf> {
+        if let Some(path) = self
+            .project(from)
+            .and_then(|project| project.resolve_import(from, import))
+        {
+            return Some(path);
+        }
+        if import.starts_with("./") || import.starts_with("../") {
+            return Some(project::normalize(&from.parent()?.join(import)));
+        }
+        from.ancestors()
+            .skip(1)
+            .map(|dir| dir.join("node_modules").join(import))
+            .find(|path| path.is_file())
+    }
+
+    /// The Solidity files of the workspace folders and the open documents whose text contains
+    /// `name`, which is how features searching the workspace skip most files without parsing them.
+    pub fn files_mentioning(&mut self, name: &str) -> Vec<Rc<SourceFile>> {
+        let mut paths: Vec<PathBuf> = self
+            .documents
+            .uris()
+            .iter()
+            .filter_map(|uri| uri.to_file_path().ok())
+            .collect();
+        let options = IndexOptions {
+            node_modules: false,
+            ..IndexOptions::default()
+        };
+        for folder in &self.workspace_folders {
+            paths.extend(index::solidity_files(folder, &options));
+        }
+        paths.sort();
+        paths.dedup();
+
+        let mut files = Vec::new();
+        for path in paths {
+            let mentions = match Url::from_file_path(&path)
+                .ok()
+                .and_then(|uri| self.documents.get(&uri))
+            {
+                Some(document) => document.text.contains(name),
+                None => {
+                    let modified = std::fs::metadata(&path).and_then(|m| m.modified()).ok();
+                    match self.disk.get(&path) {
+                        Some((time, file)) if *time == modified => file.text.contains(name),
+                        _ => std::fs::read_to_string(&path).is_ok_and(|text| text.contains(name)),
+                    }
+                }
+            };
+            if mentions {
+                files.extend(self.file(&path));
+            }
+        }
+        files
+    }
+
+    fn parse_with(version: &Version, text: &str) -> ParseOutput {
+        Parser::create(version.clone())
+            .expect("resolved versions are supported")
+            .parse(NonterminalKind::SourceUnit, text)
+    }
+
+    /// The Foundry project `path` belongs to.
+    pub fn project(&mut self, path: &Path) -> Option<&Project> {
+        let dir = path.parent()?.to_path_buf();
+        self.projects
+            .entry(dir)
+            .or_insert_with(|| Project::find(path))
+            .as_ref()
+    }
+
+    /// The detector configuration of the file at `path`.
+    pub fn config(&mut self, path: &Path) -> &Config {
+        let dir = path.parent().unwrap_or(path).to_path_buf();
+        self.configs
+            .entry(dir)
+            .or_insert_with(|| Config::find(path, &self.settings))
+    }
+
+    /// Replaces the `slap` settings of the editor, and tells whether they changed.
+    pub fn set_settings(&mut self, settings: toml::Table) -> bool {
+        if settings == self.settings {
+            return false;
+        }
+        self.settings = settings;
+        self.configs.clear();
+        true
+    }
+
+    /// Forgets the projects and configurations read so far, after a `foundry.toml` or
+    /// `slap.toml` changed. The parses go too since they depend on the `solc` version.
+    pub fn reload_configuration(&mut self) {
+        self.projects.clear();
+        self.configs.clear();
+        self.sources.clear();
+    }
+
+    pub fn remove(&mut self, uri: &Url) {
+        self.sources.remove(uri);
+    }
+}
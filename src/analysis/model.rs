@@ -0,0 +1,1284 @@
+use super::natspec::NatSpec;
+use super::syntax::{join_tokens, Span, SyntaxNode};
+use slang_solidity::cst::{EdgeLabel, Node, NonterminalKind, TerminalKind};
+use std::fmt;
+
+pub type DeclId = usize;
+pub type ScopeId = usize;
+
+/// What a file declares and references, extracted from its CST once per parse.
+///
+/// The model keeps no Slang node, so it can outlive the tree it was built from.
+#[derive(Debug, Default)]
+pub struct SourceModel {
+    pub declarations: Vec<Declaration>,
+    pub scopes: Vec<Scope>,
+    pub references: Vec<Reference>,
+    pub imports: Vec<Import>,
+    pub usings: Vec<Using>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Import {
+    /// The path as written.
+    pub path: String,
+    pub path_span: Span,
+    /// `import "path";`, which brings every symbol of the file in scope.
+    pub wildcard: bool,
+}
+
+/// A `using ... for ...;` directive, which attaches functions to a type.
+#[derive(Debug, Clone)]
+pub struct Using {
+    /// `L` in `using L for T;`, which attaches every function of the library.
+    pub library: Option<Expr>,
+    /// `f` and `g` in `using {f, g} for T;`, without the ones bound to an operator.
+    pub functions: Vec<Expr>,
+    /// `None` for `*`.
+    pub target: Option<TypeRef>,
+    pub scope: ScopeId,
+    /// `using ... for T global;`, in effect wherever `T` is used.
+    pub global: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DeclKind {
+    Contract,
+    Interface,
+    Library,
+    Function,
+    Constructor,
+    Fallback,
+    Receive,
+    Modifier,
+    StateVariable,
+    Constant,
+    Struct,
+    StructMember,
+    Enum,
+    EnumMember,
+    Event,
+    Error,
+    UserDefinedValueType,
+    Parameter,
+    LocalVariable,
+    YulFunction,
+    YulVariable,
+    /// `M` in `import "path" as M;` or `import * as M from "path";`.
+    Module,
+    /// `X` or `Y` in `import {X, Z as Y} from "path";`.
+    Import,
+}
+
+impl DeclKind {
+    pub fn is_contract(self) -> bool {
+        matches!(self, Self::Contract | Self::Interface | Self::Library)
+    }
+
+    /// Kinds that can be used as a type name.
+    pub fn is_type(self) -> bool {
+        self.is_contract() || matches!(self, Self::Struct | Self::Enum | Self::UserDefinedValueType)
+    }
+
+    /// Kinds that can share a name in the same scope, told apart by their parameters.
+    pub fn is_overloadable(self) -> bool {
+        matches!(self, Self::Function | Self::Event)
+    }
+
+    /// Variables only visible after their declaration.
+    pub fn is_local(self) -> bool {
+        matches!(self, Self::LocalVariable | Self::YulVariable)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Declaration {
+    pub kind: DeclKind,
+    /// Empty for unnamed parameters.
+    pub name: String,
+    pub name_span: Span,
+    /// The whole declaration, without its documentation.
+    pub span: Span,
+    /// The contract, struct, enum or callable the declaration belongs to.
+    pub container: Option<DeclId>,
+    /// Scope the names used by the declaration resolve in.
+    pub scope: ScopeId,
+    /// Offset from which a local variable can be referenced.
+    pub visible_from: usize,
+    pub ty: Option<TypeRef>,
+    /// The declaration as written, without its body or documentation.
+    pub signature: String,
+    pub natspec: Option<NatSpec>,
+    /// Keywords like `public`, `view`, `constant` or `override`, and the data location of
+    /// parameters and local variables.
+    pub attributes: Vec<String>,
+    pub parameters: Vec<DeclId>,
+    pub returns: Vec<DeclId>,
+    /// Members of a contract, struct or enum.
+    pub members: Vec<DeclId>,
+    /// Inherited contracts, as written after `is`.
+    pub bases: Vec<Expr>,
+    /// Where a module or imported symbol comes from.
+    pub imported: Option<Imported>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Imported {
+    /// Index of the import in the file.
+    pub import: usize,
+    /// Name of the symbol in the imported file, `None` for a module.
+    pub name: Option<String>,
+}
+
+impl Declaration {
+    pub fn has_attribute(&self, attribute: &str) -> bool {
+        self.attributes.iter().any(|a| a == attribute)
+    }
+
+    /// Whether the declaration can be accessed from outside the contract, through an instance.
+    pub fn is_external(&self) -> bool {
+        match self.kind {
+            // Functions were public by default before 0.5.
+            DeclKind::Function => !self.has_attribute("internal") && !self.has_attribute("private"),
+            DeclKind::StateVariable => self.has_attribute("public"),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScopeKind {
+    File,
+    Contract,
+    Function,
+    Block,
+}
+
+#[derive(Debug, Clone)]
+pub struct Scope {
+    pub kind: ScopeKind,
+    /// The text the scope covers, headers of functions included.
+    pub span: Span,
+    pub parent: Option<ScopeId>,
+    /// The contract or function that opened the scope.
+    pub owner: Option<DeclId>,
+    /// Declarations that can be referenced by name in the scope.
+    pub declarations: Vec<DeclId>,
+}
+
+/// An identifier that refers to a declaration.
+#[derive(Debug, Clone)]
+pub struct Reference {
+    pub name: String,
+    pub span: Span,
+    pub scope: ScopeId,
+    pub kind: ReferenceKind,
+    /// Number of arguments when the reference is called, to pick between overloads.
+    pub arguments: Option<usize>,
+    pub access: Access,
+}
+
+/// Whether a reference reads or writes what it refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    /// Assigned, incremented, decremented or deleted.
+    Write,
+}
+
+#[derive(Debug, Clone)]
+pub enum ReferenceKind {
+    /// A plain name, looked up in the enclosing scopes.
+    Name,
+    /// A member of the value of the expression, like `b` in `a.b`.
+    Member(Expr),
+    /// The name of an argument in a `f({name: value})` call of the expression.
+    NamedArgument(Expr),
+    /// A symbol of the file imported by the given import, like `X` in `import {X as Y}`.
+    Imported(usize),
+}
+
+/// Just enough of an expression to compute its type.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Name {
+        name: String,
+        offset: usize,
+    },
+    Member {
+        receiver: Box<Expr>,
+        name: String,
+    },
+    Index {
+        receiver: Box<Expr>,
+        slice: bool,
+    },
+    Call {
+        callee: Box<Expr>,
+    },
+    /// A type used as an expression, like `address` in `address(this)`.
+    Type(TypeRef),
+    /// A literal of the given type.
+    Value(TypeRef),
+    New(TypeRef),
+    /// `type(T)`.
+    TypeOf(TypeRef),
+    Unknown,
+}
+
+impl Expr {
+    /// The expression as a dotted path, if it is one.
+    pub fn path(&self) -> Option<String> {
+        match self {
+            Self::Name { name, .. } => Some(name.clone()),
+            Self::Member { receiver, name } => Some(format!("{}.{name}", receiver.path()?)),
+            _ => None,
+        }
+    }
+}
+
+/// A type name as written in the source.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypeRef {
+    Elementary(String),
+    Path(Box<Expr>),
+    Array(Box<TypeRef>),
+    Mapping(Box<TypeRef>, Box<TypeRef>),
+    Function(String),
+}
+
+impl fmt::Display for TypeRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Elementary(name) | Self::Function(name) => f.write_str(name),
+            Self::Path(path) => f.write_str(&path.path().unwrap_or_default()),
+            Self::Array(element) => write!(f, "{element}[]"),
+            Self::Mapping(key, value) => write!(f, "mapping({key} => {value})"),
+        }
+    }
+}
+
+impl SourceModel {
+    pub fn build(tree: Node) -> Self {
+        let root = SyntaxNode::root(tree);
+        let mut builder = Builder::default();
+        let span = Span::new(0, root.full_range().end.utf8);
+        let scope = builder.scope(ScopeKind::File, span, None, None);
+        builder.children(
+            &root,
+            Ctx {
+                scope,
+                container: None,
+            },
+        );
+        builder.model
+    }
+
+    /// The closest scope of `kind` around `scope`, including itself.
+    pub fn enclosing(&self, scope: ScopeId, kind: ScopeKind) -> Option<ScopeId> {
+        let mut scope = Some(scope);
+        while let Some(id) = scope {
+            if self.scopes[id].kind == kind {
+                return Some(id);
+            }
+            scope = self.scopes[id].parent;
+        }
+        None
+    }
+
+    /// The innermost scope around `offset`.
+    pub fn scope_at(&self, offset: usize) -> ScopeId {
+        // Scopes are created parents first, so the last one that matches is the innermost.
+        self.scopes
+            .iter()
+            .rposition(|scope| scope.span.contains(offset))
+            .unwrap_or(0)
+    }
+
+    /// The declaration whose name is at `offset`.
+    pub fn declaration_at(&self, offset: usize) -> Option<DeclId> {
+        self.declarations
+            .iter()
+            .position(|decl| !decl.name.is_empty() && decl.name_span.contains(offset))
+    }
+
+    pub fn reference_at(&self, offset: usize) -> Option<&Reference> {
+        self.references
+            .iter()
+            .find(|reference| reference.span.contains(offset))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Ctx {
+    scope: ScopeId,
+    container: Option<DeclId>,
+}
+
+#[derive(Debug, Default)]
+struct Builder {
+    model: SourceModel,
+}
+
+impl Builder {
+    fn scope(
+        &mut self,
+        kind: ScopeKind,
+        span: Span,
+        parent: Option<ScopeId>,
+        owner: Option<DeclId>,
+    ) -> ScopeId {
+        self.model.scopes.push(Scope {
+            kind,
+            span,
+            parent,
+            owner,
+            declarations: Vec::new(),
+        });
+        self.model.scopes.len() - 1
+    }
+
+    /// Adds a declaration, without making it visible in any scope.
+    fn declare(
+        &mut self,
+        kind: DeclKind,
+        name: Option<&SyntaxNode>,
+        node: &SyntaxNode,
+        ctx: Ctx,
+        signature: String,
+    ) -> DeclId {
+        self.model.declarations.push(Declaration {
+            kind,
+            name: name.map(|name| name.text()).unwrap_or_default(),
+            name_span: name.map_or_else(|| node.span(), |name| name.span()),
+            span: node.span(),
+            container: ctx.container,
+            scope: ctx.scope,
+            visible_from: 0,
+            ty: None,
+            signature,
+            natspec: NatSpec::of(node),
+            attributes: Vec::new(),
+            parameters: Vec::new(),
+            returns: Vec::new(),
+            members: Vec::new(),
+            bases: Vec::new(),
+            imported: None,
+        });
+        self.model.declarations.len() - 1
+    }
+
+    /// Makes a declaration visible by name in `scope`.
+    fn bind(&mut self, scope: ScopeId, decl: DeclId) {
+        if !self.model.declarations[decl].name.is_empty() {
+            self.model.scopes[scope].declarations.push(decl);
+        }
+    }
+
+    fn reference(&mut self, name: &SyntaxNode, ctx: Ctx, kind: ReferenceKind) {
+        let text = name.text();
+        if text.is_empty() {
+            return;
+        }
+        self.model.references.push(Reference {
+            name: text,
+            span: name.span(),
+            scope: ctx.scope,
+            kind,
+            arguments: None,
+            access: Access::Read,
+        });
+    }
+
+    fn children(&mut self, node: &SyntaxNode, ctx: Ctx) {
+        for child in node.children() {
+            self.visit(&child, ctx);
+        }
+    }
+
+    fn child(&mut self, node: &SyntaxNode, label: EdgeLabel, ctx: Ctx) {
+        if let Some(child) = node.child(label) {
+            self.visit(&child, ctx);
+        }
+    }
+
+    fn visit(&mut self, node: &SyntaxNode, ctx: Ctx) {
+        use NonterminalKind as K;
+
+        let Some(kind) = node.nonterminal_kind() else {
+            return;
+        };
+        match kind {
+            K::ContractDefinition | K::InterfaceDefinition | K::LibraryDefinition => {
+                self.contract(node, kind, ctx)
+            }
+            K::FunctionDefinition
+            | K::ConstructorDefinition
+            | K::ModifierDefinition
+            | K::FallbackFunctionDefinition
+            | K::ReceiveFunctionDefinition
+            | K::UnnamedFunctionDefinition => self.function(node, kind, ctx),
+            K::StateVariableDefinition | K::ConstantDefinition => {
+                self.state_variable(node, kind, ctx)
+            }
+            K::StructDefinition => self.structure(node, ctx),
+            K::EnumDefinition => self.enumeration(node, ctx),
+            K::EventDefinition | K::ErrorDefinition => self.event(node, kind, ctx),
+            K::UserDefinedValueTypeDefinition => self.value_type(node, ctx),
+            K::Block | K::ForStatement | K::YulBlock | K::YulForStatement => {
+                let scope = self.scope(ScopeKind::Block, node.span(), Some(ctx.scope), None);
+                self.children(node, Ctx { scope, ..ctx });
+            }
+            K::ImportDirective => self.import(node, ctx),
+            K::UsingDirective => self.using(node, ctx),
+            K::TryStatement => self.try_statement(node, ctx),
+            K::CatchClause => self.catch_clause(node, ctx),
+            K::VariableDeclarationStatement => self.variable_declaration(node, ctx),
+            K::TupleDeconstructionStatement => self.tuple_deconstruction(node, ctx),
+            K::YulVariableDeclarationStatement => self.yul_variables(node, ctx),
+            K::YulFunctionDefinition => self.yul_function(node, ctx),
+            K::Expression => match node.child(EdgeLabel::Variant) {
+                Some(name) if name.terminal_kind() == Some(TerminalKind::Identifier) => {
+                    self.reference(&name, ctx, ReferenceKind::Name)
+                }
+                _ => self.children(node, ctx),
+            },
+            K::IdentifierPath => self.path(node, ctx),
+            K::MemberAccessExpression => {
+                self.child(node, EdgeLabel::Operand, ctx);
+                if let (Some(operand), Some(member)) = (
+                    node.child(EdgeLabel::Operand),
+                    node.child(EdgeLabel::Member),
+                ) {
+                    self.reference(&member, ctx, ReferenceKind::Member(expr(&operand)));
+                }
+            }
+            K::FunctionCallExpression => self.call(node, ctx),
+            K::AssignmentExpression => {
+                self.assigned(node, EdgeLabel::LeftOperand, ctx);
+                self.child(node, EdgeLabel::RightOperand, ctx);
+            }
+            K::PrefixExpression | K::PostfixExpression => {
+                let operator = node.child(EdgeLabel::Operator);
+                match operator.and_then(|operator| operator.terminal_kind()) {
+                    Some(
+                        TerminalKind::PlusPlus
+                        | TerminalKind::MinusMinus
+                        | TerminalKind::DeleteKeyword,
+                    ) => self.assigned(node, EdgeLabel::Operand, ctx),
+                    _ => self.children(node, ctx),
+                }
+            }
+            K::YulVariableAssignmentStatement => {
+                self.assigned(node, EdgeLabel::Variables, ctx);
+                self.child(node, EdgeLabel::Expression, ctx);
+            }
+            K::YulPath => {
+                if let Some(first) = node.items().next() {
+                    self.reference(&first.variant(), ctx, ReferenceKind::Name);
+                }
+            }
+            _ => self.children(node, ctx),
+        }
+    }
+
+    fn import(&mut self, node: &SyntaxNode, ctx: Ctx) {
+        let Some(clause) = node.child(EdgeLabel::Clause).map(|clause| clause.variant()) else {
+            return;
+        };
+        let Some(path) = clause.child(EdgeLabel::Path) else {
+            return;
+        };
+        let index = self.model.imports.len();
+        let alias = clause
+            .child(EdgeLabel::Alias)
+            .and_then(|alias| alias.child(EdgeLabel::Identifier));
+        self.model.imports.push(Import {
+            path: path.text().trim_matches(['"', '\'']).to_string(),
+            path_span: path.span(),
+            wildcard: clause.is(NonterminalKind::PathImport) && alias.is_none(),
+        });
+
+        if let Some(alias) = alias {
+            let id = self.declare(DeclKind::Module, Some(&alias), node, ctx, node.display());
+            self.model.declarations[id].imported = Some(Imported {
+                import: index,
+                name: None,
+            });
+            self.bind(ctx.scope, id);
+        }
+
+        let symbols = clause.child(EdgeLabel::Symbols);
+        for symbol in symbols.iter().flat_map(|symbols| symbols.items()) {
+            let Some(name) = symbol.child(EdgeLabel::Name) else {
+                continue;
+            };
+            let alias = symbol
+                .child(EdgeLabel::Alias)
+                .and_then(|alias| alias.child(EdgeLabel::Identifier));
+            if alias.is_some() {
+                self.reference(&name, ctx, ReferenceKind::Imported(index));
+            }
+            let local = alias.as_ref().unwrap_or(&name);
+            let id = self.declare(
+                DeclKind::Import,
+                Some(local),
+                &symbol,
+                ctx,
+                symbol.display(),
+            );
+            self.model.declarations[id].imported = Some(Imported {
+                import: index,
+                name: Some(name.text()),
+            });
+            self.bind(ctx.scope, id);
+        }
+    }
+
+    fn using(&mut self, node: &SyntaxNode, ctx: Ctx) {
+        self.children(node, ctx);
+        let Some(clause) = node.child(EdgeLabel::Clause).map(|clause| clause.variant()) else {
+            return;
+        };
+        let (library, functions) = if clause.is(NonterminalKind::IdentifierPath) {
+            (Some(path_expr(&clause)), Vec::new())
+        } else {
+            let symbols = clause.child(EdgeLabel::Symbols);
+            let functions = symbols
+                .iter()
+                .flat_map(|symbols| symbols.items())
+                .filter(|symbol| symbol.child(EdgeLabel::Alias).is_none())
+                .filter_map(|symbol| symbol.child(EdgeLabel::Name))
+                .map(|name| path_expr(&name))
+                .collect();
+            (None, functions)
+        };
+        let target = node
+            .child(EdgeLabel::Target)
+            .map(|target| target.variant())
+            .and_then(|target| type_ref(&target));
+        self.model.usings.push(Using {
+            library,
+            functions,
+            target,
+            scope: ctx.scope,
+            global: node.child(EdgeLabel::GlobalKeyword).is_some(),
+        });
+    }
+
+    fn contract(&mut self, node: &SyntaxNode, kind: NonterminalKind, ctx: Ctx) {
+        let decl_kind = match kind {
+            NonterminalKind::InterfaceDefinition => DeclKind::Interface,
+            NonterminalKind::LibraryDefinition => DeclKind::Library,
+            _ => DeclKind::Contract,
+        };
+        let header = node
+            .children()
+            .take_while(|child| child.label != Some(EdgeLabel::OpenBrace))
+            .flat_map(|child| child.tokens().map(|token| token.text()).collect::<Vec<_>>());
+        let signature = join_tokens(header);
+        let name = node.child(EdgeLabel::Name);
+        let id = self.declare(decl_kind, name.as_ref(), node, ctx, signature);
+        self.bind(ctx.scope, id);
+
+        if let Some(inheritance) = node.child(EdgeLabel::Inheritance) {
+            self.visit(&inheritance, ctx);
+            let bases = inheritance
+                .child(EdgeLabel::Types)
+                .map(|types| {
+                    types
+                        .items()
+                        .filter_map(|base| base.child(EdgeLabel::TypeName))
+                        .map(|path| path_expr(&path))
+                        .collect()
+                })
+                .unwrap_or_default();
+            self.model.declarations[id].bases = bases;
+        }
+
+        let scope = self.scope(ScopeKind::Contract, node.span(), Some(ctx.scope), Some(id));
+        let first = self.model.declarations.len();
+        self.child(
+            node,
+            EdgeLabel::Members,
+            Ctx {
+                scope,
+                container: Some(id),
+            },
+        );
+        self.collect_members(id, first);
+    }
+
+    /// Records the declarations added since `first` that belong to `id` as its members.
+    fn collect_members(&mut self, id: DeclId, first: DeclId) {
+        let members = (first..self.model.declarations.len())
+            .filter(|&member| self.model.declarations[member].container == Some(id))
+            .collect();
+        self.model.declarations[id].members = members;
+    }
+
+    fn function(&mut self, node: &SyntaxNode, kind: NonterminalKind, ctx: Ctx) {
+        use NonterminalKind as K;
+
+        let (decl_kind, name) = match kind {
+            K::FunctionDefinition => (
+                DeclKind::Function,
+                node.child(EdgeLabel::Name).map(|name| name.variant()),
+            ),
+            K::ModifierDefinition => (DeclKind::Modifier, node.child(EdgeLabel::Name)),
+            K::ConstructorDefinition => (
+                DeclKind::Constructor,
+                node.child(EdgeLabel::ConstructorKeyword),
+            ),
+            K::ReceiveFunctionDefinition => {
+                (DeclKind::Receive, node.child(EdgeLabel::ReceiveKeyword))
+            }
+            K::FallbackFunctionDefinition => {
+                (DeclKind::Fallback, node.child(EdgeLabel::FallbackKeyword))
+            }
+            _ => (DeclKind::Fallback, node.child(EdgeLabel::FunctionKeyword)),
+        };
+        let header = node
+            .children()
+            .filter(|child| child.label != Some(EdgeLabel::Body))
+            .flat_map(|child| child.tokens().map(|token| token.text()).collect::<Vec<_>>());
+        let signature = join_tokens(header);
+        let id = self.declare(decl_kind, name.as_ref(), node, ctx, signature);
+        if matches!(decl_kind, DeclKind::Function | DeclKind::Modifier) {
+            self.bind(ctx.scope, id);
+        }
+        self.model.declarations[id].attributes = attributes(node);
+
+        let scope = self.scope(ScopeKind::Function, node.span(), Some(ctx.scope), Some(id));
+        let inner = Ctx {
+            scope,
+            container: Some(id),
+        };
+        let parameters = node
+            .child(EdgeLabel::Parameters)
+            .and_then(|parameters| parameters.child(EdgeLabel::Parameters));
+        self.model.declarations[id].parameters = self.parameters(parameters, inner, true);
+        let returns = node
+            .child(EdgeLabel::Returns)
+            .and_then(|returns| returns.child(EdgeLabel::Variables))
+            .and_then(|variables| variables.child(EdgeLabel::Parameters));
+        self.model.declarations[id].returns = self.parameters(returns, inner, true);
+
+        self.child(node, EdgeLabel::Attributes, inner);
+        self.child(node, EdgeLabel::Body, inner);
+    }
+
+    /// Declares the items of a parameter list, visible in the scope of `ctx` if `bind` is set.
+    fn parameters(&mut self, list: Option<SyntaxNode>, ctx: Ctx, bind: bool) -> Vec<DeclId> {
+        let Some(list) = list else {
+            return Vec::new();
+        };
+        list.items()
+            .map(|parameter| {
+                self.child(&parameter, EdgeLabel::TypeName, ctx);
+                let name = parameter.child(EdgeLabel::Name);
+                let id = self.declare(
+                    DeclKind::Parameter,
+                    name.as_ref(),
+                    &parameter,
+                    ctx,
+                    parameter.display(),
+                );
+                self.model.declarations[id].ty = parameter
+                    .child(EdgeLabel::TypeName)
+                    .and_then(|t| type_ref(&t));
+                self.model.declarations[id].attributes = location(&parameter);
+                if bind {
+                    self.bind(ctx.scope, id);
+                }
+                id
+            })
+            .collect()
+    }
+
+    fn state_variable(&mut self, node: &SyntaxNode, kind: NonterminalKind, ctx: Ctx) {
+        let attributes = match kind {
+            NonterminalKind::ConstantDefinition => vec!["constant".to_string()],
+            _ => attributes(node),
+        };
+        let constant = attributes.iter().any(|a| a == "constant");
+        // The value of a constant is worth showing, the initializer of a variable isn't.
+        let header = node
+            .children()
+            .filter(|child| match child.label {
+                Some(EdgeLabel::Semicolon) => false,
+                Some(EdgeLabel::Value) => constant,
+                _ => true,
+            })
+            .flat_map(|child| child.tokens().map(|token| token.text()).collect::<Vec<_>>());
+        let signature = join_tokens(header);
+        let decl_kind = match kind {
+            NonterminalKind::ConstantDefinition => DeclKind::Constant,
+            _ => DeclKind::StateVariable,
+        };
+        let name = node.child(EdgeLabel::Name);
+        let id = self.declare(decl_kind, name.as_ref(), node, ctx, signature);
+        self.bind(ctx.scope, id);
+        let decl = &mut self.model.declarations[id];
+        decl.attributes = attributes;
+        decl.ty = node.child(EdgeLabel::TypeName).and_then(|t| type_ref(&t));
+        self.children(node, ctx);
+    }
+
+    fn structure(&mut self, node: &SyntaxNode, ctx: Ctx) {
+        let name = node.child(EdgeLabel::Name);
+        let id = self.declare(DeclKind::Struct, name.as_ref(), node, ctx, String::new());
+        self.bind(ctx.scope, id);
+
+        let inner = Ctx {
+            container: Some(id),
+            ..ctx
+        };
+        let mut fields = Vec::new();
+        let members = node.child(EdgeLabel::Members);
+        for member in members.iter().flat_map(|members| members.items()) {
+            self.child(&member, EdgeLabel::TypeName, ctx);
+            let field = member
+                .children()
+                .filter(|child| child.label != Some(EdgeLabel::Semicolon))
+                .flat_map(|child| child.tokens().map(|token| token.text()).collect::<Vec<_>>());
+            let field = join_tokens(field);
+            let name = member.child(EdgeLabel::Name);
+            let member_id = self.declare(
+                DeclKind::StructMember,
+                name.as_ref(),
+                &member,
+                inner,
+                field.clone(),
+            );
+            self.model.declarations[member_id].ty =
+                member.child(EdgeLabel::TypeName).and_then(|t| type_ref(&t));
+            self.model.declarations[id].members.push(member_id);
+            fields.push(format!("    {field};\n"));
+        }
+
+        let name = self.model.declarations[id].name.clone();
+        self.model.declarations[id].signature = format!("struct {name} {{\n{}}}", fields.concat());
+    }
+
+    fn enumeration(&mut self, node: &SyntaxNode, ctx: Ctx) {
+        let name = node.child(EdgeLabel::Name);
+        let id = self.declare(DeclKind::Enum, name.as_ref(), node, ctx, node.display());
+        self.bind(ctx.scope, id);
+
+        let enum_name = self.model.declarations[id].name.clone();
+        let inner = Ctx {
+            container: Some(id),
+            ..ctx
+        };
+        let members = node.child(EdgeLabel::Members);
+        for member in members.iter().flat_map(|members| members.items()) {
+            let signature = format!("{enum_name}.{}", member.text());
+            let member_id = self.declare(
+                DeclKind::EnumMember,
+                Some(&member),
+                &member,
+                inner,
+                signature,
+            );
+            self.model.declarations[id].members.push(member_id);
+        }
+    }
+
+    fn event(&mut self, node: &SyntaxNode, kind: NonterminalKind, ctx: Ctx) {
+        let (decl_kind, label) = match kind {
+            NonterminalKind::EventDefinition => (DeclKind::Event, EdgeLabel::Parameters),
+            _ => (DeclKind::Error, EdgeLabel::Members),
+        };
+        let signature = join_tokens(
+            node.children()
+                .filter(|child| child.label != Some(EdgeLabel::Semicolon))
+                .flat_map(|child| child.tokens().map(|token| token.text()).collect::<Vec<_>>()),
+        );
+        let name = node.child(EdgeLabel::Name);
+        let id = self.declare(decl_kind, name.as_ref(), node, ctx, signature);
+        self.bind(ctx.scope, id);
+
+        let parameters = node
+            .child(label)
+            .and_then(|parameters| parameters.child(EdgeLabel::Parameters));
+        let inner = Ctx {
+            container: Some(id),
+            ..ctx
+        };
+        self.model.declarations[id].parameters = self.parameters(parameters, inner, false);
+    }
+
+    fn value_type(&mut self, node: &SyntaxNode, ctx: Ctx) {
+        let signature = join_tokens(
+            node.tokens()
+                .map(|token| token.text())
+                .filter(|token| token != ";"),
+        );
+        let name = node.child(EdgeLabel::Name);
+        let id = self.declare(
+            DeclKind::UserDefinedValueType,
+            name.as_ref(),
+            node,
+            ctx,
+            signature,
+        );
+        self.bind(ctx.scope, id);
+        self.model.declarations[id].ty = node
+            .child(EdgeLabel::ValueType)
+            .map(|value_type| TypeRef::Elementary(elementary(&value_type)));
+    }
+
+    fn variable_declaration(&mut self, node: &SyntaxNode, ctx: Ctx) {
+        self.child(node, EdgeLabel::VariableType, ctx);
+        self.child(node, EdgeLabel::Value, ctx);
+
+        let signature = join_tokens(
+            node.children()
+                .filter(|child| {
+                    matches!(
+                        child.label,
+                        Some(
+                            EdgeLabel::VariableType | EdgeLabel::StorageLocation | EdgeLabel::Name
+                        )
+                    )
+                })
+                .flat_map(|child| child.tokens().map(|token| token.text()).collect::<Vec<_>>()),
+        );
+        let name = node.child(EdgeLabel::Name);
+        let ty = node
+            .child(EdgeLabel::VariableType)
+            .and_then(|t| type_ref(&t));
+        let id = self.local(
+            DeclKind::LocalVariable,
+            name.as_ref(),
+            node,
+            ctx,
+            signature,
+            ty,
+        );
+        self.model.declarations[id].attributes = location(node);
+    }
+
+    /// Declares a variable visible from the end of the statement `node`.
+    fn local(
+        &mut self,
+        kind: DeclKind,
+        name: Option<&SyntaxNode>,
+        node: &SyntaxNode,
+        ctx: Ctx,
+        signature: String,
+        ty: Option<TypeRef>,
+    ) -> DeclId {
+        let id = self.declare(kind, name, node, ctx, signature);
+        let decl = &mut self.model.declarations[id];
+        decl.visible_from = node.span().end;
+        decl.ty = ty;
+        self.bind(ctx.scope, id);
+        id
+    }
+
+    fn tuple_deconstruction(&mut self, node: &SyntaxNode, ctx: Ctx) {
+        self.child(node, EdgeLabel::Expression, ctx);
+
+        // Without `var`, untyped members assign to existing variables.
+        let declares = node.child(EdgeLabel::VarKeyword).is_some();
+        let elements = node.child(EdgeLabel::Elements);
+        for element in elements.iter().flat_map(|elements| elements.items()) {
+            let Some(member) = element.child(EdgeLabel::Member).map(|m| m.variant()) else {
+                continue;
+            };
+            let name = member.child(EdgeLabel::Name);
+            if member.is(NonterminalKind::TypedTupleMember) || declares {
+                self.child(&member, EdgeLabel::TypeName, ctx);
+                let ty = member.child(EdgeLabel::TypeName).and_then(|t| type_ref(&t));
+                let signature = member.display();
+                let id = self.local(
+                    DeclKind::LocalVariable,
+                    name.as_ref(),
+                    node,
+                    ctx,
+                    signature,
+                    ty,
+                );
+                self.model.declarations[id].attributes = location(&member);
+            } else if let Some(name) = name {
+                self.reference(&name, ctx, ReferenceKind::Name);
+                if let Some(reference) = self.model.references.last_mut() {
+                    reference.access = Access::Write;
+                }
+            }
+        }
+    }
+
+    fn try_statement(&mut self, node: &SyntaxNode, ctx: Ctx) {
+        self.child(node, EdgeLabel::Expression, ctx);
+
+        // The returned values are only visible in the success block.
+        if let Some(body) = node.child(EdgeLabel::Body) {
+            let returns = node.child(EdgeLabel::Returns);
+            let scope = self.scope(ScopeKind::Block, body.span(), Some(ctx.scope), None);
+            let inner = Ctx { scope, ..ctx };
+            let parameters = returns
+                .and_then(|returns| returns.child(EdgeLabel::Variables))
+                .and_then(|variables| variables.child(EdgeLabel::Parameters));
+            self.parameters(parameters, inner, true);
+            self.visit(&body, inner);
+        }
+
+        self.child(node, EdgeLabel::CatchClauses, ctx);
+    }
+
+    fn catch_clause(&mut self, node: &SyntaxNode, ctx: Ctx) {
+        let scope = self.scope(ScopeKind::Block, node.span(), Some(ctx.scope), None);
+        let inner = Ctx { scope, ..ctx };
+        let parameters = node
+            .child(EdgeLabel::Error)
+            .and_then(|error| error.child(EdgeLabel::Parameters))
+            .and_then(|parameters| parameters.child(EdgeLabel::Parameters));
+        self.parameters(parameters, inner, true);
+        self.child(node, EdgeLabel::Body, inner);
+    }
+
+    fn yul_variables(&mut self, node: &SyntaxNode, ctx: Ctx) {
+        self.child(node, EdgeLabel::Value, ctx);
+
+        let variables = node.child(EdgeLabel::Variables);
+        for variable in variables.iter().flat_map(|variables| variables.items()) {
+            let signature = format!("let {}", variable.text());
+            self.local(
+                DeclKind::YulVariable,
+                Some(&variable),
+                node,
+                ctx,
+                signature,
+                None,
+            );
+        }
+    }
+
+    fn yul_function(&mut self, node: &SyntaxNode, ctx: Ctx) {
+        let signature = join_tokens(
+            node.children()
+                .filter(|child| child.label != Some(EdgeLabel::Body))
+                .flat_map(|child| child.tokens().map(|token| token.text()).collect::<Vec<_>>()),
+        );
+        let name = node.child(EdgeLabel::Name);
+        let id = self.declare(DeclKind::YulFunction, name.as_ref(), node, ctx, signature);
+        self.bind(ctx.scope, id);
+
+        let scope = self.scope(ScopeKind::Function, node.span(), Some(ctx.scope), Some(id));
+        let inner = Ctx {
+            scope,
+            container: Some(id),
+        };
+        let parameters = node
+            .child(EdgeLabel::Parameters)
+            .and_then(|parameters| parameters.child(EdgeLabel::Parameters));
+        let returns = node
+            .child(EdgeLabel::Returns)
+            .and_then(|returns| returns.child(EdgeLabel::Variables));
+        let mut yul_parameters = |list: Option<SyntaxNode>| -> Vec<DeclId> {
+            list.iter()
+                .flat_map(|list| list.items())
+                .map(|variable| {
+                    let signature = variable.text();
+                    let id = self.declare(
+                        DeclKind::YulVariable,
+                        Some(&variable),
+                        &variable,
+                        inner,
+                        signature,
+                    );
+                    self.bind(scope, id);
+                    id
+                })
+                .collect()
+        };
+        let parameters = yul_parameters(parameters);
+        let returns = yul_parameters(returns);
+        let decl = &mut self.model.declarations[id];
+        decl.parameters = parameters;
+        decl.returns = returns;
+
+        self.child(node, EdgeLabel::Body, inner);
+    }
+
+    /// `A.B.C` references `A`, then `B` as a member of `A` and so on.
+    fn path(&mut self, node: &SyntaxNode, ctx: Ctx) {
+        let mut receiver: Option<Expr> = None;
+        for item in node.items() {
+            let name = item.text();
+            let kind = match &receiver {
+                Some(receiver) => ReferenceKind::Member(receiver.clone()),
+                None => ReferenceKind::Name,
+            };
+            self.reference(&item, ctx, kind);
+            receiver = Some(match receiver {
+                Some(receiver) => Expr::Member {
+                    receiver: Box::new(receiver),
+                    name,
+                },
+                None => Expr::Name {
+                    name,
+                    offset: item.span().start,
+                },
+            });
+        }
+    }
+
+    /// Visits the `label` child of `node`, marking the references to what it assigns as writes.
+    fn assigned(&mut self, node: &SyntaxNode, label: EdgeLabel, ctx: Ctx) {
+        let Some(target) = node.child(label) else {
+            return;
+        };
+        let first = self.model.references.len();
+        self.visit(&target, ctx);
+        let mut spans = Vec::new();
+        assigned_spans(&target, &mut spans);
+        for reference in &mut self.model.references[first..] {
+            if spans.contains(&reference.span) {
+                reference.access = Access::Write;
+            }
+        }
+    }
+
+    fn call(&mut self, node: &SyntaxNode, ctx: Ctx) {
+        let first = self.model.references.len();
+        self.child(node, EdgeLabel::Operand, ctx);
+        let Some(operand) = node.child(EdgeLabel::Operand) else {
+            return self.children(node, ctx);
+        };
+        let callee = expr(&operand);
+
+        let arguments = node
+            .child(EdgeLabel::Arguments)
+            .map(|arguments| arguments.variant());
+        let mut count = 0;
+        if let Some(arguments) = arguments {
+            if arguments.is(NonterminalKind::NamedArgumentsDeclaration) {
+                let named = arguments
+                    .child(EdgeLabel::Arguments)
+                    .and_then(|group| group.child(EdgeLabel::Arguments));
+                for argument in named.iter().flat_map(|named| named.items()) {
+                    count += 1;
+                    if let Some(name) = argument.child(EdgeLabel::Name) {
+                        self.reference(&name, ctx, ReferenceKind::NamedArgument(callee.clone()));
+                    }
+                    self.child(&argument, EdgeLabel::Value, ctx);
+                }
+            } else {
+                count = arguments
+                    .child(EdgeLabel::Arguments)
+                    .map_or(0, |positional| positional.items().count());
+                self.visit(&arguments, ctx);
+            }
+        }
+
+        // The reference naming the callee is the last one of the operand: `f`, `a.f` or `new C`.
+        let end = callee_end(&operand);
+        if let Some(reference) = self.model.references[first..]
+            .iter_mut()
+            .rev()
+            .find(|reference| reference.span.end == end)
+        {
+            reference.arguments = Some(count);
+        }
+    }
+}
+
+/// End of the expression that is called, looking through call options like `{value: 1}`.
+fn callee_end(operand: &SyntaxNode) -> usize {
+    let node = operand.variant();
+    match node.child(EdgeLabel::Operand) {
+        Some(inner) if node.is(NonterminalKind::CallOptionsExpression) => callee_end(&inner),
+        _ => operand.span().end,
+    }
+}
+
+/// Spans of the names whose value changes when `target` is assigned: `a` in `a[i]`, `b` in
+/// `a.b`, and every element of a tuple.
+fn assigned_spans(target: &SyntaxNode, spans: &mut Vec<Span>) {
+    use NonterminalKind as K;
+
+    match target.nonterminal_kind() {
+        None => spans.push(target.span()),
+        Some(K::Expression) => assigned_spans(&target.variant(), spans),
+        Some(K::MemberAccessExpression) => {
+            spans.extend(target.child(EdgeLabel::Member).map(|member| member.span()))
+        }
+        Some(K::IndexAccessExpression) => {
+            if let Some(operand) = target.child(EdgeLabel::Operand) {
+                assigned_spans(&operand, spans);
+            }
+        }
+        Some(K::TupleExpression) => {
+            let items = target.child(EdgeLabel::Items);
+            for item in items.iter().flat_map(|items| items.items()) {
+                if let Some(expression) = item.child(EdgeLabel::Expression) {
+                    assigned_spans(&expression, spans);
+                }
+            }
+        }
+        Some(K::YulPaths) => {
+            for path in target.items() {
+                spans.extend(path.items().next().map(|first| first.variant().span()));
+            }
+        }
+        Some(_) => {}
+    }
+}
+
+/// Keywords among the attributes of a function or state variable.
+fn attributes(node: &SyntaxNode) -> Vec<String> {
+    let Some(attributes) = node.child(EdgeLabel::Attributes) else {
+        return Vec::new();
+    };
+    attributes
+        .items()
+        .filter_map(|attribute| {
+            let attribute = attribute.variant();
+            match attribute.nonterminal_kind() {
+                None => Some(attribute.text()),
+                Some(NonterminalKind::OverrideSpecifier) => Some("override".to_string()),
+                Some(_) => None,
+            }
+        })
+        .collect()
+}
+
+/// The data location of a variable, like `memory`, when it's written.
+fn location(node: &SyntaxNode) -> Vec<String> {
+    node.child(EdgeLabel::StorageLocation)
+        .map(|location| location.display())
+        .into_iter()
+        .collect()
+}
+
+/// The canonical name of an elementary type, `uint` being `uint256` and so on.
+fn elementary(node: &SyntaxNode) -> String {
+    let name = node.display();
+    match name.as_str() {
+        "uint" => "uint256".to_string(),
+        "int" => "int256".to_string(),
+        "byte" => "bytes1".to_string(),
+        "ufixed" => "ufixed128x18".to_string(),
+        "fixed" => "fixed128x18".to_string(),
+        _ => name,
+    }
+}
+
+fn path_expr(path: &SyntaxNode) -> Expr {
+    path.items()
+        .fold(Expr::Unknown, |receiver, item| match receiver {
+            Expr::Unknown => Expr::Name {
+                name: item.text(),
+                offset: item.span().start,
+            },
+            receiver => Expr::Member {
+                receiver: Box::new(receiver),
+                name: item.text(),
+            },
+        })
+}
+
+/// Reads a `TypeName`, or any of the nodes wrapping one.
+fn type_ref(node: &SyntaxNode) -> Option<TypeRef> {
+    use NonterminalKind as K;
+
+    match node.nonterminal_kind()? {
+        K::TypeName | K::VariableDeclarationType | K::MappingKeyType => {
+            type_ref(&node.child(EdgeLabel::Variant)?)
+        }
+        K::ElementaryType => Some(TypeRef::Elementary(elementary(node))),
+        K::IdentifierPath => Some(TypeRef::Path(Box::new(path_expr(node)))),
+        K::ArrayTypeName => Some(TypeRef::Array(Box::new(type_ref(
+            &node.child(EdgeLabel::Operand)?,
+        )?))),
+        K::MappingType => {
+            let key = node.child(EdgeLabel::KeyType)?.child(EdgeLabel::KeyType)?;
+            let value = node
+                .child(EdgeLabel::ValueType)?
+                .child(EdgeLabel::TypeName)?;
+            Some(TypeRef::Mapping(
+                Box::new(type_ref(&key)?),
+                Box::new(type_ref(&value)?),
+            ))
+        }
+        K::FunctionType => Some(TypeRef::Function(node.display())),
+        _ => None,
+    }
+}
+
+/// Reads an `Expression`, or the node of one of its variants.
+fn expr(node: &SyntaxNode) -> Expr {
+    use NonterminalKind as K;
+
+    let node = match node.nonterminal_kind() {
+        Some(K::Expression) => match node.child(EdgeLabel::Variant) {
+            Some(variant) => variant,
+            None => return Expr::Unknown,
+        },
+        _ => node.clone(),
+    };
+    let child = |label| {
+        node.child(label)
+            .map_or(Expr::Unknown, |child| expr(&child))
+    };
+    let child_type = |label| node.child(label).and_then(|child| type_ref(&child));
+
+    match node.nonterminal_kind() {
+        None => match node.terminal_kind() {
+            Some(TerminalKind::Identifier) => Expr::Name {
+                name: node.text(),
+                offset: node.span().start,
+            },
+            Some(TerminalKind::PayableKeyword) => {
+                Expr::Type(TypeRef::Elementary("address payable".to_string()))
+            }
+            Some(TerminalKind::TrueKeyword | TerminalKind::FalseKeyword) => {
+                Expr::Value(TypeRef::Elementary("bool".to_string()))
+            }
+            _ => Expr::Unknown,
+        },
+        Some(K::MemberAccessExpression) => match node.child(EdgeLabel::Member) {
+            Some(member) => Expr::Member {
+                receiver: Box::new(child(EdgeLabel::Operand)),
+                name: member.text(),
+            },
+            None => Expr::Unknown,
+        },
+        Some(K::IndexAccessExpression) => Expr::Index {
+            receiver: Box::new(child(EdgeLabel::Operand)),
+            slice: node.child(EdgeLabel::End).is_some(),
+        },
+        Some(K::FunctionCallExpression) => Expr::Call {
+            callee: Box::new(child(EdgeLabel::Operand)),
+        },
+        Some(K::CallOptionsExpression) => child(EdgeLabel::Operand),
+        Some(K::TupleExpression) => {
+            let items: Vec<_> = node
+                .child(EdgeLabel::Items)
+                .map(|items| items.items().collect())
+                .unwrap_or_default();
+            match items.as_slice() {
+                [item] => item
+                    .child(EdgeLabel::Expression)
+                    .map_or(Expr::Unknown, |item| expr(&item)),
+                _ => Expr::Unknown,
+            }
+        }
+        Some(K::ElementaryType) => Expr::Type(TypeRef::Elementary(elementary(&node))),
+        Some(K::NewExpression) => child_type(EdgeLabel::TypeName).map_or(Expr::Unknown, Expr::New),
+        Some(K::TypeExpression) => {
+            child_type(EdgeLabel::TypeName).map_or(Expr::Unknown, Expr::TypeOf)
+        }
+        Some(K::DecimalNumberExpression | K::HexNumberExpression) => {
+            Expr::Value(TypeRef::Elementary("uint256".to_string()))
+        }
+        Some(K::StringExpression) => Expr::Value(TypeRef::Elementary("string".to_string())),
+        Some(K::ConditionalExpression) => child(EdgeLabel::TrueExpression),
+        Some(K::AssignmentExpression) => child(EdgeLabel::LeftOperand),
+        _ => Expr::Unknown,
+    }
+}
@@ -0,0 +1,166 @@
+use super::syntax::SyntaxNode;
+use slang_solidity::cst::TerminalKind;
+
+/// The NatSpec documentation of a declaration, from the `///` or `/** */` comments above it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NatSpec {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    /// `@notice`, which is also what untagged text means.
+    pub notice: String,
+    pub dev: String,
+    pub params: Vec<(String, String)>,
+    pub returns: Vec<String>,
+    /// Name of the base contract given by `@inheritdoc`.
+    pub inheritdoc: Option<String>,
+    pub custom: Vec<(String, String)>,
+}
+
+impl NatSpec {
+    /// Reads the NatSpec comments in the leading trivia of `node`.
+    pub fn of(node: &SyntaxNode) -> Option<Self> {
+        let comments: Vec<_> = node
+            .terminals()
+            .take_while(|terminal| terminal.is_trivia())
+            .filter(|terminal| {
+                matches!(
+                    terminal.terminal_kind(),
+                    Some(
+                        TerminalKind::SingleLineNatSpecComment
+                            | TerminalKind::MultiLineNatSpecComment
+                    )
+                )
+            })
+            .filter_map(|terminal| terminal.terminal_text().map(str::to_string))
+            .collect();
+        let natspec = Self::parse(comments.iter().map(String::as_str));
+        (!natspec.is_empty()).then_some(natspec)
+    }
+
+    pub fn parse<'a>(comments: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut natspec = Self::default();
+        let mut tag = "notice".to_string();
+        let mut text = String::new();
+        for line in comments.into_iter().flat_map(comment_lines) {
+            match line.strip_prefix('@') {
+                Some(tagged) => {
+                    natspec.add(&tag, &text);
+                    let (name, rest) = tagged
+                        .split_once(char::is_whitespace)
+                        .unwrap_or((tagged, ""));
+                    tag = name.to_string();
+                    text = rest.trim().to_string();
+                }
+                None if text.is_empty() => text = line,
+                None => {
+                    text.push('\n');
+                    text.push_str(&line);
+                }
+            }
+        }
+        natspec.add(&tag, &text);
+        natspec
+    }
+
+    fn add(&mut self, tag: &str, text: &str) {
+        let text = text.trim();
+        let first_word = || {
+            let (word, rest) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+            (word.to_string(), rest.trim().to_string())
+        };
+        match tag {
+            "notice" | "dev" if text.is_empty() => {}
+            "notice" => append(&mut self.notice, text),
+            "dev" => append(&mut self.dev, text),
+            "title" => self.title = Some(text.to_string()),
+            "author" => self.author = Some(text.to_string()),
+            "param" => self.params.push(first_word()),
+            "return" => self.returns.push(text.to_string()),
+            "inheritdoc" => self.inheritdoc = Some(text.to_string()),
+            _ => {
+                if let Some(custom) = tag.strip_prefix("custom:") {
+                    self.custom.push((custom.to_string(), text.to_string()));
+                }
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+
+    /// Fills what's missing with the documentation of the base declaration.
+    pub fn inherit(&mut self, base: &NatSpec) {
+        if self.notice.is_empty() {
+            self.notice = base.notice.clone();
+        }
+        if self.dev.is_empty() {
+            self.dev = base.dev.clone();
+        }
+        if self.params.is_empty() {
+            self.params = base.params.clone();
+        }
+        if self.returns.is_empty() {
+            self.returns = base.returns.clone();
+        }
+        self.inheritdoc = None;
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut sections = Vec::new();
+        if let Some(title) = &self.title {
+            sections.push(format!("**{title}**"));
+        }
+        if !self.notice.is_empty() {
+            sections.push(self.notice.clone());
+        }
+        if !self.dev.is_empty() {
+            sections.push(self.dev.clone());
+        }
+        if !self.params.is_empty() {
+            let params: Vec<_> = self
+                .params
+                .iter()
+                .map(|(name, text)| format!("- `{name}`: {text}"))
+                .collect();
+            sections.push(format!("**Parameters**\n{}", params.join("\n")));
+        }
+        if !self.returns.is_empty() {
+            let returns: Vec<_> = self
+                .returns
+                .iter()
+                .map(|text| format!("- {text}"))
+                .collect();
+            sections.push(format!("**Returns**\n{}", returns.join("\n")));
+        }
+        if let Some(author) = &self.author {
+            sections.push(format!("*@author* {author}"));
+        }
+        for (tag, text) in &self.custom {
+            sections.push(format!("*@custom:{tag}* {text}"));
+        }
+        sections.join("\n\n")
+    }
+}
+
+fn append(section: &mut String, text: &str) {
+    if !section.is_empty() {
+        section.push('\n');
+    }
+    section.push_str(text);
+}
+
+/// The lines of a comment without the comment markers.
+fn comment_lines(comment: &str) -> Vec<String> {
+    if let Some(line) = comment.strip_prefix("///") {
+        return vec![line.trim().to_string()];
+    }
+    let body = comment.trim_start_matches("/**").trim_end_matches("*/");
+    body.lines()
+        .map(|line| {
+            let line = line.trim();
+            line.strip_prefix('*').unwrap_or(line).trim().to_string()
+        })
+        .filter(|line| !line.is_empty())
+        .collect()
+}
@@ -0,0 +1,869 @@
+use super::builtins::{self, Builtin, MAGIC};
+use super::model::{
+    DeclId, DeclKind, Declaration, Expr, Imported, Reference, ReferenceKind, ScopeKind, TypeRef,
+};
+use super::natspec::NatSpec;
+use super::source::SourceFile;
+use super::syntax::Span;
+use super::Analysis;
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+    rc::Rc,
+};
+
+/// A declaration of a given file.
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    pub file: Rc<SourceFile>,
+    pub id: DeclId,
+}
+
+impl Symbol {
+    pub fn new(file: &Rc<SourceFile>, id: DeclId) -> Self {
+        Self {
+            file: Rc::clone(file),
+            id,
+        }
+    }
+
+    pub fn decl(&self) -> &Declaration {
+        &self.file.model.declarations[self.id]
+    }
+
+    pub fn container(&self) -> Option<Symbol> {
+        self.decl()
+            .container
+            .map(|container| Symbol::new(&self.file, container))
+    }
+
+    fn declarations(&self, ids: &[DeclId]) -> Vec<Symbol> {
+        ids.iter().map(|&id| Symbol::new(&self.file, id)).collect()
+    }
+
+    pub fn parameters(&self) -> Vec<Symbol> {
+        self.declarations(&self.decl().parameters)
+    }
+
+    pub fn members(&self) -> Vec<Symbol> {
+        self.declarations(&self.decl().members)
+    }
+
+    /// Types of the parameters, which tell overloads apart.
+    pub fn parameter_types(&self) -> Vec<String> {
+        self.parameters()
+            .iter()
+            .map(|parameter| {
+                parameter
+                    .decl()
+                    .ty
+                    .as_ref()
+                    .map(ToString::to_string)
+                    .unwrap_or_default()
+            })
+            .collect()
+    }
+}
+
+impl PartialEq for Symbol {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id && self.file.path == other.file.path
+    }
+}
+
+/// What a name can refer to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Definition {
+    Declaration(Symbol),
+    Builtin(&'static Builtin),
+}
+
+/// The type of an expression, as far as we can tell without a compiler.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Type {
+    Elementary(String),
+    Array(Box<Type>),
+    Mapping(Box<Type>, Box<Type>),
+    /// A value of a contract, struct, enum or user-defined value type.
+    Instance(Symbol),
+    /// A type used as an expression, like `Token` in `Token(addr)` or `Color` in `Color.Red`.
+    Meta(Box<Type>),
+    /// A namespace like `msg`, or the result of `type(T)`.
+    Magic(&'static str),
+    Function(Symbol),
+    BuiltinFunction(&'static Builtin),
+    /// `super` in the given contract.
+    Super(Symbol),
+    /// A file imported with an alias, given by the alias declaration.
+    Module(Symbol),
+}
+
+impl Type {
+    fn from_builtin(ty: &'static str) -> Option<Self> {
+        match ty {
+            "" => None,
+            ty => Some(match MAGIC.iter().find(|magic| **magic == ty) {
+                Some(magic) => Self::Magic(magic),
+                None => Self::Elementary(ty.to_string()),
+            }),
+        }
+    }
+}
+
+/// Imports are followed this deep at most, in case the files seen so far don't stop a cycle.
+const MAX_IMPORT_DEPTH: usize = 32;
+
+/// Name resolution and type inference over the source models, loading imported files on demand.
+#[derive(Debug)]
+pub struct Semantics<'a> {
+    analysis: &'a mut Analysis,
+    linearizations: HashMap<(PathBuf, DeclId), Vec<Symbol>>,
+}
+
+impl<'a> Semantics<'a> {
+    pub fn new(analysis: &'a mut Analysis) -> Self {
+        Self {
+            analysis,
+            linearizations: HashMap::new(),
+        }
+    }
+
+    /// The file imported by the import at `index` in `file`.
+    pub fn import(&mut self, file: &SourceFile, index: usize) -> Option<Rc<SourceFile>> {
+        let import = file.model.imports.get(index)?;
+        let path = self.analysis.resolve_import(&file.path, &import.path)?;
+        self.analysis.file(&path)
+    }
+
+    /// What the name at `offset` refers to, along with the span of the name.
+    ///
+    /// On the name of a declaration, that's the declaration itself.
+    pub fn definitions_at(
+        &mut self,
+        file: &Rc<SourceFile>,
+        offset: usize,
+    ) -> Option<(Span, Vec<Definition>)> {
+        if let Some(id) = file.model.declaration_at(offset) {
+            let symbol = Symbol::new(file, id);
+            let span = symbol.decl().name_span;
+            return Some((span, self.follow(vec![symbol], &mut HashSet::new(), 0)));
+        }
+        let reference = file.model.reference_at(offset)?;
+        Some((reference.span, self.resolve(file, reference)))
+    }
+
+    pub fn resolve(&mut self, file: &Rc<SourceFile>, reference: &Reference) -> Vec<Definition> {
+        let definitions = match &reference.kind {
+            ReferenceKind::Name => {
+                self.lookup(file, reference.scope, &reference.name, reference.span.start)
+            }
+            ReferenceKind::Member(receiver) => {
+                match self.type_of(file, reference.scope, receiver) {
+                    Some(ty) => self
+                        .members_in(file, reference.scope, &ty)
+                        .into_iter()
+                        .filter(|member| name(member) == reference.name)
+                        .collect(),
+                    None => Vec::new(),
+                }
+            }
+            ReferenceKind::NamedArgument(callee) => {
+                let Some(callee) = self.type_of(file, reference.scope, callee) else {
+                    return Vec::new();
+                };
+                let parameters = match callee {
+                    Type::Function(function) => function.parameters(),
+                    Type::Meta(ty) => match *ty {
+                        Type::Instance(structure) if structure.decl().kind == DeclKind::Struct => {
+                            structure.members()
+                        }
+                        _ => Vec::new(),
+                    },
+                    _ => Vec::new(),
+                };
+                parameters
+                    .into_iter()
+                    .filter(|parameter| parameter.decl().name == reference.name)
+                    .map(Definition::Declaration)
+                    .collect()
+            }
+            ReferenceKind::Imported(index) => match self.import(file, *index) {
+                Some(imported) => self.exported(&imported, &reference.name, &mut HashSet::new(), 0),
+                None => Vec::new(),
+            },
+        };
+        select_overloads(definitions, reference.arguments)
+    }
+
+    /// Looks `name` up from `scope`, then in the enclosing scopes, the inherited contracts, the
+    /// imported files and the globals.
+    pub fn lookup(
+        &mut self,
+        file: &Rc<SourceFile>,
+        scope: usize,
+        name: &str,
+        offset: usize,
+    ) -> Vec<Definition> {
+        let model = &file.model;
+        let mut current = Some(scope);
+        while let Some(id) = current {
+            let scope = &model.scopes[id];
+            let found: Vec<_> = match (scope.kind, scope.owner) {
+                (ScopeKind::Contract, Some(contract)) => {
+                    self.inherited(&Symbol::new(file, contract), name)
+                }
+                _ => scope
+                    .declarations
+                    .iter()
+                    .map(|&decl| Symbol::new(file, decl))
+                    .filter(|symbol| {
+                        let decl = symbol.decl();
+                        decl.name == name && (!decl.kind.is_local() || decl.visible_from <= offset)
+                    })
+                    .collect(),
+            };
+            if !found.is_empty() {
+                return self.follow(found, &mut HashSet::new(), 0);
+            }
+            current = scope.parent;
+        }
+
+        let found = self.wildcard_exports(file, name, &mut HashSet::new(), 0);
+        if !found.is_empty() {
+            return found;
+        }
+        builtins::lookup("", name)
+            .map(Definition::Builtin)
+            .collect()
+    }
+
+    /// Every name that can be looked up from `scope` at `offset`, with how far from it it's
+    /// declared: the scopes in between, then the bases of the contract, the imports and finally
+    /// the globals.
+    pub fn visible(
+        &mut self,
+        file: &Rc<SourceFile>,
+        scope: usize,
+        offset: usize,
+    ) -> Vec<(Definition, usize)> {
+        let mut visible: Vec<(Definition, usize)> = Vec::new();
+        let mut add = |definition: Definition, distance: usize| {
+            // Anything but functions and events is hidden by a closer declaration of the name.
+            let hidden = visible.iter().any(|(other, _)| {
+                *other == definition
+                    || (name(other) == name(&definition)
+                        && !matches!(other, Definition::Declaration(symbol)
+                            if symbol.decl().kind.is_overloadable()))
+            });
+            if !hidden {
+                visible.push((definition, distance));
+            }
+        };
+
+        let mut distance = 0;
+        let mut current = Some(scope);
+        while let Some(id) = current {
+            let scope = &file.model.scopes[id];
+            match (scope.kind, scope.owner) {
+                (ScopeKind::Contract, Some(contract)) => {
+                    let bases = self.linearization(&Symbol::new(file, contract));
+                    for member in own_members(&bases, None) {
+                        let base = member
+                            .container()
+                            .and_then(|container| bases.iter().position(|base| *base == container))
+                            .unwrap_or_default();
+                        if base > 0 && member.decl().has_attribute("private") {
+                            continue;
+                        }
+                        add(Definition::Declaration(member), distance + base);
+                    }
+                    distance += bases.len();
+                }
+                _ => {
+                    let symbols: Vec<_> = scope
+                        .declarations
+                        .iter()
+                        .map(|&decl| Symbol::new(file, decl))
+                        .filter(|symbol| {
+                            !symbol.decl().kind.is_local() || symbol.decl().visible_from <= offset
+                        })
+                        .collect();
+                    // Aliased imports are kept as is, they aren't known by their own name here.
+                    let (aliases, symbols): (Vec<_>, Vec<_>) =
+                        symbols.into_iter().partition(|symbol| {
+                            symbol.decl().imported.as_ref().is_some_and(|imported| {
+                                imported
+                                    .name
+                                    .as_ref()
+                                    .is_some_and(|name| *name != symbol.decl().name)
+                            })
+                        });
+                    let aliases = aliases.into_iter().map(Definition::Declaration);
+                    for definition in self
+                        .follow(symbols, &mut HashSet::new(), 0)
+                        .into_iter()
+                        .chain(aliases)
+                    {
+                        add(definition, distance);
+                    }
+                    distance += 1;
+                }
+            }
+            current = scope.parent;
+        }
+
+        let mut seen = HashSet::from([file.path.clone()]);
+        for (index, import) in file.model.imports.iter().enumerate() {
+            if import.wildcard {
+                if let Some(imported) = self.import(file, index) {
+                    for definition in self.exports(&imported, &mut seen, 1) {
+                        add(definition, distance);
+                    }
+                }
+            }
+        }
+        for builtin in builtins::members("") {
+            add(Definition::Builtin(builtin), distance + 1);
+        }
+        visible
+    }
+
+    /// Replaces imported symbols by what they refer to in the imported file.
+    ///
+    /// A symbol whose file can't be found is kept as is, it's still better than nothing. `seen`
+    /// has the files already searched for a name, which aren't searched for it again.
+    fn follow(
+        &mut self,
+        symbols: Vec<Symbol>,
+        seen: &mut HashSet<(PathBuf, String)>,
+        depth: usize,
+    ) -> Vec<Definition> {
+        let mut definitions = Vec::new();
+        for symbol in symbols {
+            let imported = match &symbol.decl().imported {
+                Some(Imported {
+                    import,
+                    name: Some(name),
+                }) => self
+                    .import(&symbol.file, *import)
+                    .map(|file| self.exported(&file, name, seen, depth + 1)),
+                _ => None,
+            };
+            match imported {
+                Some(imported) if !imported.is_empty() => definitions.extend(imported),
+                _ => definitions.push(Definition::Declaration(symbol)),
+            }
+        }
+        definitions
+    }
+
+    /// The top-level declarations named `name` that importing `file` brings in scope.
+    fn exported(
+        &mut self,
+        file: &Rc<SourceFile>,
+        name: &str,
+        seen: &mut HashSet<(PathBuf, String)>,
+        depth: usize,
+    ) -> Vec<Definition> {
+        if depth > MAX_IMPORT_DEPTH || !seen.insert((file.path.clone(), name.to_string())) {
+            return Vec::new();
+        }
+        let found: Vec<_> = file.model.scopes[0]
+            .declarations
+            .iter()
+            .map(|&decl| Symbol::new(file, decl))
+            .filter(|symbol| symbol.decl().name == name)
+            .collect();
+        if !found.is_empty() {
+            return self.follow(found, seen, depth);
+        }
+        self.wildcard_exports(file, name, seen, depth)
+    }
+
+    /// Looks `name` up in the files imported by `file` with `import "path";`.
+    fn wildcard_exports(
+        &mut self,
+        file: &Rc<SourceFile>,
+        name: &str,
+        seen: &mut HashSet<(PathBuf, String)>,
+        depth: usize,
+    ) -> Vec<Definition> {
+        for (index, import) in file.model.imports.iter().enumerate() {
+            if !import.wildcard {
+                continue;
+            }
+            if let Some(imported) = self.import(file, index) {
+                let found = self.exported(&imported, name, seen, depth + 1);
+                if !found.is_empty() {
+                    return found;
+                }
+            }
+        }
+        Vec::new()
+    }
+
+    /// Every top-level declaration that importing `file` brings in scope, but the ones of the
+    /// files already `seen`.
+    fn exports(
+        &mut self,
+        file: &Rc<SourceFile>,
+        seen: &mut HashSet<PathBuf>,
+        depth: usize,
+    ) -> Vec<Definition> {
+        if depth > MAX_IMPORT_DEPTH || !seen.insert(file.path.clone()) {
+            return Vec::new();
+        }
+        let own: Vec<_> = file.model.scopes[0]
+            .declarations
+            .iter()
+            .map(|&decl| Symbol::new(file, decl))
+            .collect();
+        let mut exports = self.follow(own, &mut HashSet::new(), depth);
+        for (index, import) in file.model.imports.iter().enumerate() {
+            if import.wildcard {
+                if let Some(imported) = self.import(file, index) {
+                    exports.extend(self.exports(&imported, seen, depth + 1));
+                }
+            }
+        }
+        exports
+    }
+
+    /// Members named `name` of the contract or one of its bases, the most derived first.
+    fn inherited(&mut self, contract: &Symbol, name: &str) -> Vec<Symbol> {
+        let bases = self.linearization(contract);
+        let found = own_members(&bases, Some(name));
+        // Anything but functions and events hides the declarations of the bases.
+        match found.first() {
+            Some(first) if !first.decl().kind.is_overloadable() => vec![first.clone()],
+            _ => found,
+        }
+    }
+
+    /// The contract followed by its bases, from the most derived to the most basic, according to
+    /// the C3 linearization Solidity uses.
+    pub fn linearization(&mut self, contract: &Symbol) -> Vec<Symbol> {
+        let key = (contract.file.path.clone(), contract.id);
+        if let Some(linearization) = self.linearizations.get(&key) {
+            return linearization.clone();
+        }
+        // Guards against inheritance cycles while this one is computed.
+        self.linearizations
+            .insert(key.clone(), vec![contract.clone()]);
+
+        let decl = contract.decl();
+        let bases: Vec<Symbol> = decl
+            .bases
+            .iter()
+            .filter_map(
+                |base| match self.type_of(&contract.file, decl.scope, base) {
+                    Some(Type::Meta(ty)) => match *ty {
+                        Type::Instance(base) if base.decl().kind.is_contract() => Some(base),
+                        _ => None,
+                    },
+                    _ => None,
+                },
+            )
+            .collect();
+
+        // Bases are listed from the most basic to the most derived.
+        let mut sequences: Vec<Vec<Symbol>> = bases
+            .iter()
+            .rev()
+            .map(|base| self.linearization(base))
+            .collect();
+        sequences.push(bases.iter().rev().cloned().collect());
+
+        let mut linearization = vec![contract.clone()];
+        while sequences.iter().any(|sequence| !sequence.is_empty()) {
+            let head = sequences
+                .iter()
+                .filter_map(|sequence| sequence.first())
+                .find(|candidate| {
+                    !sequences
+                        .iter()
+                        .any(|sequence| sequence.iter().skip(1).any(|s| s == *candidate))
+                })
+                .cloned();
+            let Some(head) = head else {
+                log::warn!("no linearization of {} exists", decl.name);
+                break;
+            };
+            for sequence in &mut sequences {
+                sequence.retain(|s| *s != head);
+            }
+            linearization.push(head);
+        }
+
+        self.linearizations.insert(key, linearization.clone());
+        linearization
+    }
+
+    pub fn type_of(&mut self, file: &Rc<SourceFile>, scope: usize, expr: &Expr) -> Option<Type> {
+        match expr {
+            Expr::Name { name, offset } => match name.as_str() {
+                "this" => self.contract_at(file, scope).map(Type::Instance),
+                "super" => self.contract_at(file, scope).map(Type::Super),
+                _ => {
+                    let definition = self.lookup(file, scope, name, *offset).into_iter().next()?;
+                    self.type_of_definition(&definition)
+                }
+            },
+            Expr::Member { receiver, name } => {
+                let receiver = self.type_of(file, scope, receiver)?;
+                let member = self
+                    .members_in(file, scope, &receiver)
+                    .into_iter()
+                    .find(|member| self::name(member) == name)?;
+                self.type_of_definition(&member)
+            }
+            Expr::Index { receiver, slice } => match self.type_of(file, scope, receiver)? {
+                Type::Mapping(_, value) => Some(*value),
+                ty @ Type::Array(_) if *slice => Some(ty),
+                Type::Array(element) => Some(*element),
+                Type::Elementary(name) if name == "bytes" => {
+                    Some(Type::Elementary("bytes1".to_string()))
+                }
+                // `uint[]` in `abi.decode(data, (uint[]))`
+                Type::Meta(element) => Some(Type::Meta(Box::new(Type::Array(element)))),
+                _ => None,
+            },
+            Expr::Call { callee } => match self.type_of(file, scope, callee)? {
+                // `new T` is already typed as the value it creates.
+                ty if matches!(**callee, Expr::New(_)) => Some(ty),
+                Type::Meta(ty) => Some(*ty),
+                Type::Function(function) => {
+                    let returned = function.decl().returns.first().copied()?;
+                    self.type_of_definition(&Definition::Declaration(Symbol::new(
+                        &function.file,
+                        returned,
+                    )))
+                }
+                Type::BuiltinFunction(builtin) => Type::from_builtin(builtin.ty),
+                _ => None,
+            },
+            Expr::Type(ty) => Some(Type::Meta(Box::new(self.resolve_type(file, scope, ty)?))),
+            Expr::Value(ty) | Expr::New(ty) => self.resolve_type(file, scope, ty),
+            Expr::TypeOf(ty) => match self.resolve_type(file, scope, ty)? {
+                Type::Instance(contract) if contract.decl().kind == DeclKind::Interface => {
+                    Some(Type::Magic("type(interface)"))
+                }
+                Type::Instance(contract) if contract.decl().kind.is_contract() => {
+                    Some(Type::Magic("type(contract)"))
+                }
+                Type::Elementary(_) => Some(Type::Magic("type(integer)")),
+                _ => None,
+            },
+            Expr::Unknown => None,
+        }
+    }
+
+    pub fn type_of_definition(&mut self, definition: &Definition) -> Option<Type> {
+        let symbol = match definition {
+            Definition::Builtin(builtin) if builtin.function => {
+                return Some(Type::BuiltinFunction(builtin))
+            }
+            Definition::Builtin(builtin) => return Type::from_builtin(builtin.ty),
+            Definition::Declaration(symbol) => symbol,
+        };
+        let decl = symbol.decl();
+        match decl.kind {
+            kind if kind.is_type() => Some(Type::Meta(Box::new(Type::Instance(symbol.clone())))),
+            DeclKind::EnumMember => symbol.container().map(Type::Instance),
+            DeclKind::Function => Some(Type::Function(symbol.clone())),
+            DeclKind::Module => Some(Type::Module(symbol.clone())),
+            DeclKind::StateVariable
+            | DeclKind::Constant
+            | DeclKind::StructMember
+            | DeclKind::Parameter
+            | DeclKind::LocalVariable => {
+                let ty = decl.ty.as_ref()?;
+                self.resolve_type(&symbol.file, decl.scope, ty)
+            }
+            _ => None,
+        }
+    }
+
+    fn resolve_type(&mut self, file: &Rc<SourceFile>, scope: usize, ty: &TypeRef) -> Option<Type> {
+        match ty {
+            TypeRef::Elementary(name) => Some(Type::Elementary(name.clone())),
+            TypeRef::Path(path) => match self.type_of(file, scope, path)? {
+                Type::Meta(ty) => Some(*ty),
+                _ => None,
+            },
+            TypeRef::Array(element) => Some(Type::Array(Box::new(
+                self.resolve_type(file, scope, element)?,
+            ))),
+            TypeRef::Mapping(key, value) => Some(Type::Mapping(
+                Box::new(self.resolve_type(file, scope, key)?),
+                Box::new(self.resolve_type(file, scope, value)?),
+            )),
+            TypeRef::Function(_) => None,
+        }
+    }
+
+    /// What can be accessed with `.` on a value of type `ty`.
+    pub fn members(&mut self, ty: &Type) -> Vec<Definition> {
+        let builtins = |namespace| {
+            builtins::members(namespace)
+                .map(Definition::Builtin)
+                .collect()
+        };
+        match ty {
+            Type::Instance(symbol) => match symbol.decl().kind {
+                kind if kind.is_contract() => own_members(&self.linearization(symbol), None)
+                    .into_iter()
+                    .filter(|member| member.decl().is_external())
+                    .map(Definition::Declaration)
+                    .collect(),
+                DeclKind::Struct => symbol
+                    .members()
+                    .into_iter()
+                    .map(Definition::Declaration)
+                    .collect(),
+                _ => Vec::new(),
+            },
+            Type::Meta(ty) => match ty.as_ref() {
+                Type::Instance(symbol) => match symbol.decl().kind {
+                    kind if kind.is_contract() => own_members(&self.linearization(symbol), None)
+                        .into_iter()
+                        .map(Definition::Declaration)
+                        .collect(),
+                    DeclKind::Enum => symbol
+                        .members()
+                        .into_iter()
+                        .map(Definition::Declaration)
+                        .collect(),
+                    DeclKind::UserDefinedValueType => builtins("value type"),
+                    _ => Vec::new(),
+                },
+                Type::Elementary(name) if name == "bytes" || name == "string" => {
+                    builtins(name.as_str())
+                }
+                _ => Vec::new(),
+            },
+            Type::Elementary(name) => match name.as_str() {
+                "address" => builtins("address"),
+                "address payable" => builtins::members("address")
+                    .chain(builtins::members("address payable"))
+                    .map(Definition::Builtin)
+                    .collect(),
+                "bytes" => builtins("array"),
+                name if name.starts_with("bytes") => builtins("bytesN"),
+                _ => Vec::new(),
+            },
+            Type::Super(contract) => own_members(&self.linearization(contract)[1..], None)
+                .into_iter()
+                .map(Definition::Declaration)
+                .collect(),
+            Type::Array(_) => builtins("array"),
+            Type::Magic(namespace) => builtins(namespace),
+            Type::Function(_) => builtins("function"),
+            Type::Module(module) => {
+                let imported = module
+                    .decl()
+                    .imported
+                    .as_ref()
+                    .and_then(|imported| self.import(&module.file, imported.import));
+                match imported {
+                    Some(file) => self.exports(&file, &mut HashSet::new(), 0),
+                    None => Vec::new(),
+                }
+            }
+            Type::Mapping(..) | Type::BuiltinFunction(_) => Vec::new(),
+        }
+    }
+
+    /// What can be accessed with `.` on a value of type `ty` from `scope`, including the functions
+    /// attached to it with `using for`.
+    pub fn members_in(
+        &mut self,
+        file: &Rc<SourceFile>,
+        scope: usize,
+        ty: &Type,
+    ) -> Vec<Definition> {
+        let mut members = self.members(ty);
+        for function in self.attached(file, scope, ty) {
+            if !members.contains(&function) {
+                members.push(function);
+            }
+        }
+        members
+    }
+
+    /// The functions that the `using for` directives in effect at `scope` attach to `ty`: the
+    /// ones of the file and of the enclosing contract, and the global ones of the file declaring
+    /// `ty`.
+    fn attached(&mut self, file: &Rc<SourceFile>, scope: usize, ty: &Type) -> Vec<Definition> {
+        if !matches!(
+            ty,
+            Type::Elementary(_) | Type::Array(_) | Type::Mapping(..) | Type::Instance(_)
+        ) {
+            return Vec::new();
+        }
+        let contract = file.model.enclosing(scope, ScopeKind::Contract);
+        let mut usings: Vec<_> = file
+            .model
+            .usings
+            .iter()
+            .filter(|using| using.scope == 0 || Some(using.scope) == contract)
+            .map(|using| (Rc::clone(file), using.clone()))
+            .collect();
+        if let Type::Instance(symbol) = ty {
+            if symbol.file.path != file.path {
+                usings.extend(
+                    symbol
+                        .file
+                        .model
+                        .usings
+                        .iter()
+                        .filter(|using| using.global)
+                        .map(|using| (Rc::clone(&symbol.file), using.clone())),
+                );
+            }
+        }
+
+        let mut attached = Vec::new();
+        for (file, using) in usings {
+            if let Some(target) = &using.target {
+                if self.resolve_type(&file, using.scope, target).as_ref() != Some(ty) {
+                    continue;
+                }
+            }
+            let mut functions = Vec::new();
+            if let Some(library) = &using.library {
+                if let Some(Type::Meta(library)) = self.type_of(&file, using.scope, library) {
+                    if let Type::Instance(library) = *library {
+                        functions.extend(
+                            library
+                                .members()
+                                .into_iter()
+                                .filter(|member| member.decl().kind == DeclKind::Function),
+                        );
+                    }
+                }
+            }
+            for function in &using.functions {
+                if let Some(Type::Function(function)) = self.type_of(&file, using.scope, function) {
+                    functions.push(function);
+                }
+            }
+            for function in functions {
+                // The value is passed as the first parameter, which has to be of its type.
+                let Some(first) = function.parameters().into_iter().next() else {
+                    continue;
+                };
+                let first = self.type_of_definition(&Definition::Declaration(first));
+                let definition = Definition::Declaration(function);
+                if first.is_none_or(|first| &first == ty) && !attached.contains(&definition) {
+                    attached.push(definition);
+                }
+            }
+        }
+        attached
+    }
+
+    /// The contract whose body contains `scope`.
+    fn contract_at(&mut self, file: &Rc<SourceFile>, scope: usize) -> Option<Symbol> {
+        let scope = file.model.enclosing(scope, ScopeKind::Contract)?;
+        file.model.scopes[scope]
+            .owner
+            .map(|contract| Symbol::new(file, contract))
+    }
+
+    /// The documentation of a declaration, including what it inherits from the declaration it
+    /// overrides, explicitly with `@inheritdoc` or implicitly when it has none of its own.
+    pub fn natspec(&mut self, symbol: &Symbol) -> Option<NatSpec> {
+        let decl = symbol.decl();
+        let mut natspec = decl.natspec.clone().unwrap_or_default();
+        let inherits =
+            natspec.inheritdoc.is_some() || (natspec.is_empty() && decl.has_attribute("override"));
+        if inherits {
+            if let Some(base) = self.overridden(symbol, natspec.inheritdoc.as_deref()) {
+                if let Some(base) = self.natspec(&base) {
+                    natspec.inherit(&base);
+                }
+            }
+        }
+        (!natspec.is_empty()).then_some(natspec)
+    }
+
+    /// The declaration `symbol` overrides, in the base named `base` if given.
+    pub fn overridden(&mut self, symbol: &Symbol, base: Option<&str>) -> Option<Symbol> {
+        let contract = symbol.container()?;
+        let types = symbol.parameter_types();
+        self.linearization(&contract)
+            .into_iter()
+            .skip(1)
+            .filter(|candidate| base.is_none_or(|base| candidate.decl().name == base))
+            .flat_map(|candidate| candidate.members())
+            .find(|member| {
+                member.decl().name == symbol.decl().name
+                    && member.decl().kind == symbol.decl().kind
+                    && member.parameter_types() == types
+            })
+    }
+}
+
+/// The members of `bases`, given from the most derived, without the overridden ones.
+fn own_members(bases: &[Symbol], name: Option<&str>) -> Vec<Symbol> {
+    let mut found: Vec<Symbol> = Vec::new();
+    for member in bases.iter().flat_map(|base| base.members()) {
+        let decl = member.decl();
+        if name.is_some_and(|name| decl.name != name) {
+            continue;
+        }
+        let overridden = found.iter().any(|f| {
+            let other = f.decl();
+            other.name == decl.name
+                && (!other.kind.is_overloadable()
+                    || !decl.kind.is_overloadable()
+                    || f.parameter_types() == member.parameter_types())
+        });
+        if !overridden {
+            found.push(member);
+        }
+    }
+    found
+}
+
+pub fn name(definition: &Definition) -> &str {
+    match definition {
+        Definition::Declaration(symbol) => &symbol.decl().name,
+        Definition::Builtin(builtin) => builtin.name,
+    }
+}
+
+/// Narrows overloaded functions down to the ones taking `arguments` arguments, if any.
+fn select_overloads(definitions: Vec<Definition>, arguments: Option<usize>) -> Vec<Definition> {
+    let Some(arguments) = arguments else {
+        return definitions;
+    };
+    if definitions.len() < 2 {
+        return definitions;
+    }
+    let matching: Vec<_> = definitions
+        .iter()
+        .filter(|definition| match definition {
+            Definition::Declaration(symbol) => symbol.decl().parameters.len() == arguments,
+            Definition::Builtin(builtin) => {
+                let parameters = builtin
+                    .signature
+                    .split_once('(')
+                    .and_then(|(_, rest)| rest.split_once(')'))
+                    .map_or("", |(parameters, _)| parameters);
+                let count = match parameters.trim() {
+                    "" => 0,
+                    parameters => parameters.split(',').count(),
+                };
+                count == arguments
+            }
+        })
+        .cloned()
+        .collect();
+    if matching.is_empty() {
+        definitions
+    } else {
+        matching
+    }
+}
@@ -0,0 +1,177 @@
+use super::model::SourceModel;
+use super::syntax::{Span, SyntaxNode};
+use crate::document::{LineIndex, PositionEncoding};
+use semver::Version;
+use slang_solidity::cst::{Cursor, Node, TerminalKindExtensions, TextIndex, TextRange};
+use slang_solidity::parser::{ParseError, ParseOutput};
+use std::{ops::Deref, path::PathBuf, rc::Rc};
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, Location, Position, Range, Url};
+
+/// `source` of the diagnostics reporting syntax errors, to tell them apart from detector findings.
+pub const PARSER_DIAGNOSTIC_SOURCE: &str = "slap-parser";
+
+/// A parsed file without its tree: the text and what it declares.
+///
+/// This is what language features work with, and what is kept for files that aren't open.
+#[derive(Debug)]
+pub struct SourceFile {
+    pub path: PathBuf,
+    /// Version of the open document, or `None` when the content was read from disk.
+    pub version: Option<i32>,
+    pub text: String,
+    /// Language version the text was parsed with, resolved from its pragmas.
+    pub solidity_version: Version,
+    pub line_index: LineIndex,
+    pub encoding: PositionEncoding,
+    pub model: SourceModel,
+}
+
+impl SourceFile {
+    pub fn position(&self, offset: usize) -> Position {
+        self.line_index.position(&self.text, offset, self.encoding)
+    }
+
+    pub fn offset(&self, position: Position) -> usize {
+        self.line_index.offset(&self.text, position, self.encoding)
+    }
+
+    /// Converts a CST text range to an LSP range in the negotiated encoding.
+    pub fn range(&self, range: &TextRange) -> Range {
+        Range::new(
+            self.position(range.start.utf8),
+            self.position(range.end.utf8),
+        )
+    }
+
+    pub fn span_range(&self, span: Span) -> Range {
+        Range::new(self.position(span.start), self.position(span.end))
+    }
+
+    pub fn location(&self, span: Span) -> Option<Location> {
+        let uri = Url::from_file_path(&self.path).ok()?;
+        Some(Location::new(uri, self.span_range(span)))
+    }
+}
+
+/// The result of parsing one version of a document, shared by every detector and request handler.
+///
+/// The CST is reference counted with `Rc`, so a `ParsedSource` never leaves the analysis thread.
+#[derive(Debug)]
+pub struct ParsedSource {
+    pub file: Rc<SourceFile>,
+    output: ParseOutput,
+}
+
+impl Deref for ParsedSource {
+    type Target = SourceFile;
+
+    fn deref(&self) -> &SourceFile {
+        &self.file
+    }
+}
+
+impl ParsedSource {
+    pub fn new(
+        path: PathBuf,
+        version: Option<i32>,
+        text: String,
+        solidity_version: Version,
+        output: ParseOutput,
+        encoding: PositionEncoding,
+    ) -> Self {
+        let file = SourceFile {
+            path,
+            version,
+            line_index: LineIndex::new(&text),
+            text,
+            solidity_version,
+            encoding,
+            model: SourceModel::build(output.tree()),
+        };
+        Self {
+            file: Rc::new(file),
+            output,
+        }
+    }
+
+    pub fn tree(&self) -> Node {
+        self.output.tree()
+    }
+
+    /// A cursor at the root of the tree.
+    pub fn cursor(&self) -> Cursor {
+        self.output.tree().cursor_with_offset(TextIndex::ZERO)
+    }
+
+    pub fn errors(&self) -> &[ParseError] {
+        self.output.errors()
+    }
+
+    /// The syntax errors of the file as LSP diagnostics.
+    pub fn error_diagnostics(&self) -> Vec<Diagnostic> {
+        self.errors()
+            .iter()
+            .map(|error| {
+                let range = error.text_range();
+                let mut end = range.end.utf8;
+                // Slang reports missing tokens as empty ranges, which most editors don't display.
+                if range.start.utf8 == end {
+                    if let Some(c) = self.text[end..]
+                        .chars()
+                        .next()
+                        .filter(|c| *c != '\n' && *c != '\r')
+                    {
+                        end += c.len_utf8();
+                    }
+                }
+                Diagnostic {
+                    range: Range::new(self.position(range.start.utf8), self.position(end)),
+                    severity: Some(DiagnosticSeverity::ERROR),
+                    source: Some(PARSER_DIAGNOSTIC_SOURCE.to_string()),
+                    message: error.message(),
+                    ..Default::default()
+                }
+            })
+            .collect()
+    }
+
+    /// Ranges the parser couldn't make sense of: the reported errors and the text it skipped.
+    pub fn error_ranges(&self) -> Vec<Range> {
+        let skipped = SyntaxNode::root(self.tree())
+            .terminals()
+            .filter(|terminal| {
+                terminal
+                    .node
+                    .as_terminal()
+                    .is_some_and(|terminal| !terminal.kind.is_valid())
+            })
+            .map(|terminal| self.range(&terminal.full_range()))
+            .collect::<Vec<_>>();
+        self.errors()
+            .iter()
+            .map(|error| self.range(error.text_range()))
+            .chain(skipped)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+impl ParsedSource {
+    /// Parses `text` as the content of `/Test.sol`, with the newest version Slang supports.
+    pub fn from_text(text: &str) -> Self {
+        use slang_solidity::{cst::NonterminalKind, parser::Parser};
+
+        let version = Parser::SUPPORTED_VERSIONS.last().unwrap().clone();
+        let output = Parser::create(version.clone())
+            .unwrap()
+            .parse(NonterminalKind::SourceUnit, text);
+        Self::new(
+            PathBuf::from("/Test.sol"),
+            None,
+            text.to_string(),
+            version,
+            output,
+            PositionEncoding::Utf16,
+        )
+    }
+}
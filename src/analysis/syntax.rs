@@ -0,0 +1,174 @@
+use slang_solidity::cst::{EdgeLabel, Node, NonterminalKind, TerminalKind, TextIndex, TextRange};
+
+/// Byte range into the source text.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    /// Whether `offset` is within the span, or right after it like a cursor at the end of a word.
+    pub fn contains(&self, offset: usize) -> bool {
+        self.start <= offset && offset <= self.end
+    }
+}
+
+/// A CST node together with its position, which Slang nodes don't carry themselves.
+///
+/// Cheaper to navigate by label than a `Cursor`, at the cost of not knowing its parent.
+#[derive(Debug, Clone)]
+pub struct SyntaxNode {
+    pub node: Node,
+    pub label: Option<EdgeLabel>,
+    pub offset: TextIndex,
+}
+
+impl SyntaxNode {
+    pub fn root(node: Node) -> Self {
+        Self {
+            node,
+            label: None,
+            offset: TextIndex::ZERO,
+        }
+    }
+
+    pub fn nonterminal_kind(&self) -> Option<NonterminalKind> {
+        self.node.as_nonterminal().map(|node| node.kind)
+    }
+
+    pub fn is(&self, kind: NonterminalKind) -> bool {
+        self.nonterminal_kind() == Some(kind)
+    }
+
+    pub fn terminal_kind(&self) -> Option<TerminalKind> {
+        self.node.as_terminal().map(|node| node.kind)
+    }
+
+    /// Text of a terminal, which may be trivia.
+    pub fn terminal_text(&self) -> Option<&str> {
+        self.node.as_terminal().map(|node| node.text.as_str())
+    }
+
+    pub fn is_trivia(&self) -> bool {
+        self.node.is_trivia()
+    }
+
+    /// Range of the node, including its leading and trailing trivia.
+    pub fn full_range(&self) -> TextRange {
+        self.offset..self.offset + self.node.text_len()
+    }
+
+    /// Range of the node without its leading and trailing trivia.
+    pub fn span(&self) -> Span {
+        let full = self.full_range();
+        let start = self
+            .tokens()
+            .next()
+            .map_or(full.start.utf8, |token| token.offset.utf8);
+        let end = self.last_token_end().unwrap_or(start);
+        Span::new(start, end.max(start))
+    }
+
+    fn last_token_end(&self) -> Option<usize> {
+        if self.node.is_terminal() {
+            return (!self.is_trivia()).then(|| self.full_range().end.utf8);
+        }
+        let children: Vec<_> = self.children().collect();
+        children
+            .iter()
+            .rev()
+            .find_map(|child| child.last_token_end())
+    }
+
+    pub fn children(&self) -> impl Iterator<Item = SyntaxNode> + '_ {
+        let mut offset = self.offset;
+        self.node.edges().iter().map(move |edge| {
+            let child = SyntaxNode {
+                node: edge.node.clone(),
+                label: edge.label,
+                offset,
+            };
+            offset += edge.node.text_len();
+            child
+        })
+    }
+
+    /// The first child reached through `label`.
+    pub fn child(&self, label: EdgeLabel) -> Option<SyntaxNode> {
+        self.children().find(|child| child.label == Some(label))
+    }
+
+    /// Follows `Variant` edges through choice nodes like `Expression` or `TypeName`.
+    pub fn variant(&self) -> SyntaxNode {
+        let mut node = self.clone();
+        while let Some(variant) = node.child(EdgeLabel::Variant) {
+            node = variant;
+        }
+        node
+    }
+
+    /// Children reached through `Item` edges, which is how Slang lays out lists.
+    pub fn items(&self) -> impl Iterator<Item = SyntaxNode> + '_ {
+        self.children()
+            .filter(|child| child.label == Some(EdgeLabel::Item))
+    }
+
+    /// Every node of the subtree in pre-order, including this one.
+    pub fn descendants(&self) -> impl Iterator<Item = SyntaxNode> {
+        let mut stack = vec![self.clone()];
+        std::iter::from_fn(move || {
+            let node = stack.pop()?;
+            let children: Vec<_> = node.children().collect();
+            stack.extend(children.into_iter().rev());
+            Some(node)
+        })
+    }
+
+    pub fn terminals(&self) -> impl Iterator<Item = SyntaxNode> {
+        self.descendants().filter(|node| node.node.is_terminal())
+    }
+
+    /// The non-trivia terminals of the subtree.
+    pub fn tokens(&self) -> impl Iterator<Item = SyntaxNode> {
+        self.terminals().filter(|terminal| !terminal.is_trivia())
+    }
+
+    /// The text of the node as it would be written on one line, see [`join_tokens`].
+    pub fn display(&self) -> String {
+        join_tokens(self.tokens().map(|token| token.text()))
+    }
+
+    /// The source text of the node without its trivia, tokens separated by nothing.
+    pub fn text(&self) -> String {
+        self.terminals()
+            .filter(|terminal| !terminal.is_trivia())
+            .filter_map(|terminal| terminal.node.as_terminal().map(|t| t.text.clone()))
+            .collect()
+    }
+}
+
+/// Joins tokens with single spaces where Solidity code usually has them, so that a declaration
+/// spanning several lines reads as `function f(uint256 a) external returns (bool)`.
+pub fn join_tokens<S: AsRef<str>>(tokens: impl IntoIterator<Item = S>) -> String {
+    let mut text = String::new();
+    let mut previous: Option<S> = None;
+    for token in tokens {
+        let current = token.as_ref();
+        if let Some(previous) = previous.as_ref().map(|p| p.as_ref()) {
+            let glued = matches!(previous, "(" | "[" | ".")
+                || matches!(current, ")" | "]" | "," | ";" | "." | "[")
+                || (current == "(" && !matches!(previous, "returns" | "=>" | "=" | ","));
+            if !glued {
+                text.push(' ');
+            }
+        }
+        text.push_str(current);
+        previous = Some(token);
+    }
+    text
+}
@@ -0,0 +1,163 @@
+use super::syntax::SyntaxNode;
+use semver::{Version, VersionReq};
+use slang_solidity::cst::{EdgeLabel, Node, NonterminalKind};
+use slang_solidity::parser::Parser;
+
+/// Versions allowed by one `pragma solidity`: any of its `||` separated sets may match.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VersionPragma(Vec<VersionReq>);
+
+impl VersionPragma {
+    pub fn matches(&self, version: &Version) -> bool {
+        self.0.iter().any(|req| req.matches(version))
+    }
+}
+
+/// Reads every `pragma solidity` of the file.
+pub fn version_pragmas(tree: Node) -> Vec<VersionPragma> {
+    SyntaxNode::root(tree)
+        .descendants()
+        .filter(|node| node.is(NonterminalKind::VersionPragma))
+        .filter_map(|pragma| {
+            let sets = pragma.child(EdgeLabel::Sets)?;
+            let reqs = sets
+                .items()
+                .filter_map(|set| {
+                    let comparators: Vec<_> = set.items().map(comparator).collect();
+                    VersionReq::parse(&comparators.join(", "))
+                        .inspect_err(|err| log::warn!("unsupported version pragma: {err}"))
+                        .ok()
+                })
+                .collect();
+            Some(VersionPragma(reqs))
+        })
+        .collect()
+}
+
+/// Translates a `VersionExpression` to the `semver` syntax, whose defaults differ from npm's.
+fn comparator(expression: SyntaxNode) -> String {
+    let expression = expression.variant();
+    match expression.nonterminal_kind() {
+        Some(NonterminalKind::VersionRange) => {
+            let start = expression.child(EdgeLabel::Start).map(|v| literal(&v));
+            let end = expression.child(EdgeLabel::End).map(|v| literal(&v));
+            format!(
+                ">={}, <={}",
+                start.unwrap_or_default(),
+                end.unwrap_or_default()
+            )
+        }
+        _ => {
+            let operator = expression
+                .child(EdgeLabel::Operator)
+                .map(|operator| operator.text())
+                // A bare version is an exact match in Solidity, but a caret requirement in `semver`.
+                .unwrap_or_else(|| "=".to_string());
+            let literal = expression
+                .child(EdgeLabel::Literal)
+                .map(|v| literal(&v))
+                .unwrap_or_default();
+            format!("{operator}{literal}")
+        }
+    }
+}
+
+fn literal(literal: &SyntaxNode) -> String {
+    literal
+        .text()
+        .trim_matches(['"', '\''])
+        .replace(['x', 'X'], "*")
+}
+
+/// Picks the Slang version to parse a file with.
+///
+/// That's the configured compiler version when the pragmas allow it, otherwise the newest version
+/// Slang supports that satisfies every pragma (and isn't newer than the configured compiler).
+pub fn resolve(pragmas: &[VersionPragma], configured: Option<&Version>) -> Version {
+    let supported = Parser::SUPPORTED_VERSIONS;
+    let latest = supported
+        .last()
+        .expect("slang supports at least one version");
+
+    let candidates: Vec<&Version> = supported
+        .iter()
+        .filter(|version| pragmas.iter().all(|pragma| pragma.matches(version)))
+        .collect();
+
+    if let Some(configured) = configured {
+        if candidates.contains(&configured) {
+            return configured.clone();
+        }
+        if let Some(version) = candidates
+            .iter()
+            .rev()
+            .find(|version| **version <= configured)
+        {
+            return (*version).clone();
+        }
+    }
+
+    match candidates.last() {
+        Some(version) => (*version).clone(),
+        None => {
+            log::warn!("no supported solidity version satisfies {pragmas:?}");
+            configured
+                .and_then(|configured| supported.iter().rev().find(|v| *v <= configured))
+                .unwrap_or(latest)
+                .clone()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{resolve, version_pragmas};
+    use semver::Version;
+    use slang_solidity::{cst::NonterminalKind, parser::Parser};
+
+    fn resolved(text: &str, configured: Option<&str>) -> String {
+        let latest = Parser::SUPPORTED_VERSIONS.last().unwrap().clone();
+        let output = Parser::create(latest)
+            .unwrap()
+            .parse(NonterminalKind::SourceUnit, text);
+        let configured = configured.map(|version| Version::parse(version).unwrap());
+        resolve(&version_pragmas(output.tree()), configured.as_ref()).to_string()
+    }
+
+    #[test]
+    fn newest_version_the_pragmas_allow() {
+        assert_eq!(resolved("pragma solidity ^0.8.0 <0.8.20;", None), "0.8.19");
+        assert_eq!(resolved("pragma solidity 0.7.6;", None), "0.7.6");
+        assert_eq!(resolved("pragma solidity 0.6.0 - 0.6.8;", None), "0.6.8");
+        assert_eq!(
+            resolved("pragma solidity >=0.5.0 <0.6.0 || 0.4.24;", None),
+            "0.5.17"
+        );
+        assert_eq!(
+            resolved("pragma solidity ^0.8.0;\npragma solidity <=0.8.4;", None),
+            "0.8.4"
+        );
+        let latest = Parser::SUPPORTED_VERSIONS.last().unwrap().to_string();
+        assert_eq!(resolved("contract C {}", None), latest);
+        assert_eq!(resolved("pragma solidity ^0.99.0;", None), latest);
+    }
+
+    #[test]
+    fn configured_version() {
+        assert_eq!(
+            resolved("pragma solidity ^0.8.0;", Some("0.8.10")),
+            "0.8.10"
+        );
+        assert_eq!(
+            resolved("pragma solidity <0.8.20;", Some("0.8.24")),
+            "0.8.19"
+        );
+        assert_eq!(
+            resolved("pragma solidity ^0.99.0;", Some("0.8.10")),
+            "0.8.10"
+        );
+        // Older than the pragmas allow, so it can't be followed.
+        let latest = Parser::SUPPORTED_VERSIONS.last().unwrap().to_string();
+        assert_eq!(resolved("pragma solidity ^0.8.12;", Some("0.8.10")), latest);
+    }
+}
@@ -0,0 +1,476 @@
+use crate::detectors::{query::QueryDetector, Category, Confidence, Detectors, Metadata};
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+use tower_lsp::lsp_types::DiagnosticSeverity;
+
+/// How the detectors run on the files of a project, from its `slap.toml` or the `[tool.slap]`
+/// table of its `foundry.toml`:
+///
+/// ```toml
+/// include = ["src/**"]
+/// exclude = ["src/legacy/**"]
+///
+/// [rules]
+/// structs = "off"
+/// unused-import = { severity = "warning" }
+///
+/// [[overrides]]
+/// paths = ["test/**", "script/**"]
+/// rules = { unused-import = "off" }
+///
+/// [detectors.tx-origin]
+/// query = '''[MemberAccessExpression
+///     operand: [Expression ["tx"]]
+///     @member member: ["origin"]
+/// ]'''
+/// capture = "member"
+/// message = "`tx.origin` is the account that sent the transaction, not the caller"
+/// severity = "warning"
+/// files = "src/**"
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    /// Directory the globs are relative to.
+    pub root: PathBuf,
+    /// Only the files matching one of these get findings, every file when empty.
+    pub include: Vec<Glob>,
+    pub exclude: Vec<Glob>,
+    /// By detector ID.
+    pub rules: HashMap<String, Rule>,
+    /// Applied in order to the files they match, over `rules`.
+    pub overrides: Vec<Override>,
+    /// Rules written as Slang queries, which run along the ones of slap.
+    pub detectors: Vec<QueryDetector>,
+}
+
+/// The settings of a detector, `None` for the detector's own.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Rule {
+    pub enabled: Option<bool>,
+    pub severity: Option<DiagnosticSeverity>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Override {
+    pub paths: Vec<Glob>,
+    pub rules: HashMap<String, Rule>,
+}
+
+impl Config {
+    /// The configuration of the file at `path`, the default one if there is none, with the `slap`
+    /// settings of the editor applied over it.
+    ///
+    /// The closest `slap.toml` and `foundry.toml` above the file are looked up on their own, so a
+    /// `slap.toml` at the root of a repository also applies to the Foundry projects below it. The
+    /// globs are relative to the directory of the file they're in.
+    pub fn find(path: &Path, settings: &toml::Table) -> Self {
+        let closest = |name: &str| {
+            path.ancestors()
+                .skip(1)
+                .find(|dir| dir.join(name).is_file())
+        };
+        let foundry = closest("foundry.toml");
+        let slap = closest("slap.toml");
+        // Both are above the file, so one is above the other.
+        let root = match (foundry, slap) {
+            (Some(foundry), Some(slap)) if foundry.starts_with(slap) => Some(slap),
+            (Some(foundry), _) => Some(foundry),
+            (None, slap) => slap,
+        };
+        let mut config = Self {
+            root: root.map(Path::to_path_buf).unwrap_or_default(),
+            ..Self::default()
+        };
+        if let Some(dir) = foundry {
+            config.load_tool(dir);
+        }
+        // `slap.toml` wins over `[tool.slap]`.
+        if let Some(dir) = slap {
+            config.load_slap(dir);
+        }
+        config.merge(settings);
+        config
+    }
+
+    /// Merges the `[tool.slap]` table of the `foundry.toml` in `dir`.
+    fn load_tool(&mut self, dir: &Path) {
+        if let Some(foundry) = read_table(&dir.join("foundry.toml")) {
+            let tool = foundry
+                .get("tool")
+                .and_then(|tool| tool.get("slap"))
+                .and_then(|slap| slap.as_table());
+            if let Some(tool) = tool {
+                self.merge_in(tool, dir);
+            }
+        }
+    }
+
+    /// Merges the `slap.toml` in `dir`.
+    fn load_slap(&mut self, dir: &Path) {
+        if let Some(slap) = read_table(&dir.join("slap.toml")) {
+            self.merge_in(&slap, dir);
+        }
+    }
+
+    /// Sets what `table` sets, keeping the rest.
+    pub fn merge(&mut self, table: &toml::Table) {
+        let root = self.root.clone();
+        self.merge_in(table, &root);
+    }
+
+    /// Sets what `table` sets, with its globs relative to `dir`, which is the root or below it.
+    fn merge_in(&mut self, table: &toml::Table, dir: &Path) {
+        let dir = dir.strip_prefix(&self.root).unwrap_or(Path::new(""));
+        let globs = |value: &toml::Value| globs(value, dir);
+        if let Some(include) = table.get("include") {
+            self.include = globs(include);
+        }
+        if let Some(exclude) = table.get("exclude") {
+            self.exclude = globs(exclude);
+        }
+        if let Some(rules) = table.get("rules").and_then(|rules| rules.as_table()) {
+            merge_rules(&mut self.rules, rules);
+        }
+        let overrides = table
+            .get("overrides")
+            .and_then(|overrides| overrides.as_array());
+        for entry in overrides.into_iter().flatten() {
+            let Some(entry) = entry.as_table() else {
+                log::warn!("invalid override: {entry}");
+                continue;
+            };
+            let mut rules = HashMap::new();
+            if let Some(table) = entry.get("rules").and_then(|rules| rules.as_table()) {
+                merge_rules(&mut rules, table);
+            }
+            self.overrides.push(Override {
+                paths: entry.get("paths").map(globs).unwrap_or_default(),
+                rules,
+            });
+        }
+        let detectors = table
+            .get("detectors")
+            .and_then(|detectors| detectors.as_table());
+        for (id, entry) in detectors.into_iter().flatten() {
+            let detector = entry
+                .as_table()
+                .ok_or_else(|| "expected a table".to_string())
+                .and_then(|entry| query_detector(id, entry, dir));
+            match detector {
+                Ok(detector) => {
+                    self.detectors.retain(|other| other.metadata.id != *id);
+                    self.detectors.push(detector);
+                }
+                Err(err) => log::error!("invalid detector {id}: {err}"),
+            }
+        }
+    }
+
+    /// Whether the file at `path` gets findings at all.
+    pub fn includes(&self, path: &Path) -> bool {
+        let relative = self.relative(path);
+        (self.include.is_empty() || self.include.iter().any(|glob| glob.matches(&relative)))
+            && !self.exclude.iter().any(|glob| glob.matches(&relative))
+    }
+
+    /// Whether the file at `path` matches one of `globs`, or `globs` is empty.
+    pub fn matches(&self, globs: &[Glob], path: &Path) -> bool {
+        let relative = self.relative(path);
+        globs.is_empty() || globs.iter().any(|glob| glob.matches(&relative))
+    }
+
+    /// The settings of the detector `id` for the file at `path`.
+    pub fn rule(&self, id: &str, path: &Path) -> Rule {
+        let relative = self.relative(path);
+        let mut rule = self.rules.get(id).copied().unwrap_or_default();
+        let overrides = self
+            .overrides
+            .iter()
+            .filter(|entry| entry.paths.iter().any(|glob| glob.matches(&relative)));
+        for entry in overrides {
+            if let Some(overridden) = entry.rules.get(id) {
+                rule.enabled = overridden.enabled.or(rule.enabled);
+                rule.severity = overridden.severity.or(rule.severity);
+            }
+        }
+        rule
+    }
+
+    fn relative(&self, path: &Path) -> String {
+        path.strip_prefix(&self.root)
+            .unwrap_or(path)
+            .to_string_lossy()
+            .replace('\\', "/")
+    }
+}
+
+fn read_table(path: &Path) -> Option<toml::Table> {
+    let content = std::fs::read_to_string(path).ok()?;
+    match content.parse() {
+        Ok(table) => Some(table),
+        Err(err) => {
+            log::error!("invalid {}: {err}", path.display());
+            None
+        }
+    }
+}
+
+/// Reads a pattern or a list of them, relative to `dir` within the root.
+fn globs(value: &toml::Value, dir: &Path) -> Vec<Glob> {
+    let values = match value.as_str() {
+        Some(_) => std::slice::from_ref(value),
+        None => value.as_array().map(Vec::as_slice).unwrap_or_default(),
+    };
+    values
+        .iter()
+        .filter_map(|value| value.as_str())
+        .map(|pattern| Glob::under(dir, pattern))
+        .collect()
+}
+
+/// Reads rules written as a severity or `"off"`, or as a table with `enabled` and `severity`.
+fn merge_rules(rules: &mut HashMap<String, Rule>, table: &toml::Table) {
+    for (id, value) in table {
+        let rule = rules.entry(id.clone()).or_default();
+        let (enabled, severity) = match value {
+            toml::Value::String(severity) => (None, Some(severity.as_str())),
+            toml::Value::Boolean(enabled) => (Some(*enabled), None),
+            toml::Value::Table(table) => (
+                table.get("enabled").and_then(|enabled| enabled.as_bool()),
+                table.get("severity").and_then(|severity| severity.as_str()),
+            ),
+            value => {
+                log::warn!("invalid rule {id}: {value}");
+                continue;
+            }
+        };
+        if enabled.is_some() {
+            rule.enabled = enabled;
+        }
+        match severity {
+            Some("off") => rule.enabled = Some(false),
+            Some(severity) => match parse_severity(severity) {
+                Some(severity) => {
+                    rule.enabled = enabled.or(Some(true));
+                    rule.severity = Some(severity);
+                }
+                None => log::warn!("unknown severity {severity:?} for rule {id}"),
+            },
+            None => {}
+        }
+    }
+}
+
+/// Reads a rule written as a query, which must have a `query` and a `message`, and an ID that no
+/// built-in rule has.
+fn query_detector(id: &str, table: &toml::Table, dir: &Path) -> Result<QueryDetector, String> {
+    // Its findings couldn't be told apart from the ones of the built-in rule.
+    if Detectors::builtin().rules().any(|rule| rule.id == id) {
+        return Err("a built-in rule already has this ID".to_string());
+    }
+    let string = |key: &str| table.get(key).and_then(|value| value.as_str());
+    let query = string("query").ok_or("missing query")?;
+    let message = string("message").ok_or("missing message")?;
+    let category = match string("category") {
+        Some(category) => {
+            Category::parse(category).ok_or(format!("unknown category {category:?}"))?
+        }
+        None => Category::Informational,
+    };
+    let confidence = match string("confidence") {
+        Some(confidence) => {
+            Confidence::parse(confidence).ok_or(format!("unknown confidence {confidence:?}"))?
+        }
+        None => Confidence::Medium,
+    };
+    let severity = match string("severity") {
+        Some(severity) => {
+            parse_severity(severity).ok_or(format!("unknown severity {severity:?}"))?
+        }
+        None => DiagnosticSeverity::WARNING,
+    };
+    let metadata = Metadata {
+        id: Cow::Owned(id.to_string()),
+        name: Cow::Owned(string("name").unwrap_or(id).to_string()),
+        category,
+        severity,
+        confidence,
+        description: Cow::Owned(string("description").unwrap_or(message).to_string()),
+        url: string("url").map(|url| Cow::Owned(url.to_string())),
+        enabled: true,
+    };
+    QueryDetector::new(
+        metadata,
+        query,
+        string("capture").map(str::to_string),
+        message.to_string(),
+        table
+            .get("files")
+            .map(|files| globs(files, dir))
+            .unwrap_or_default(),
+    )
+}
+
+fn parse_severity(severity: &str) -> Option<DiagnosticSeverity> {
+    match severity {
+        "error" => Some(DiagnosticSeverity::ERROR),
+        "warning" | "warn" => Some(DiagnosticSeverity::WARNING),
+        "info" | "information" => Some(DiagnosticSeverity::INFORMATION),
+        "hint" => Some(DiagnosticSeverity::HINT),
+        _ => None,
+    }
+}
+
+/// A path pattern relative to the project root: `*` matches within a directory, `**` across
+/// directories, `?` a single character, and a pattern ending with `/` everything under it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Glob(pub String);
+
+impl Glob {
+    /// `pattern` written in `dir`, relative to the root.
+    fn under(dir: &Path, pattern: &str) -> Self {
+        match dir.as_os_str().is_empty() {
+            true => Self(pattern.to_string()),
+            false => Self(format!(
+                "{}/{}",
+                dir.to_string_lossy().replace('\\', "/"),
+                pattern.trim_start_matches("./")
+            )),
+        }
+    }
+
+    pub fn matches(&self, path: &str) -> bool {
+        let pattern = self.0.trim_start_matches("./");
+        let pattern = match pattern.ends_with('/') {
+            true => format!("{pattern}**"),
+            false => pattern.to_string(),
+        };
+        matches(&pattern, path)
+    }
+}
+
+fn matches(pattern: &str, path: &str) -> bool {
+    // `**/` also matches no directory at all.
+    if let Some(rest) = pattern.strip_prefix("**/") {
+        return matches(rest, path)
+            || path
+                .match_indices('/')
+                .any(|(index, _)| matches(rest, &path[index + 1..]));
+    }
+    if let Some(rest) = pattern.strip_prefix("**") {
+        return boundaries(path).any(|index| matches(rest, &path[index..]));
+    }
+    if let Some(rest) = pattern.strip_prefix('*') {
+        let end = path.find('/').unwrap_or(path.len());
+        return boundaries(&path[..end]).any(|index| matches(rest, &path[index..]));
+    }
+    let mut pattern = pattern.chars();
+    let mut path = path.chars();
+    match (pattern.next(), path.next()) {
+        (None, next) => next.is_none(),
+        (Some('?'), Some(c)) if c != '/' => matches(pattern.as_str(), path.as_str()),
+        (Some(expected), Some(c)) if expected == c => matches(pattern.as_str(), path.as_str()),
+        _ => false,
+    }
+}
+
+/// The offsets between the characters of `text`, its start and end included.
+fn boundaries(text: &str) -> impl Iterator<Item = usize> + '_ {
+    text.char_indices()
+        .map(|(index, _)| index)
+        .chain([text.len()])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Config, Glob};
+    use std::fs;
+
+    fn matches(pattern: &str, path: &str) -> bool {
+        Glob(pattern.to_string()).matches(path)
+    }
+
+    #[test]
+    fn double_star_slash_matches_zero_directories() {
+        assert!(matches("**/*.sol", "A.sol"));
+        assert!(matches("**/*.sol", "src/a/A.sol"));
+        assert!(matches("src/**/A.sol", "src/A.sol"));
+        assert!(matches("src/**/A.sol", "src/a/b/A.sol"));
+        assert!(!matches("src/**/A.sol", "srcA.sol"));
+        assert!(!matches("src/**/A.sol", "lib/src/A.sol"));
+    }
+
+    #[test]
+    fn trailing_slash_matches_everything_under_it() {
+        assert!(matches("lib/", "lib/A.sol"));
+        assert!(matches("./lib/", "lib/forge-std/src/Test.sol"));
+        assert!(!matches("lib/", "lib"));
+        assert!(!matches("lib/", "libs/A.sol"));
+        assert!(!matches("lib/", "src/lib/A.sol"));
+    }
+
+    #[test]
+    fn single_star_stays_within_a_directory() {
+        assert!(matches("src/*.sol", "src/A.sol"));
+        assert!(matches("*.t.sol", ".t.sol"));
+        assert!(!matches("src/*.sol", "src/a/A.sol"));
+        assert!(matches("src/**", "src/a/A.sol"));
+        assert!(matches("test/*/*.t.sol", "test/a/A.t.sol"));
+    }
+
+    #[test]
+    fn question_mark_matches_one_character() {
+        assert!(matches("A?.sol", "AB.sol"));
+        assert!(matches("A?.sol", "Aé.sol"));
+        assert!(matches("*é.sol", "café.sol"));
+        assert!(!matches("A?.sol", "A.sol"));
+        assert!(!matches("A?.sol", "A/.sol"));
+    }
+
+    #[test]
+    fn declared_rules_cannot_reuse_builtin_ids() {
+        let table = toml::from_str(
+            r#"
+            [detectors.structs]
+            query = "[StructDefinition]"
+            message = "struct"
+
+            [detectors.struct-definition]
+            query = "[StructDefinition]"
+            message = "struct"
+            "#,
+        )
+        .unwrap();
+        let mut config = Config::default();
+        config.merge(&table);
+        let ids: Vec<_> = config
+            .detectors
+            .iter()
+            .map(|detector| &detector.metadata.id)
+            .collect();
+        assert_eq!(ids, ["struct-definition"]);
+    }
+
+    #[test]
+    fn slap_toml_above_a_foundry_project() {
+        let root = std::env::temp_dir().join(format!("slap-config-{}", std::process::id()));
+        let project = root.join("pkg");
+        fs::create_dir_all(&project).unwrap();
+        let slap = "exclude = [\"pkg/src/legacy/**\"]\n[rules]\nstructs = \"off\"\n";
+        fs::write(root.join("slap.toml"), slap).unwrap();
+        let foundry = "[tool.slap]\ninclude = [\"src/**\"]\n";
+        fs::write(project.join("foundry.toml"), foundry).unwrap();
+
+        let file = project.join("src/A.sol");
+        let config = Config::find(&file, &toml::Table::new());
+        let _ = fs::remove_dir_all(&root);
+        assert_eq!(config.root, root);
+        assert_eq!(config.rule("structs", &file).enabled, Some(false));
+        assert!(config.includes(&file));
+        assert!(!config.includes(&project.join("test/A.t.sol")));
+        assert!(!config.includes(&project.join("src/legacy/A.sol")));
+    }
+}
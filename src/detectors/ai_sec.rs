@@ -1,18 +1,39 @@
-use super::{Detector, LspMessage};
+use super::{Category, Confidence, Detector, DetectorFuture, LspMessage, Metadata};
+use crate::analysis::source::ParsedSource;
 use reqwest::header::{AUTHORIZATION, CONTENT_TYPE};
 use serde::Deserialize;
-use std::{future::Future, path::PathBuf, pin::Pin};
+use std::borrow::Cow;
 use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, Position, Range};
 
+static METADATA: Metadata = Metadata {
+    id: Cow::Borrowed("ai-sec"),
+    name: Cow::Borrowed("AI security review"),
+    category: Category::Security,
+    severity: DiagnosticSeverity::HINT,
+    confidence: Confidence::Low,
+    description: Cow::Borrowed(
+        "Asks a language model for the improvements and vulnerabilities of the file. Its answer \
+         isn't tied to the lines it talks about, so it is reported at the top of the file.",
+    ),
+    url: Some(Cow::Borrowed("https://github.com/iFrostizz/slap#ai-sec")),
+    enabled: false,
+};
+
 #[derive(Debug)]
 pub struct AIDetector;
 
 impl Detector for AIDetector {
-    fn run(
-        &self,
-        file: PathBuf,
-        content: String,
-    ) -> Pin<Box<dyn Future<Output = Vec<LspMessage>> + Send + '_>> {
+    fn metadata(&self) -> &Metadata {
+        &METADATA
+    }
+
+    fn on_save(&self) -> bool {
+        true
+    }
+
+    fn run(&self, source: &ParsedSource) -> DetectorFuture {
+        let file = source.path.clone();
+        let content = source.text.clone();
         Box::pin(async move {
             // run some AI
             // todo!()
@@ -49,7 +70,19 @@ impl Detector for AIDetector {
                 .send()
                 .await;
 
-            let res: Resp = res.unwrap().json().await.unwrap();
+            let res: Resp = match res {
+                Ok(res) => match res.json().await {
+                    Ok(res) => res,
+                    Err(err) => {
+                        log::error!("invalid AI response: {err}");
+                        return vec![LspMessage::Error];
+                    }
+                },
+                Err(err) => {
+                    log::error!("AI request failed: {err}");
+                    return vec![LspMessage::Error];
+                }
+            };
 
             res.choices
                 .into_iter()
@@ -58,7 +91,6 @@ impl Detector for AIDetector {
                     path: file.to_owned(),
                     diags: vec![Diagnostic {
                         range: Range::new(Position::new(0, 0), Position::new(1, 10)),
-                        severity: Some(DiagnosticSeverity::HINT),
                         message: message.content,
                         ..Default::default()
                     }],
@@ -105,21 +137,15 @@ impl Detector for AIDetector {
 
 #[derive(Debug, Deserialize)]
 struct Resp {
-    id: String,
-    object: String,
-    created: u64,
-    model: String,
     choices: Vec<Choice>,
 }
 
 #[derive(Debug, Deserialize)]
 struct Choice {
-    index: u32,
     message: Message,
 }
 
 #[derive(Debug, Deserialize)]
 struct Message {
-    role: String,
     content: String,
 }
@@ -1,12 +1,24 @@
-use std::{
-    future::Future,
-    path::{Path, PathBuf},
-    pin::Pin,
+use crate::{
+    analysis::{source::ParsedSource, syntax::Span},
+    config::Config,
+};
+use futures::future::join_all;
+use serde_json::{json, Value};
+use std::{borrow::Cow, collections::HashSet, future::Future, path::PathBuf, pin::Pin};
+use tower_lsp::lsp_types::{
+    CodeDescription, Diagnostic, DiagnosticSeverity, NumberOrString, Range, Url, WorkspaceEdit,
 };
-use tower_lsp::lsp_types::Diagnostic;
 
 pub mod ai_sec;
+pub mod query;
 pub mod structs;
+pub mod suppressions;
+pub mod unused_imports;
+
+use suppressions::Suppressions;
+
+/// Source of the diagnostics of the detectors.
+pub const DIAGNOSTIC_SOURCE: &str = "slap";
 
 #[derive(Debug)]
 pub enum LspMessage {
@@ -17,34 +29,452 @@ pub enum LspMessage {
     Error,
 }
 
-pub trait Detector: Sync + Send {
-    fn run(
-        &self,
-        file: PathBuf,
-        content: String,
-    ) -> Pin<Box<dyn Future<Output = Vec<LspMessage>> + Send + '_>>;
+/// A change that resolves a finding and can be applied without review, offered as a quick fix.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fix {
+    pub title: String,
+    /// May touch other files than the one of the finding.
+    pub edit: WorkspaceEdit,
+}
+
+impl Fix {
+    /// Stores the fixes in the `data` of the diagnostic, which clients send back with the code
+    /// action requests.
+    pub fn attach(diag: &mut Diagnostic, fixes: Vec<Fix>) {
+        let fixes: Vec<_> = fixes
+            .into_iter()
+            .map(|fix| json!({ "title": fix.title, "edit": fix.edit }))
+            .collect();
+        let mut data = match diag.data.take() {
+            Some(Value::Object(data)) => data,
+            _ => Default::default(),
+        };
+        data.insert("fixes".to_string(), Value::Array(fixes));
+        diag.data = Some(Value::Object(data));
+    }
+
+    /// The fixes attached to the diagnostic.
+    pub fn of(diag: &Diagnostic) -> Vec<Fix> {
+        let fixes = diag
+            .data
+            .as_ref()
+            .and_then(|data| data.get("fixes"))
+            .and_then(Value::as_array);
+        fixes
+            .into_iter()
+            .flatten()
+            .filter_map(|fix| {
+                Some(Fix {
+                    title: fix.get("title")?.as_str()?.to_string(),
+                    edit: serde_json::from_value(fix.get("edit")?.clone()).ok()?,
+                })
+            })
+            .collect()
+    }
 }
 
-// #[derive(Debug)]
-// pub struct Detectors(Vec<AIDetector>);
+/// What a detector hands back: the CST work is done by then, the future only finishes the job.
+pub type DetectorFuture = Pin<Box<dyn Future<Output = Vec<LspMessage>> + Send>>;
 
-pub struct Detectors(pub Vec<Box<dyn Detector>>);
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Category {
+    Security,
+    Gas,
+    Style,
+    Informational,
+}
+
+impl Category {
+    pub fn parse(category: &str) -> Option<Self> {
+        match category {
+            "security" => Some(Self::Security),
+            "gas" => Some(Self::Gas),
+            "style" => Some(Self::Style),
+            "informational" => Some(Self::Informational),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Security => "security",
+            Self::Gas => "gas",
+            Self::Style => "style",
+            Self::Informational => "informational",
+        }
+    }
+}
+
+/// How sure a detector is that its findings are real issues.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Confidence {
+    Low,
+    Medium,
+    High,
+}
+
+impl Confidence {
+    pub fn parse(confidence: &str) -> Option<Self> {
+        match confidence {
+            "low" => Some(Self::Low),
+            "medium" => Some(Self::Medium),
+            "high" => Some(Self::High),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Low => "low",
+            Self::Medium => "medium",
+            Self::High => "high",
+        }
+    }
+}
+
+/// What a detector is about, which its findings are labeled with.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Metadata {
+    /// What the configuration and the suppression comments call the rule, like `unused-import`,
+    /// and the code of its findings.
+    pub id: Cow<'static, str>,
+    pub name: Cow<'static, str>,
+    pub category: Category,
+    /// Of the findings, unless the detector or the configuration sets another.
+    pub severity: DiagnosticSeverity,
+    pub confidence: Confidence,
+    /// Markdown explaining what is found and why it matters.
+    pub description: Cow<'static, str>,
+    /// Documentation of the rule, linked from its findings.
+    pub url: Option<Cow<'static, str>>,
+    /// Whether the rule runs when the configuration doesn't say.
+    pub enabled: bool,
+}
+
+impl Metadata {
+    /// Fills in the code, the documentation link and the severity of a finding of the rule,
+    /// `severity` being the one the configuration sets.
+    fn label(&self, diag: &mut Diagnostic, severity: Option<DiagnosticSeverity>) {
+        diag.code
+            .get_or_insert_with(|| NumberOrString::String(self.id.to_string()));
+        if diag.code_description.is_none() {
+            diag.code_description = self
+                .url
+                .as_ref()
+                .and_then(|url| Url::parse(url).ok())
+                .map(|href| CodeDescription { href });
+        }
+        diag.severity = severity.or(diag.severity).or(Some(self.severity));
+    }
+
+    /// What the `slap/rules` request lists.
+    pub fn to_json(&self) -> Value {
+        let severity = match self.severity {
+            DiagnosticSeverity::ERROR => "error",
+            DiagnosticSeverity::WARNING => "warning",
+            DiagnosticSeverity::INFORMATION => "info",
+            _ => "hint",
+        };
+        json!({
+            "id": self.id,
+            "name": self.name,
+            "category": self.category.as_str(),
+            "severity": severity,
+            "confidence": self.confidence.as_str(),
+            "description": self.description,
+            "url": self.url,
+            "enabled": self.enabled,
+        })
+    }
+}
+
+pub trait Detector {
+    fn metadata(&self) -> &Metadata;
+
+    /// Called on the analysis thread with the parse of the current document version, which is
+    /// shared by all detectors. Anything that needs the tree must happen before returning, since
+    /// the future can only hold `Send` data.
+    fn run(&self, source: &ParsedSource) -> DetectorFuture;
+
+    /// Whether the detector only runs when the document is saved rather than on every change,
+    /// for detectors that are slow or send the file elsewhere.
+    fn on_save(&self) -> bool {
+        false
+    }
+}
+
+/// Shared by the requests, unlike the detectors a configuration declares.
+pub struct Detectors(pub Vec<Box<dyn Detector + Send + Sync>>);
 
 impl std::fmt::Debug for Detectors {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        // todo!()
-        Ok(())
+        f.debug_struct("Detectors")
+            .field("len", &self.0.len())
+            .finish()
     }
 }
 
 impl Detectors {
-    pub async fn run(&self, path: &Path, content: String) -> Vec<LspMessage> {
-        let mut messages = Vec::new();
-        for detector in &self.0 {
-            // messages.append(&mut detector.run(&path.clone(), &content).await);
-            // messages.append(&mut detector.run().await);
-            messages.append(&mut detector.run(path.to_owned(), content.clone()).await);
-        }
-        messages
+    /// Every detector slap ships with, which the configuration of each project picks from.
+    pub fn builtin() -> Self {
+        Self(vec![
+            Box::new(ai_sec::AIDetector),
+            Box::new(structs::StructsDetector),
+            Box::new(unused_imports::UnusedImportsDetector),
+        ])
+    }
+
+    /// The metadata of every detector, and of the unused suppression comments.
+    pub fn rules(&self) -> impl Iterator<Item = &Metadata> {
+        self.0
+            .iter()
+            .map(|detector| detector.metadata())
+            .chain([&suppressions::METADATA])
+    }
+
+    /// Runs every detector, even on a tree with syntax errors: findings that touch a range the
+    /// parser had to recover from are dropped, the rest of the file is still analyzed.
+    ///
+    /// The findings in the file are then labeled with the metadata of their detector, filtered
+    /// through its suppression comments, and handed back in a single message. Only the detectors
+    /// `config` enables for the file run, with the severities it sets, along with the ones it
+    /// declares as queries.
+    ///
+    /// The detectors that run on save are skipped unless the document was `saved`, their findings
+    /// in `previous`, the diagnostics last published for the file, being kept instead.
+    pub fn run(
+        &self,
+        source: &ParsedSource,
+        config: &Config,
+        saved: bool,
+        previous: &[Diagnostic],
+    ) -> DetectorFuture {
+        let path = source.path.clone();
+        if !config.includes(&path) {
+            let diags = Vec::new();
+            return Box::pin(std::future::ready(vec![LspMessage::Diagnostics {
+                path,
+                diags,
+            }]));
+        }
+        let declared = config
+            .detectors
+            .iter()
+            .filter(|detector| config.matches(&detector.files, &path))
+            .map(|detector| detector as &dyn Detector);
+        let (enabled, skipped): (Vec<_>, Vec<_>) = self
+            .0
+            .iter()
+            .map(|detector| detector.as_ref() as &dyn Detector)
+            .chain(declared)
+            .map(|detector| (detector, config.rule(&detector.metadata().id, &path)))
+            .filter(|(detector, rule)| rule.enabled.unwrap_or(detector.metadata().enabled))
+            .partition(|(detector, _)| saved || !detector.on_save());
+        let kept: Vec<_> = previous
+            .iter()
+            .filter(|diag| {
+                skipped.iter().any(|(detector, _)| {
+                    diag.code == Some(NumberOrString::String(detector.metadata().id.to_string()))
+                })
+            })
+            .cloned()
+            .collect();
+        let none_skipped = skipped.is_empty();
+        let (labels, futures): (Vec<_>, Vec<_>) = enabled
+            .into_iter()
+            .map(|(detector, rule)| {
+                let label = (detector.metadata().clone(), rule.severity);
+                (label, detector.run(source))
+            })
+            .unzip();
+        let error_ranges = source.error_ranges();
+        let suppressions = Suppressions::parse(source);
+        let unused = config.rule(&suppressions::METADATA.id, &path);
+
+        Box::pin(async move {
+            let mut diags = Vec::new();
+            let mut messages = Vec::new();
+            let found = join_all(futures).await;
+            // The rules that ran without failing, whose findings the suppressions can be checked
+            // against.
+            let failed = |messages: &Vec<LspMessage>| {
+                messages
+                    .iter()
+                    .any(|message| matches!(message, LspMessage::Error))
+            };
+            let ran: HashSet<_> = labels
+                .iter()
+                .zip(&found)
+                .filter(|(_, messages)| !failed(messages))
+                .map(|((metadata, _), _)| metadata.id.to_string())
+                .collect();
+            let all_ran = none_skipped && !found.iter().any(failed);
+            for ((metadata, severity), message) in
+                labels.iter().zip(found).flat_map(|(label, messages)| {
+                    messages.into_iter().map(move |message| (label, message))
+                })
+            {
+                match message {
+                    LspMessage::Diagnostics {
+                        path: diags_path,
+                        diags: found,
+                    } if diags_path == path => diags.extend(
+                        found
+                            .into_iter()
+                            .filter(|diag| {
+                                !error_ranges
+                                    .iter()
+                                    .any(|error| intersects(error, &diag.range))
+                            })
+                            .map(|mut diag| {
+                                metadata.label(&mut diag, *severity);
+                                diag
+                            }),
+                    ),
+                    message => messages.push(message),
+                }
+            }
+            diags.extend(kept);
+            let mut diags = suppressions.apply(diags, unused, &ran, all_ran);
+            for diag in &mut diags {
+                diag.source = Some(DIAGNOSTIC_SOURCE.to_string());
+            }
+            messages.push(LspMessage::Diagnostics { path, diags });
+            messages
+        })
+    }
+}
+
+/// Whether two ranges overlap, counting an empty range as overlapping the ranges around it.
+pub fn intersects(a: &Range, b: &Range) -> bool {
+    if a.start == a.end || b.start == b.end {
+        a.start <= b.end && b.start <= a.end
+    } else {
+        a.start < b.end && b.start < a.end
+    }
+}
+
+/// The lines of `span` when nothing else is on them, otherwise `span` itself.
+fn line_span(text: &str, span: Span) -> Span {
+    let line_start = text[..span.start].rfind('\n').map_or(0, |index| index + 1);
+    let line_end = text[span.end..]
+        .find('\n')
+        .map_or(text.len(), |index| span.end + index + 1);
+    let alone = text[line_start..span.start].trim().is_empty()
+        && text[span.end..line_end].trim().is_empty();
+    match alone {
+        true => Span::new(line_start, line_end),
+        false => span,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Category, Confidence, Detector, DetectorFuture, Detectors, LspMessage, Metadata};
+    use crate::{
+        analysis::source::ParsedSource,
+        config::{Config, Rule},
+    };
+    use std::borrow::Cow;
+    use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, NumberOrString, Position, Range};
+
+    /// Reports the start of every file.
+    struct Start {
+        metadata: Metadata,
+        on_save: bool,
+    }
+
+    impl Start {
+        fn boxed(
+            id: &'static str,
+            enabled: bool,
+            on_save: bool,
+        ) -> Box<dyn Detector + Send + Sync> {
+            let metadata = Metadata {
+                id: Cow::Borrowed(id),
+                name: Cow::Borrowed(id),
+                category: Category::Informational,
+                severity: DiagnosticSeverity::HINT,
+                confidence: Confidence::High,
+                description: Cow::Borrowed(""),
+                url: None,
+                enabled,
+            };
+            Box::new(Self { metadata, on_save })
+        }
+    }
+
+    impl Detector for Start {
+        fn metadata(&self) -> &Metadata {
+            &self.metadata
+        }
+
+        fn run(&self, source: &ParsedSource) -> DetectorFuture {
+            let diags = vec![Diagnostic {
+                range: Range::new(Position::new(0, 0), Position::new(0, 1)),
+                message: "start".to_string(),
+                ..Default::default()
+            }];
+            let path = source.path.clone();
+            Box::pin(std::future::ready(vec![LspMessage::Diagnostics {
+                path,
+                diags,
+            }]))
+        }
+
+        fn on_save(&self) -> bool {
+            self.on_save
+        }
+    }
+
+    fn run(
+        detectors: &Detectors,
+        config: &Config,
+        saved: bool,
+        previous: &[Diagnostic],
+    ) -> Vec<Diagnostic> {
+        let source = ParsedSource::from_text("contract C {}\n");
+        let messages = futures::executor::block_on(detectors.run(&source, config, saved, previous));
+        let [LspMessage::Diagnostics { diags, .. }] = &messages[..] else {
+            panic!("expected diagnostics, got {messages:?}");
+        };
+        diags.clone()
+    }
+
+    fn codes(diags: &[Diagnostic]) -> Vec<String> {
+        diags
+            .iter()
+            .map(|diag| match &diag.code {
+                Some(NumberOrString::String(code)) => code.clone(),
+                code => panic!("unexpected code {code:?}"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn rules_off_by_default_run_once_enabled() {
+        let detectors = Detectors(vec![Start::boxed("opt-in", false, false)]);
+        let mut config = Config::default();
+        assert!(run(&detectors, &config, true, &[]).is_empty());
+        let enabled = Rule {
+            enabled: Some(true),
+            severity: None,
+        };
+        config.rules.insert("opt-in".to_string(), enabled);
+        assert_eq!(codes(&run(&detectors, &config, true, &[])), ["opt-in"]);
+    }
+
+    #[test]
+    fn on_save_findings_are_kept_until_the_next_save() {
+        let detectors = Detectors(vec![
+            Start::boxed("change", true, false),
+            Start::boxed("save", true, true),
+        ]);
+        let config = Config::default();
+        assert_eq!(codes(&run(&detectors, &config, false, &[])), ["change"]);
+        let saved = run(&detectors, &config, true, &[]);
+        assert_eq!(codes(&saved), ["change", "save"]);
+        let changed = run(&detectors, &config, false, &saved);
+        assert_eq!(codes(&changed), ["change", "save"]);
     }
 }
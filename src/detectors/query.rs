@@ -0,0 +1,164 @@
+use super::{Detector, DetectorFuture, LspMessage, Metadata};
+use crate::{
+    analysis::{source::ParsedSource, syntax::SyntaxNode},
+    config::Glob,
+};
+use slang_solidity::cst::Query;
+use tower_lsp::lsp_types::Diagnostic;
+
+/// A rule of the configuration: every match of a Slang query is a finding, highlighting the node
+/// of one of its captures.
+///
+/// The query is parsed once when the configuration is read, so like the configuration it stays on
+/// the analysis thread.
+#[derive(Debug, Clone)]
+pub struct QueryDetector {
+    pub metadata: Metadata,
+    query: Query,
+    /// The whole match when `None`.
+    capture: Option<String>,
+    message: String,
+    /// Only the files matching one of these get findings, every file when empty.
+    pub files: Vec<Glob>,
+}
+
+impl QueryDetector {
+    /// Fails when `query` doesn't parse or has no `capture`, which leaves the rule out of the
+    /// configuration.
+    pub fn new(
+        metadata: Metadata,
+        query: &str,
+        capture: Option<String>,
+        message: String,
+        files: Vec<Glob>,
+    ) -> Result<Self, String> {
+        let query = Query::parse(query).map_err(|err| {
+            format!(
+                "invalid query at {}:{}: {}",
+                err.line + 1,
+                err.column + 1,
+                err.message
+            )
+        })?;
+        if let Some(capture) = &capture {
+            if !query.capture_quantifiers.contains_key(capture) {
+                return Err(format!("the query has no @{capture} capture"));
+            }
+        }
+        Ok(Self {
+            metadata,
+            query,
+            capture,
+            message,
+            files,
+        })
+    }
+
+    fn find(&self, source: &ParsedSource) -> Vec<LspMessage> {
+        let diags = source
+            .cursor()
+            .query(vec![self.query.clone()])
+            .flat_map(|query_match| match &self.capture {
+                Some(capture) => query_match
+                    .capture(capture)
+                    .map(|(_, cursors)| cursors.collect())
+                    .unwrap_or_default(),
+                None => vec![query_match.root_cursor.clone()],
+            })
+            .map(|cursor| {
+                let node = SyntaxNode {
+                    node: cursor.node(),
+                    label: cursor.label(),
+                    offset: cursor.text_offset(),
+                };
+                Diagnostic {
+                    range: source.span_range(node.span()),
+                    message: self.message.clone(),
+                    ..Default::default()
+                }
+            })
+            .collect();
+        vec![LspMessage::Diagnostics {
+            path: source.path.clone(),
+            diags,
+        }]
+    }
+}
+
+impl Detector for QueryDetector {
+    fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    fn run(&self, source: &ParsedSource) -> DetectorFuture {
+        Box::pin(std::future::ready(self.find(source)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        analysis::source::ParsedSource,
+        config::Config,
+        detectors::{Detector, LspMessage},
+    };
+    use tower_lsp::lsp_types::{Position, Range};
+
+    /// The ranges and messages of the findings of the rules `declared` in a configuration on
+    /// `text`.
+    fn findings(declared: &str, text: &str) -> Vec<(Range, String)> {
+        let mut config = Config::default();
+        config.merge(&declared.parse().unwrap());
+        let source = ParsedSource::from_text(text);
+        config
+            .detectors
+            .iter()
+            .flat_map(|detector| futures::executor::block_on(detector.run(&source)))
+            .flat_map(|message| match message {
+                LspMessage::Diagnostics { diags, .. } => diags,
+                LspMessage::Error => panic!("the query failed"),
+            })
+            .map(|diag| (diag.range, diag.message))
+            .collect()
+    }
+
+    #[test]
+    fn declared_query() {
+        let declared = r#"
+            [detectors.tx-origin]
+            query = '[MemberAccessExpression operand: [Expression ["tx"]] @member member: ["origin"]]'
+            capture = "member"
+            message = "tx.origin"
+        "#;
+        let text = "contract C {\n    function f() public view returns (address) {\n        return tx.origin;\n    }\n}\n";
+        let range = Range::new(Position::new(2, 18), Position::new(2, 24));
+        assert_eq!(findings(declared, text), [(range, "tx.origin".to_string())]);
+    }
+
+    #[test]
+    fn whole_matches_without_a_capture() {
+        let declared = r#"
+            [detectors.struct-definition]
+            query = "[StructDefinition]"
+            message = "struct"
+        "#;
+        let text = "struct S {\n    uint a;\n}\n";
+        let range = Range::new(Position::new(0, 0), Position::new(2, 1));
+        assert_eq!(findings(declared, text), [(range, "struct".to_string())]);
+    }
+
+    #[test]
+    fn invalid_queries_are_left_out() {
+        let declared = r#"
+            [detectors.unparsed]
+            query = "[StructDefinition"
+            message = "struct"
+
+            [detectors.missing-capture]
+            query = "[StructDefinition]"
+            capture = "name"
+            message = "struct"
+        "#;
+        assert_eq!(findings(declared, "struct S {\n    uint a;\n}\n"), []);
+    }
+}
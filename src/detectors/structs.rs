@@ -1,41 +1,37 @@
-use super::{Detector, LspMessage};
-use semver::Version;
-use slang_solidity::cst::{Cursor, Node, NonterminalKind, Query, TextIndex};
-use slang_solidity::parser::Parser;
-use std::future::Future;
-use std::path::PathBuf;
-use std::pin::Pin;
-use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, Position, Range};
+use super::{Category, Confidence, Detector, DetectorFuture, LspMessage, Metadata};
+use crate::analysis::source::ParsedSource;
+use slang_solidity::cst::{Cursor, Query};
+use std::borrow::Cow;
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity};
+
+static METADATA: Metadata = Metadata {
+    id: Cow::Borrowed("structs"),
+    name: Cow::Borrowed("Struct definitions"),
+    category: Category::Informational,
+    severity: DiagnosticSeverity::INFORMATION,
+    confidence: Confidence::High,
+    description: Cow::Borrowed("Points out the name of every struct definition."),
+    url: Some(Cow::Borrowed("https://github.com/iFrostizz/slap#structs")),
+    enabled: true,
+};
 
 #[derive(Debug)]
 pub struct StructsDetector;
 
 impl Detector for StructsDetector {
-    fn run(
-        &self,
-        path: PathBuf,
-        content: String,
-    ) -> Pin<Box<dyn Future<Output = Vec<LspMessage>> + Send + '_>> {
-        Box::pin(async move {
-            let parser =
-                Parser::create(Version::parse("0.8.13").unwrap()).expect("wrong solidity version");
-
-            let parse_output = parser.parse(NonterminalKind::SourceUnit, &content);
+    fn metadata(&self) -> &Metadata {
+        &METADATA
+    }
 
-            if parse_output.errors().is_empty() {
-                Self::find_structs(path, parse_output.tree())
-            } else {
-                log::error!("{:?}", parse_output.errors());
-                vec![]
-            }
-        })
+    fn run(&self, source: &ParsedSource) -> DetectorFuture {
+        Box::pin(std::future::ready(Self::find_structs(source)))
     }
 }
 
 impl StructsDetector {
-    fn find_structs(path: PathBuf, tree: Node) -> Vec<LspMessage> {
+    fn find_structs(source: &ParsedSource) -> Vec<LspMessage> {
         let query = Query::parse("[StructDefinition @struct_name name:[Identifier]]").unwrap();
-        let cursor = tree.cursor_with_offset(TextIndex::ZERO);
+        let cursor = source.cursor();
 
         let cursors: Vec<_> = cursor
             .query(vec![query])
@@ -53,7 +49,7 @@ impl StructsDetector {
 
         cursors
             .into_iter()
-            .filter_map(|mut cursor: Cursor| {
+            .map(|cursor: Cursor| {
                 // log::debug!("{:?}", &cursor);
                 log::debug!("{:?}", &cursor.text_range());
                 // log::debug!("{:?}", &cursor.text_offset());
@@ -69,27 +65,14 @@ impl StructsDetector {
                 //     .map(|terminal_node| terminal_node.text.clone())
                 // None
 
-                let range = cursor.text_range();
-                let (start, end) = (range.start, range.end);
-
-                Some(LspMessage::Diagnostics {
-                    path: path.clone(),
+                LspMessage::Diagnostics {
+                    path: source.path.clone(),
                     diags: vec![Diagnostic {
-                        range: Range::new(
-                            Position::new(
-                                start.line.try_into().unwrap(),
-                                start.column.try_into().unwrap(),
-                            ),
-                            Position::new(
-                                end.line.try_into().unwrap(),
-                                end.column.try_into().unwrap(),
-                            ),
-                        ),
-                        severity: Some(DiagnosticSeverity::INFORMATION),
+                        range: source.range(&cursor.text_range()),
                         message: "Wow that's a really cool struct!!".to_string(),
                         ..Default::default()
                     }],
-                })
+                }
             })
             .collect()
     }
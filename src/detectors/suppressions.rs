@@ -0,0 +1,408 @@
+use super::{line_span, Category, Confidence, Fix, Metadata};
+use crate::{
+    analysis::{
+        source::ParsedSource,
+        syntax::{Span, SyntaxNode},
+    },
+    config::Rule,
+    document::LineIndex,
+};
+use slang_solidity::cst::TerminalKind;
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    ops::RangeInclusive,
+};
+use tower_lsp::lsp_types::{
+    Diagnostic, DiagnosticSeverity, DiagnosticTag, NumberOrString, Position, Range, TextEdit, Url,
+    WorkspaceEdit,
+};
+
+/// The rule of the suppression comments that suppress nothing.
+pub static METADATA: Metadata = Metadata {
+    id: Cow::Borrowed("unused-suppression"),
+    name: Cow::Borrowed("Unused suppressions"),
+    category: Category::Style,
+    severity: DiagnosticSeverity::WARNING,
+    confidence: Confidence::High,
+    description: Cow::Borrowed(
+        "Suppression comments that silence no finding, with a fix removing them.",
+    ),
+    url: Some(Cow::Borrowed(
+        "https://github.com/iFrostizz/slap#unused-suppression",
+    )),
+    enabled: true,
+};
+
+const DISABLE_NEXT_LINE: &str = "slap-disable-next-line";
+const DISABLE_LINE: &str = "slap-disable-line";
+const DISABLE: &str = "slap-disable";
+const ENABLE: &str = "slap-enable";
+
+/// A `slap-disable-next-line`, `slap-disable-line` or `slap-disable` comment.
+#[derive(Debug)]
+struct Suppression {
+    /// The comment.
+    range: Range,
+    /// What removes the comment when it suppresses nothing.
+    removal: Range,
+    directive: &'static str,
+    /// Rule IDs it applies to, every rule when empty.
+    rules: Vec<String>,
+    /// Lines of the findings it silences.
+    lines: RangeInclusive<u32>,
+    /// Rules a `slap-enable` re-enabled while the range stays open for the others, with the line
+    /// of that comment.
+    enabled: Vec<(String, u32)>,
+}
+
+/// The suppression comments of a file, which silence the findings of the detectors.
+#[derive(Debug)]
+pub struct Suppressions {
+    uri: Option<Url>,
+    suppressions: Vec<Suppression>,
+}
+
+impl Suppressions {
+    pub fn parse(source: &ParsedSource) -> Self {
+        let mut suppressions = Vec::new();
+        // `slap-disable` comments not closed by a `slap-enable` yet.
+        let mut open: Vec<Suppression> = Vec::new();
+        let root = SyntaxNode::root(source.tree());
+        for terminal in root.terminals() {
+            // NatSpec comments count too, a `/// slap-disable-next-line` is no documentation.
+            let text = match terminal.terminal_kind() {
+                Some(
+                    TerminalKind::SingleLineComment
+                    | TerminalKind::MultiLineComment
+                    | TerminalKind::SingleLineNatSpecComment
+                    | TerminalKind::MultiLineNatSpecComment,
+                ) => terminal.terminal_text().unwrap_or_default(),
+                _ => continue,
+            };
+            let content = match text.strip_prefix("/*") {
+                Some(block) => block.trim_end_matches("*/").trim_start_matches('*'),
+                None => text.trim_start_matches('/'),
+            }
+            .trim();
+            let Some((directive, rules)) = [DISABLE_NEXT_LINE, DISABLE_LINE, DISABLE, ENABLE]
+                .into_iter()
+                .find_map(|directive| {
+                    let rest = content.strip_prefix(directive)?;
+                    let ends = rest.is_empty() || rest.starts_with(char::is_whitespace);
+                    ends.then_some((directive, rest))
+                })
+            else {
+                continue;
+            };
+            let rules: Vec<_> = rules
+                .split([',', ' ', '\t'])
+                .filter(|rule| !rule.is_empty())
+                .map(str::to_string)
+                .collect();
+            let span = Span::new(terminal.offset.utf8, terminal.offset.utf8 + text.len());
+            let line = source.position(span.start).line;
+            let lines = match directive {
+                DISABLE_NEXT_LINE => line + 1..=line + 1,
+                DISABLE_LINE => line..=line,
+                DISABLE => line..=u32::MAX,
+                _ => {
+                    // Re-enables the rules it names in the open ranges, and closes the ranges left
+                    // with no rule, or all of them.
+                    for open in &mut open {
+                        open.enabled.extend(
+                            rules
+                                .iter()
+                                .filter(|rule| open.rules.is_empty() || open.rules.contains(rule))
+                                .map(|rule| (rule.clone(), line)),
+                        );
+                    }
+                    let (closed, still_open): (Vec<_>, Vec<_>) =
+                        open.into_iter().partition(|open| {
+                            let enabled = |rule: &String| {
+                                open.enabled.iter().any(|(enabled, _)| enabled == rule)
+                            };
+                            rules.is_empty()
+                                || (!open.rules.is_empty() && open.rules.iter().all(enabled))
+                        });
+                    open = still_open;
+                    suppressions.extend(closed.into_iter().map(|mut closed| {
+                        closed.lines = *closed.lines.start()..=line;
+                        closed
+                    }));
+                    continue;
+                }
+            };
+            let suppression = Suppression {
+                range: source.span_range(span),
+                removal: source.span_range(removal(&source.text, span)),
+                directive,
+                rules,
+                lines,
+                enabled: Vec::new(),
+            };
+            match directive {
+                DISABLE => open.push(suppression),
+                _ => suppressions.push(suppression),
+            }
+        }
+        suppressions.extend(open);
+        Self {
+            uri: Url::from_file_path(&source.path).ok(),
+            suppressions,
+        }
+    }
+
+    /// Drops the findings a suppression applies to, and reports the suppressions that apply to
+    /// none unless `rule`, the settings of `unused-suppression`, disables it.
+    ///
+    /// Only the findings of the rules in `ran` are known to be complete, so a suppression is
+    /// reported only when every rule it names is one of them. One naming no rule is reported only
+    /// when every enabled rule ran, which `all_ran` tells.
+    pub fn apply(
+        &self,
+        diags: Vec<Diagnostic>,
+        rule: Rule,
+        ran: &HashSet<String>,
+        all_ran: bool,
+    ) -> Vec<Diagnostic> {
+        let mut used = vec![false; self.suppressions.len()];
+        let mut kept: Vec<_> = diags
+            .into_iter()
+            .filter(|diag| {
+                let code = match &diag.code {
+                    Some(NumberOrString::String(code)) => Some(code.clone()),
+                    Some(NumberOrString::Number(code)) => Some(code.to_string()),
+                    None => None,
+                };
+                let mut suppressed = false;
+                let line = diag.range.start.line;
+                for (index, suppression) in self.suppressions.iter().enumerate() {
+                    let enabled = suppression.enabled.iter().any(|(rule, from)| {
+                        line > *from && code.as_ref().is_some_and(|code| code == rule)
+                    });
+                    let applies = suppression.lines.contains(&line)
+                        && !enabled
+                        && (suppression.rules.is_empty()
+                            || code
+                                .as_ref()
+                                .is_some_and(|code| suppression.rules.contains(code)));
+                    if applies {
+                        used[index] = true;
+                        suppressed = true;
+                    }
+                }
+                !suppressed
+            })
+            .collect();
+        if !rule.enabled.unwrap_or(METADATA.enabled) {
+            return kept;
+        }
+
+        let unused = self
+            .suppressions
+            .iter()
+            .zip(used)
+            .filter(|(suppression, used)| {
+                let checked = match suppression.rules.is_empty() {
+                    true => all_ran,
+                    false => suppression.rules.iter().all(|rule| ran.contains(rule)),
+                };
+                !used && checked
+            });
+        for (suppression, _) in unused {
+            let mut diag = Diagnostic {
+                range: suppression.range,
+                message: match suppression.rules.is_empty() {
+                    true => format!("`{}` suppresses nothing", suppression.directive),
+                    false => format!(
+                        "`{} {}` suppresses nothing",
+                        suppression.directive,
+                        suppression.rules.join(", ")
+                    ),
+                },
+                tags: Some(vec![DiagnosticTag::UNNECESSARY]),
+                ..Default::default()
+            };
+            if let Some(uri) = &self.uri {
+                let edit = TextEdit {
+                    range: suppression.removal,
+                    new_text: String::new(),
+                };
+                let fix = Fix {
+                    title: "Remove unused suppression".to_string(),
+                    edit: WorkspaceEdit {
+                        changes: Some(HashMap::from([(uri.clone(), vec![edit])])),
+                        ..Default::default()
+                    },
+                };
+                Fix::attach(&mut diag, vec![fix]);
+            }
+            METADATA.label(&mut diag, rule.severity);
+            kept.push(diag);
+        }
+        kept
+    }
+}
+
+/// The comment at `span` with the whitespace before it, or its whole line when it's alone on it.
+fn removal(text: &str, span: Span) -> Span {
+    let lines = line_span(text, span);
+    if lines != span {
+        return lines;
+    }
+    let start = text[..span.start].trim_end_matches([' ', '\t']).len();
+    Span::new(start, span.end)
+}
+
+/// The edit inserting a `slap-disable-next-line` comment for the rule of `diag` above its line,
+/// with the same indentation and line ending.
+pub fn disable_next_line(text: &str, diag: &Diagnostic) -> TextEdit {
+    let start = LineIndex::new(text).line_start(diag.range.start.line as usize, text);
+    let line = &text[start..];
+    let end = line.find(['\r', '\n']).unwrap_or(line.len());
+    // The ending of the line, or of the first one for the last line.
+    let ending = match &line[end..] {
+        "" => text.find(['\r', '\n']).map_or("", |end| &text[end..]),
+        rest => rest,
+    };
+    let newline = match ending {
+        ending if ending.starts_with("\r\n") => "\r\n",
+        ending if ending.starts_with('\r') => "\r",
+        _ => "\n",
+    };
+    let line = &line[..end];
+    let indent = &line[..line.len() - line.trim_start().len()];
+    let comment = match &diag.code {
+        Some(NumberOrString::String(code)) => format!("// {DISABLE_NEXT_LINE} {code}"),
+        Some(NumberOrString::Number(code)) => format!("// {DISABLE_NEXT_LINE} {code}"),
+        None => format!("// {DISABLE_NEXT_LINE}"),
+    };
+    let start = Position::new(diag.range.start.line, 0);
+    TextEdit {
+        range: Range::new(start, start),
+        new_text: format!("{indent}{comment}{newline}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{disable_next_line, Suppressions};
+    use crate::{analysis::source::ParsedSource, config::Rule};
+    use std::collections::HashSet;
+    use tower_lsp::lsp_types::{Diagnostic, NumberOrString, Position, Range};
+
+    /// The `(line, rule)` findings left once the suppressions of `text` apply, without the unused
+    /// suppressions.
+    fn kept(text: &str, findings: &[(u32, &str)]) -> Vec<(u32, String)> {
+        let source = ParsedSource::from_text(text);
+        let diags = findings
+            .iter()
+            .map(|(line, rule)| Diagnostic {
+                range: Range::new(Position::new(*line, 0), Position::new(*line, 1)),
+                code: Some(NumberOrString::String(rule.to_string())),
+                ..Default::default()
+            })
+            .collect();
+        let ran = findings.iter().map(|(_, rule)| rule.to_string()).collect();
+        Suppressions::parse(&source)
+            .apply(diags, Rule::default(), &ran, true)
+            .into_iter()
+            .filter_map(|diag| match diag.code {
+                Some(NumberOrString::String(code)) if code != "unused-suppression" => {
+                    Some((diag.range.start.line, code))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn line_directives() {
+        let text = "// slap-disable-next-line a\ncontract C {} // slap-disable-line b\n";
+        let kept = kept(text, &[(1, "a"), (1, "b"), (1, "c"), (2, "a")]);
+        assert_eq!(kept, [(1, "c".to_string()), (2, "a".to_string())]);
+    }
+
+    #[test]
+    fn enabling_one_rule_keeps_a_blanket_range_open() {
+        let text = "// slap-disable\n\n// slap-enable a\n\n// slap-enable\n\n";
+        let kept = kept(text, &[(1, "a"), (1, "b"), (3, "a"), (3, "b"), (5, "b")]);
+        assert_eq!(kept, [(3, "a".to_string()), (5, "b".to_string())]);
+    }
+
+    #[test]
+    fn enabling_rules_one_by_one() {
+        let text = "// slap-disable a b\n\n// slap-enable a\n\n// slap-enable b\n\n";
+        let kept = kept(text, &[(1, "a"), (1, "b"), (3, "a"), (3, "b"), (5, "b")]);
+        assert_eq!(kept, [(3, "a".to_string()), (5, "b".to_string())]);
+    }
+
+    #[test]
+    fn nested_ranges() {
+        let text =
+            "// slap-disable a\n// slap-disable b\n\n// slap-enable b\n\n// slap-enable a\n\n";
+        let findings = [(2, "a"), (2, "b"), (4, "a"), (4, "b"), (6, "a")];
+        assert_eq!(
+            kept(text, &findings),
+            [(4, "b".to_string()), (6, "a".to_string())]
+        );
+    }
+
+    #[test]
+    fn a_range_left_open_runs_to_the_end() {
+        let text = "// slap-disable a\n\n// slap-enable c\n\n";
+        assert_eq!(kept(text, &[(3, "a"), (3, "b")]), [(3, "b".to_string())]);
+    }
+
+    /// The lines of the suppressions of `text` reported unused when the rules in `ran` ran without
+    /// finding anything.
+    fn unused(text: &str, ran: &[&str], all_ran: bool) -> Vec<u32> {
+        let source = ParsedSource::from_text(text);
+        let ran: HashSet<_> = ran.iter().map(|rule| rule.to_string()).collect();
+        Suppressions::parse(&source)
+            .apply(Vec::new(), Rule::default(), &ran, all_ran)
+            .into_iter()
+            .map(|diag| diag.range.start.line)
+            .collect()
+    }
+
+    #[test]
+    fn unused_only_once_its_rules_ran() {
+        let text = "// slap-disable-line a\n// slap-disable-line a b\n// slap-disable-line\n";
+        assert_eq!(unused(text, &["a"], false), [0]);
+        assert_eq!(unused(text, &["a", "b"], false), [0, 1]);
+        assert_eq!(unused(text, &["a", "b"], true), [0, 1, 2]);
+    }
+
+    #[test]
+    fn natspec_directives() {
+        let text = "/// slap-disable-next-line a\ncontract C {} /** slap-disable-line b */\n";
+        let kept = kept(text, &[(1, "a"), (1, "b"), (1, "c")]);
+        assert_eq!(kept, [(1, "c".to_string())]);
+    }
+
+    #[test]
+    fn disabling_the_next_line() {
+        let diag = |line| Diagnostic {
+            range: Range::new(Position::new(line, 4), Position::new(line, 5)),
+            code: Some(NumberOrString::String("a".to_string())),
+            ..Default::default()
+        };
+        for (text, newline) in [
+            ("x\n    y\n", "\n"),
+            ("x\r    y\r", "\r"),
+            ("x\r\n    y", "\r\n"),
+        ] {
+            let edit = disable_next_line(text, &diag(1));
+            assert_eq!(
+                edit.range,
+                Range::new(Position::new(1, 0), Position::new(1, 0))
+            );
+            assert_eq!(
+                edit.new_text,
+                format!("    // slap-disable-next-line a{newline}")
+            );
+        }
+    }
+}
@@ -0,0 +1,246 @@
+use super::{line_span, Category, Confidence, Detector, DetectorFuture, Fix, LspMessage, Metadata};
+use crate::analysis::{
+    model::{DeclKind, ReferenceKind},
+    source::ParsedSource,
+    syntax::{Span, SyntaxNode},
+};
+use slang_solidity::cst::{EdgeLabel, NonterminalKind};
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+};
+use tower_lsp::lsp_types::{
+    Diagnostic, DiagnosticSeverity, DiagnosticTag, TextEdit, Url, WorkspaceEdit,
+};
+
+static METADATA: Metadata = Metadata {
+    id: Cow::Borrowed("unused-import"),
+    name: Cow::Borrowed("Unused imports"),
+    category: Category::Style,
+    severity: DiagnosticSeverity::HINT,
+    confidence: Confidence::High,
+    description: Cow::Borrowed(
+        "Imported symbols and aliases that nothing in the file refers to. They make the \
+         dependencies of the file look larger than they are, and the fix removes them, or the \
+         whole import directive when none of its symbols is used.",
+    ),
+    url: Some(Cow::Borrowed(
+        "https://github.com/iFrostizz/slap#unused-import",
+    )),
+    enabled: true,
+};
+
+/// Imported symbols and aliases that nothing in the file refers to, with a fix removing them.
+#[derive(Debug)]
+pub struct UnusedImportsDetector;
+
+impl Detector for UnusedImportsDetector {
+    fn metadata(&self) -> &Metadata {
+        &METADATA
+    }
+
+    fn run(&self, source: &ParsedSource) -> DetectorFuture {
+        Box::pin(std::future::ready(Self::find_unused(source)))
+    }
+}
+
+/// An import directive and the spans of its `{...}` symbols.
+struct Directive {
+    span: Span,
+    symbols: Vec<Span>,
+}
+
+impl UnusedImportsDetector {
+    fn find_unused(source: &ParsedSource) -> Vec<LspMessage> {
+        let Ok(uri) = Url::from_file_path(&source.path) else {
+            return Vec::new();
+        };
+        let model = &source.model;
+        let root = SyntaxNode::root(source.tree());
+        let directives: Vec<_> = root
+            .child(EdgeLabel::Members)
+            .iter()
+            .flat_map(|members| members.items().collect::<Vec<_>>())
+            .map(|member| member.variant())
+            .filter(|member| member.is(NonterminalKind::ImportDirective))
+            .map(|directive| Directive {
+                span: directive.span(),
+                symbols: directive
+                    .descendants()
+                    .find(|node| node.is(NonterminalKind::ImportDeconstructionSymbols))
+                    .map(|symbols| symbols.items().map(|symbol| symbol.span()).collect())
+                    .unwrap_or_default(),
+            })
+            .collect();
+
+        // `@inheritdoc` names a base contract, which the compiler needs in scope.
+        let inherited: HashSet<&str> = model
+            .declarations
+            .iter()
+            .filter_map(|decl| decl.natspec.as_ref()?.inheritdoc.as_deref())
+            .filter_map(|inheritdoc| inheritdoc.split_whitespace().next())
+            .collect();
+        let used = |name: &str| {
+            inherited.contains(name)
+                || model.references.iter().any(|reference| {
+                    matches!(reference.kind, ReferenceKind::Name) && reference.name == name
+                })
+        };
+        // The unused declarations of each import.
+        let mut unused: HashMap<usize, Vec<usize>> = HashMap::new();
+        for (id, decl) in model.declarations.iter().enumerate() {
+            let Some(imported) = &decl.imported else {
+                continue;
+            };
+            if matches!(decl.kind, DeclKind::Import | DeclKind::Module) && !used(&decl.name) {
+                unused.entry(imported.import).or_default().push(id);
+            }
+        }
+
+        let mut diags = Vec::new();
+        for (import, ids) in unused {
+            let Some(path_span) = model.imports.get(import).map(|import| import.path_span) else {
+                continue;
+            };
+            let Some(directive) = directives
+                .iter()
+                .find(|directive| directive.span.contains(path_span.start))
+            else {
+                continue;
+            };
+            let declared = model
+                .declarations
+                .iter()
+                .filter(|decl| decl.imported.as_ref().is_some_and(|i| i.import == import))
+                .count();
+            // When none of its symbols is used, the whole directive goes.
+            let whole = ids.len() == declared;
+            for id in ids {
+                let decl = &model.declarations[id];
+                let removed = match whole {
+                    true => line_span(&source.text, directive.span),
+                    false => match symbol_span(&directive.symbols, decl.span) {
+                        Some(span) => span,
+                        None => continue,
+                    },
+                };
+                let edit = WorkspaceEdit {
+                    changes: Some(HashMap::from([(
+                        uri.clone(),
+                        vec![TextEdit {
+                            range: source.span_range(removed),
+                            new_text: String::new(),
+                        }],
+                    )])),
+                    ..Default::default()
+                };
+                let mut diag = Diagnostic {
+                    range: source.span_range(decl.name_span),
+                    message: format!("`{}` is imported but never used", decl.name),
+                    tags: Some(vec![DiagnosticTag::UNNECESSARY]),
+                    ..Default::default()
+                };
+                Fix::attach(
+                    &mut diag,
+                    vec![Fix {
+                        title: format!("Remove unused import `{}`", decl.name),
+                        edit,
+                    }],
+                );
+                diags.push(diag);
+            }
+        }
+        diags.sort_by_key(|diag| diag.range.start);
+        vec![LspMessage::Diagnostics {
+            path: source.path.clone(),
+            diags,
+        }]
+    }
+}
+
+/// The symbol at `span` with the comma separating it from the next one, or from the previous one
+/// for the last symbol.
+fn symbol_span(symbols: &[Span], span: Span) -> Option<Span> {
+    let index = symbols
+        .iter()
+        .position(|symbol| symbol.contains(span.start))?;
+    match (symbols.get(index + 1), index.checked_sub(1)) {
+        (Some(next), _) => Some(Span::new(symbols[index].start, next.start)),
+        (None, Some(previous)) => Some(Span::new(symbols[previous].end, symbols[index].end)),
+        (None, None) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::UnusedImportsDetector;
+    use crate::{
+        analysis::source::ParsedSource,
+        detectors::{Fix, LspMessage},
+    };
+
+    /// The names reported unused in `text`, and the text once the fix of each is applied alone.
+    fn unused(text: &str) -> Vec<(String, String)> {
+        let source = ParsedSource::from_text(text);
+        let messages = UnusedImportsDetector::find_unused(&source);
+        let [LspMessage::Diagnostics { diags, .. }] = &messages[..] else {
+            panic!("expected diagnostics, got {messages:?}");
+        };
+        diags
+            .iter()
+            .map(|diag| {
+                let name = source.text
+                    [source.offset(diag.range.start)..source.offset(diag.range.end)]
+                    .to_string();
+                let fixes = Fix::of(diag);
+                let [fix] = &fixes[..] else {
+                    panic!("expected one fix");
+                };
+                let edit = &fix.edit.changes.as_ref().unwrap().values().next().unwrap()[0];
+                let mut fixed = source.text.clone();
+                fixed.replace_range(
+                    source.offset(edit.range.start)..source.offset(edit.range.end),
+                    &edit.new_text,
+                );
+                (name, fixed)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn unused_symbols() {
+        let text = "import {A, B} from \"./A.sol\";\ncontract C is A {}\n";
+        let expected = "import {A} from \"./A.sol\";\ncontract C is A {}\n";
+        assert_eq!(unused(text), [("B".to_string(), expected.to_string())]);
+        let text = "import {A, B as D} from \"./A.sol\";\ncontract C is D {}\n";
+        let expected = "import {B as D} from \"./A.sol\";\ncontract C is D {}\n";
+        assert_eq!(unused(text), [("A".to_string(), expected.to_string())]);
+    }
+
+    #[test]
+    fn unused_directives() {
+        let text = "import {A} from \"./A.sol\";\nimport \"./B.sol\" as B;\ncontract C {}\n";
+        let without_a = "import \"./B.sol\" as B;\ncontract C {}\n";
+        let without_b = "import {A} from \"./A.sol\";\ncontract C {}\n";
+        assert_eq!(
+            unused(text),
+            [
+                ("A".to_string(), without_a.to_string()),
+                ("B".to_string(), without_b.to_string())
+            ]
+        );
+        // Imports of whole files declare nothing to report.
+        assert_eq!(unused("import \"./A.sol\";\ncontract C {}\n"), []);
+    }
+
+    #[test]
+    fn inheritdoc_is_a_use() {
+        let text = "import {IA} from \"./IA.sol\";
+contract C {
+    /// @inheritdoc IA
+    function f() external {}
+}
+";
+        assert_eq!(unused(text), []);
+    }
+}
@@ -0,0 +1,288 @@
+use std::{collections::HashMap, sync::RwLock};
+use tower_lsp::lsp_types::{
+    ClientCapabilities, Position, PositionEncodingKind, TextDocumentContentChangeEvent, Url,
+};
+
+/// Unit in which the `character` of an LSP `Position` is counted, as negotiated with the client.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum PositionEncoding {
+    Utf8,
+    /// Mandatory encoding, used when the client doesn't advertise anything else.
+    #[default]
+    Utf16,
+    Utf32,
+}
+
+impl PositionEncoding {
+    /// Picks the first encoding of the client's preference list that we support.
+    pub fn negotiate(capabilities: &ClientCapabilities) -> Self {
+        capabilities
+            .general
+            .as_ref()
+            .and_then(|general| general.position_encodings.as_ref())
+            .and_then(|encodings| {
+                encodings.iter().find_map(|kind| match kind.as_str() {
+                    "utf-8" => Some(Self::Utf8),
+                    "utf-16" => Some(Self::Utf16),
+                    "utf-32" => Some(Self::Utf32),
+                    _ => None,
+                })
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn kind(self) -> PositionEncodingKind {
+        match self {
+            Self::Utf8 => PositionEncodingKind::UTF8,
+            Self::Utf16 => PositionEncodingKind::UTF16,
+            Self::Utf32 => PositionEncodingKind::UTF32,
+        }
+    }
+
+    fn len(self, c: char) -> usize {
+        match self {
+            Self::Utf8 => c.len_utf8(),
+            Self::Utf16 => c.len_utf16(),
+            Self::Utf32 => 1,
+        }
+    }
+}
+
+/// Byte offsets of the start of every line, to convert between offsets and LSP positions.
+///
+/// Lines are split on `\n`, `\r\n` and `\r`, which are the terminators the LSP spec knows about.
+#[derive(Debug, Clone)]
+pub struct LineIndex {
+    line_starts: Vec<usize>,
+}
+
+impl LineIndex {
+    pub fn new(text: &str) -> Self {
+        let mut line_starts = vec![0];
+        let bytes = text.as_bytes();
+        for (i, byte) in bytes.iter().enumerate() {
+            match byte {
+                b'\n' => line_starts.push(i + 1),
+                b'\r' if bytes.get(i + 1) != Some(&b'\n') => line_starts.push(i + 1),
+                _ => {}
+            }
+        }
+        Self { line_starts }
+    }
+
+    /// Byte offset of the start of `line`, clamped to the end of the text.
+    pub fn line_start(&self, line: usize, text: &str) -> usize {
+        self.line_starts.get(line).copied().unwrap_or(text.len())
+    }
+
+    /// Converts a byte offset into `text` to an LSP position.
+    pub fn position(&self, text: &str, offset: usize, encoding: PositionEncoding) -> Position {
+        let offset = offset.min(text.len());
+        let line = self.line_starts.partition_point(|&start| start <= offset) - 1;
+        let start = self.line_starts[line];
+        let character = text
+            .get(start..offset)
+            .map(|prefix| prefix.chars().map(|c| encoding.len(c)).sum())
+            .unwrap_or(offset - start);
+        Position::new(line as u32, character as u32)
+    }
+
+    /// Converts an LSP position to a byte offset into `text`.
+    ///
+    /// Positions past the end of a line are clamped to the line end, as required by the spec.
+    pub fn offset(&self, text: &str, position: Position, encoding: PositionEncoding) -> usize {
+        let line = position.line as usize;
+        if line >= self.line_starts.len() {
+            return text.len();
+        }
+        let start = self.line_starts[line];
+        let end = self.line_start(line + 1, text);
+        let line_text = text[start..end].trim_end_matches(['\n', '\r']);
+
+        let mut units = 0;
+        for (i, c) in line_text.char_indices() {
+            if units >= position.character as usize {
+                return start + i;
+            }
+            units += encoding.len(c);
+        }
+        start + line_text.len()
+    }
+}
+
+/// A text document opened by the client, holding the buffer content rather than the file on disk.
+#[derive(Debug, Clone)]
+pub struct Document {
+    pub version: i32,
+    pub text: String,
+}
+
+impl Document {
+    pub fn new(version: i32, text: String) -> Self {
+        Self { version, text }
+    }
+
+    /// Applies a `didChange` content change, which replaces either a range or the whole text.
+    pub fn apply_change(
+        &mut self,
+        change: TextDocumentContentChangeEvent,
+        encoding: PositionEncoding,
+    ) {
+        match change.range {
+            Some(range) => {
+                let index = LineIndex::new(&self.text);
+                let start = index.offset(&self.text, range.start, encoding);
+                let end = index.offset(&self.text, range.end, encoding).max(start);
+                self.text.replace_range(start..end, &change.text);
+            }
+            None => self.text = change.text,
+        }
+    }
+}
+
+/// Every document currently open in the client, keyed by URI.
+#[derive(Debug, Default)]
+pub struct DocumentStore {
+    documents: RwLock<HashMap<Url, Document>>,
+}
+
+impl DocumentStore {
+    pub fn open(&self, uri: Url, version: i32, text: String) {
+        self.documents
+            .write()
+            .unwrap()
+            .insert(uri, Document::new(version, text));
+    }
+
+    /// Applies the changes of a `didChange` notification in order, and bumps the document version.
+    /// Changes to a version the document already has or is past are dropped, since they don't
+    /// apply to its current text.
+    pub fn change(
+        &self,
+        uri: &Url,
+        version: i32,
+        changes: Vec<TextDocumentContentChangeEvent>,
+        encoding: PositionEncoding,
+    ) {
+        let mut documents = self.documents.write().unwrap();
+        let Some(document) = documents.get_mut(uri) else {
+            log::warn!("change to a document that isn't open: {uri}");
+            return;
+        };
+        if version <= document.version {
+            log::warn!(
+                "dropping out of order change to {uri}: version {version} after {}",
+                document.version
+            );
+            return;
+        }
+        for change in changes {
+            document.apply_change(change, encoding);
+        }
+        document.version = version;
+    }
+
+    pub fn close(&self, uri: &Url) {
+        self.documents.write().unwrap().remove(uri);
+    }
+
+    pub fn get(&self, uri: &Url) -> Option<Document> {
+        self.documents.read().unwrap().get(uri).cloned()
+    }
+
+    /// The version of the document, `None` when it isn't open.
+    pub fn version(&self, uri: &Url) -> Option<i32> {
+        self.documents
+            .read()
+            .unwrap()
+            .get(uri)
+            .map(|document| document.version)
+    }
+
+    pub fn uris(&self) -> Vec<Url> {
+        self.documents.read().unwrap().keys().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Document, DocumentStore, LineIndex, PositionEncoding};
+    use tower_lsp::lsp_types::{Position, Range, TextDocumentContentChangeEvent, Url};
+
+    fn change(range: Option<Range>, text: &str) -> TextDocumentContentChangeEvent {
+        TextDocumentContentChangeEvent {
+            range,
+            range_length: None,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn line_terminators() {
+        let text = "a\r\nb\rc\nd";
+        let index = LineIndex::new(text);
+        let encoding = PositionEncoding::Utf16;
+        for (offset, line, character) in [(0, 0, 0), (3, 1, 0), (5, 2, 0), (7, 3, 0), (8, 3, 1)] {
+            let position = Position::new(line, character);
+            assert_eq!(index.position(text, offset, encoding), position);
+            assert_eq!(index.offset(text, position, encoding), offset);
+        }
+        // Past the end of a line is its end, before the `\r\n`.
+        assert_eq!(index.offset(text, Position::new(0, 5), encoding), 1);
+        assert_eq!(
+            index.offset(text, Position::new(9, 0), encoding),
+            text.len()
+        );
+    }
+
+    #[test]
+    fn encodings() {
+        // `é` is 2 bytes and 1 UTF-16 unit, `😀` 4 bytes and 2 UTF-16 units.
+        let text = "é😀x";
+        let index = LineIndex::new(text);
+        let x = text.len() - 1;
+        for (encoding, character) in [
+            (PositionEncoding::Utf8, 6),
+            (PositionEncoding::Utf16, 3),
+            (PositionEncoding::Utf32, 2),
+        ] {
+            let position = Position::new(0, character);
+            assert_eq!(index.position(text, x, encoding), position);
+            assert_eq!(index.offset(text, position, encoding), x);
+        }
+        // Inside a surrogate pair rounds to the end of the character.
+        let position = Position::new(0, 2);
+        assert_eq!(index.offset(text, position, PositionEncoding::Utf16), x);
+    }
+
+    #[test]
+    fn apply_changes() {
+        let mut document = Document::new(1, "a😀b\r\ncd\r\n".to_string());
+        let range = Range::new(Position::new(0, 3), Position::new(1, 1));
+        document.apply_change(change(Some(range), "X"), PositionEncoding::Utf16);
+        assert_eq!(document.text, "a😀Xd\r\n");
+        let end = Position::new(1, 0);
+        document.apply_change(
+            change(Some(Range::new(end, end)), "e"),
+            PositionEncoding::Utf16,
+        );
+        assert_eq!(document.text, "a😀Xd\r\ne");
+        document.apply_change(change(None, "f"), PositionEncoding::Utf16);
+        assert_eq!(document.text, "f");
+    }
+
+    #[test]
+    fn stale_changes_are_dropped() {
+        let store = DocumentStore::default();
+        let uri = Url::parse("file:///A.sol").unwrap();
+        store.open(uri.clone(), 2, "a".to_string());
+        let encoding = PositionEncoding::Utf16;
+        for version in [1, 2] {
+            store.change(&uri, version, vec![change(None, "stale")], encoding);
+        }
+        assert_eq!(store.get(&uri).unwrap().text, "a");
+        store.change(&uri, 3, vec![change(None, "b")], encoding);
+        assert_eq!(store.version(&uri), Some(3));
+        assert_eq!(store.get(&uri).unwrap().text, "b");
+    }
+}
@@ -0,0 +1,127 @@
+use crate::{
+    analysis::source::PARSER_DIAGNOSTIC_SOURCE,
+    detectors::{intersects, suppressions, Fix},
+};
+use std::collections::HashMap;
+use tower_lsp::lsp_types::{
+    CodeAction, CodeActionKind, CodeActionOrCommand, Diagnostic, NumberOrString, Range, TextEdit,
+    Url, WorkspaceEdit,
+};
+
+/// The fixes of the findings in `range`, and for each kind of finding there, one fixing all the
+/// findings of that kind in the file when there are several. Then the suppression comments that
+/// would silence each finding. `diags` are the ones published for the file of `uri`, whose
+/// content is `text`.
+pub fn code_actions(
+    uri: &Url,
+    text: &str,
+    diags: &[Diagnostic],
+    range: Range,
+) -> Vec<CodeActionOrCommand> {
+    let mut actions = Vec::new();
+    let mut codes = Vec::new();
+    let mut suppress = Vec::new();
+    for diag in diags.iter().filter(|diag| intersects(&diag.range, &range)) {
+        let fixes = Fix::of(diag);
+        let preferred = fixes.len() == 1;
+        for fix in fixes {
+            actions.push(action(fix.title, fix.edit, vec![diag.clone()], preferred));
+        }
+
+        let code = match &diag.code {
+            Some(NumberOrString::Number(code)) => Some(code.to_string()),
+            Some(NumberOrString::String(code)) => Some(code.clone()),
+            None => None,
+        };
+        // Syntax errors aren't findings, and suppressing an unused suppression makes no sense.
+        let suppressible = diag.source.as_deref() != Some(PARSER_DIAGNOSTIC_SOURCE)
+            && code.as_deref() != Some(&suppressions::METADATA.id);
+        if suppressible {
+            let title = match &code {
+                Some(code) => format!("Suppress `{code}` on this line"),
+                None => "Suppress on this line".to_string(),
+            };
+            let edit = WorkspaceEdit {
+                changes: Some(HashMap::from([(
+                    uri.clone(),
+                    vec![suppressions::disable_next_line(text, diag)],
+                )])),
+                ..Default::default()
+            };
+            suppress.push(action(title, edit, vec![diag.clone()], false));
+        }
+
+        if let Some(code) = &diag.code {
+            if !codes.contains(code) {
+                codes.push(code.clone());
+            }
+        }
+    }
+
+    for code in codes {
+        let fixed: Vec<_> = diags
+            .iter()
+            .filter(|diag| diag.code.as_ref() == Some(&code))
+            .filter_map(|diag| Some((diag, Fix::of(diag).into_iter().next()?)))
+            .collect();
+        if fixed.len() < 2 {
+            continue;
+        }
+        let code = match code {
+            NumberOrString::Number(code) => code.to_string(),
+            NumberOrString::String(code) => code,
+        };
+        let edit = merge(fixed.iter().map(|(_, fix)| &fix.edit));
+        let diags = fixed.into_iter().map(|(diag, _)| diag.clone()).collect();
+        let title = format!("Fix all `{code}` findings in the file");
+        actions.push(action(title, edit, diags, false));
+    }
+    actions.extend(suppress);
+    actions
+}
+
+fn action(
+    title: String,
+    edit: WorkspaceEdit,
+    diagnostics: Vec<Diagnostic>,
+    is_preferred: bool,
+) -> CodeActionOrCommand {
+    CodeActionOrCommand::CodeAction(CodeAction {
+        title,
+        kind: Some(CodeActionKind::QUICKFIX),
+        diagnostics: Some(diagnostics),
+        edit: Some(edit),
+        is_preferred: Some(is_preferred),
+        ..Default::default()
+    })
+}
+
+/// The edits of all the fixes, without the ones overlapping an edit that comes before them,
+/// which the client would reject.
+fn merge<'a>(edits: impl Iterator<Item = &'a WorkspaceEdit>) -> WorkspaceEdit {
+    let mut changes: HashMap<Url, Vec<TextEdit>> = HashMap::new();
+    for edit in edits {
+        for (uri, edits) in edit.changes.iter().flatten() {
+            changes
+                .entry(uri.clone())
+                .or_default()
+                .extend(edits.iter().cloned());
+        }
+    }
+    for edits in changes.values_mut() {
+        edits.sort_by_key(|edit| (edit.range.start, edit.range.end));
+        edits.dedup();
+        let mut end = None;
+        edits.retain(|edit| {
+            let keep = end.is_none_or(|end| edit.range.start >= end);
+            if keep {
+                end = Some(edit.range.end);
+            }
+            keep
+        });
+    }
+    WorkspaceEdit {
+        changes: Some(changes),
+        ..Default::default()
+    }
+}
@@ -0,0 +1,671 @@
+use crate::{
+    analysis::{
+        model::{DeclKind, ReferenceKind},
+        semantics::{self, Definition, Semantics},
+        source::{ParsedSource, SourceFile},
+        syntax::Span,
+        Analysis,
+    },
+    index::IndexHandle,
+    project,
+};
+use slang_solidity::cst::{NonterminalKind, TerminalKind, TerminalKindExtensions};
+use std::{
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+use tower_lsp::lsp_types::{
+    CompletionItem, CompletionItemKind, CompletionItemLabelDetails, CompletionResponse,
+    CompletionTextEdit, Documentation, MarkupContent, MarkupKind, Position, Range, SymbolKind,
+    TextEdit,
+};
+
+/// Stands in for the name being typed, so that the parser sees an identifier at the cursor.
+pub const MARKER: &str = "__slap_completion__";
+
+/// Ranks symbols that need an import after every name in scope.
+const UNIMPORTED_RANK: usize = 9998;
+
+/// Ranks keywords and elementary types last.
+const KEYWORD_RANK: usize = 9999;
+
+const FILE_KEYWORDS: &[&str] = &[
+    "pragma",
+    "import",
+    "abstract",
+    "contract",
+    "interface",
+    "library",
+    "function",
+    "struct",
+    "enum",
+    "event",
+    "error",
+    "type",
+    "using",
+    "constant",
+];
+
+const CONTRACT_KEYWORDS: &[&str] = &[
+    "function",
+    "modifier",
+    "constructor",
+    "fallback",
+    "receive",
+    "struct",
+    "enum",
+    "event",
+    "error",
+    "type",
+    "using",
+    "mapping",
+    "public",
+    "internal",
+    "private",
+    "constant",
+    "immutable",
+    "override",
+];
+
+const HEADER_KEYWORDS: &[&str] = &[
+    "public", "external", "internal", "private", "pure", "view", "payable", "virtual", "override",
+    "returns",
+];
+
+const PARAMETER_KEYWORDS: &[&str] = &["memory", "storage", "calldata", "mapping"];
+
+const BODY_KEYWORDS: &[&str] = &[
+    "if",
+    "else",
+    "for",
+    "while",
+    "do",
+    "break",
+    "continue",
+    "return",
+    "emit",
+    "try",
+    "catch",
+    "unchecked",
+    "assembly",
+    "new",
+    "delete",
+    "mapping",
+    "memory",
+    "storage",
+    "calldata",
+    "payable",
+    "true",
+    "false",
+];
+
+const YUL_KEYWORDS: &[&str] = &[
+    "let", "if", "switch", "case", "default", "for", "function", "leave", "break", "continue",
+];
+
+const NATSPEC_TAGS: &[(&str, &str)] = &[
+    (
+        "@title",
+        "A title that should describe the contract or interface.",
+    ),
+    ("@author", "The name of the author."),
+    ("@notice", "Explains to an end user what this does."),
+    ("@dev", "Explains to a developer any extra details."),
+    ("@param", "Documents a parameter, followed by its name."),
+    ("@return", "Documents a return variable."),
+    (
+        "@inheritdoc",
+        "Copies all missing tags from the base function, followed by the contract name.",
+    ),
+    (
+        "@custom:",
+        "Custom tag, with an application-defined meaning.",
+    ),
+];
+
+/// What is expected where the cursor is, which decides what to suggest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Context {
+    /// Between the declarations of a file.
+    File,
+    /// Between the members of a contract.
+    Contract,
+    /// Among the attributes of a function, where modifiers go.
+    Header,
+    /// In a parameter list or the fields of a struct, where types go.
+    Parameters,
+    /// In a statement or an expression.
+    Body,
+    Yul,
+}
+
+/// Completion at the cursor: paths in an import, the members of the value before a `.`, NatSpec
+/// tags after a `@` in documentation, otherwise the names in scope, elementary types and keywords
+/// that fit there, along with the contracts of the workspace that would need an import.
+pub fn completion(
+    analysis: &mut Analysis,
+    file: &Rc<SourceFile>,
+    position: Position,
+    index: &IndexHandle,
+) -> Option<CompletionResponse> {
+    let offset = file.offset(position);
+    if let Some(items) = import_paths(analysis, file, offset) {
+        return Some(CompletionResponse::Array(items));
+    }
+
+    let start = word_start(&file.text, offset);
+    let text = format!("{}{MARKER}{}", &file.text[..start], &file.text[offset..]);
+    let completed = analysis.parse_text(file, text);
+    let (kind, ancestors) = marker(&completed, start)?;
+
+    let items = match kind {
+        TerminalKind::SingleLineNatSpecComment | TerminalKind::MultiLineNatSpecComment => {
+            if !file.text[..start].ends_with('@') {
+                return None;
+            }
+            tags(file, Span::new(start - 1, offset))
+        }
+        kind if matches!(kind, TerminalKind::Identifier | TerminalKind::YulIdentifier)
+            || !kind.is_valid() =>
+        {
+            if file.text[..start].trim_end().ends_with('.') {
+                members(analysis, &completed.file, start)?
+            } else {
+                let context = context(&ancestors);
+                let mut items = names(analysis, &completed.file, start, context);
+                if !matches!(context, Context::Header | Context::Yul) {
+                    let prefix = &file.text[start..offset];
+                    let unimported = unimported(analysis, file, prefix, &items, index);
+                    items.extend(unimported);
+                }
+                items
+            }
+        }
+        _ => return None,
+    };
+    Some(CompletionResponse::Array(items))
+}
+
+/// Start of the identifier the cursor is at the end of.
+fn word_start(text: &str, offset: usize) -> usize {
+    text[..offset]
+        .char_indices()
+        .rev()
+        .find(|&(_, c)| !(c.is_ascii_alphanumeric() || c == '_' || c == '$'))
+        .map_or(0, |(index, c)| index + c.len_utf8())
+}
+
+/// The kind of the terminal the marker at `start` ended up in, with the kinds of the nodes
+/// around it from the innermost.
+fn marker(source: &ParsedSource, start: usize) -> Option<(TerminalKind, Vec<NonterminalKind>)> {
+    let mut cursor = source.cursor();
+    while cursor.go_to_next_terminal() {
+        let range = cursor.text_range();
+        if range.start.utf8 <= start && start < range.end.utf8 {
+            let kind = cursor.node().as_terminal()?.kind;
+            let ancestors = cursor.ancestors().map(|node| node.kind).collect();
+            return Some((kind, ancestors));
+        }
+    }
+    None
+}
+
+fn context(ancestors: &[NonterminalKind]) -> Context {
+    use NonterminalKind as K;
+
+    for kind in ancestors {
+        match kind {
+            K::YulBlock => return Context::Yul,
+            K::Expression | K::Block => return Context::Body,
+            K::ModifierInvocation => return Context::Header,
+            K::ParametersDeclaration
+            | K::ReturnsDeclaration
+            | K::EventParametersDeclaration
+            | K::ErrorParametersDeclaration
+            | K::StructMembers => return Context::Parameters,
+            K::ContractMembers
+            | K::ContractDefinition
+            | K::InterfaceDefinition
+            | K::LibraryDefinition => return Context::Contract,
+            _ => {}
+        }
+    }
+    Context::File
+}
+
+/// What can be named at `start` in `file`, the completed text.
+fn names(
+    analysis: &mut Analysis,
+    file: &Rc<SourceFile>,
+    start: usize,
+    context: Context,
+) -> Vec<CompletionItem> {
+    let mut semantics = Semantics::new(analysis);
+    let scope = file.model.scope_at(start);
+    let visible = semantics.visible(file, scope, start);
+    let mut items: Vec<_> = visible
+        .iter()
+        .filter(|(definition, _)| fits(definition, context))
+        .filter_map(|(definition, distance)| {
+            let mut item = item(&mut semantics, definition)?;
+            item.sort_text = Some(format!("{distance:04}{}", item.label));
+            Some(item)
+        })
+        .collect();
+
+    let keywords = match context {
+        Context::File => FILE_KEYWORDS,
+        Context::Contract => CONTRACT_KEYWORDS,
+        Context::Header => HEADER_KEYWORDS,
+        Context::Parameters => PARAMETER_KEYWORDS,
+        Context::Body => BODY_KEYWORDS,
+        Context::Yul => YUL_KEYWORDS,
+    };
+    let types = match context {
+        Context::Header | Context::Yul => Vec::new(),
+        _ => elementary_types(),
+    };
+    let keywords = keywords
+        .iter()
+        .map(|keyword| (keyword.to_string(), CompletionItemKind::KEYWORD))
+        .chain(
+            types
+                .into_iter()
+                .map(|ty| (ty, CompletionItemKind::TYPE_PARAMETER)),
+        );
+    items.extend(keywords.map(|(label, kind)| CompletionItem {
+        sort_text: Some(format!("{KEYWORD_RANK:04}{label}")),
+        label,
+        kind: Some(kind),
+        ..CompletionItem::default()
+    }));
+    items
+}
+
+/// The contracts, interfaces and libraries of the workspace matching `prefix` that aren't among
+/// `items` yet, completed along with an import of their file.
+fn unimported(
+    analysis: &mut Analysis,
+    file: &SourceFile,
+    prefix: &str,
+    items: &[CompletionItem],
+    index: &IndexHandle,
+) -> Vec<CompletionItem> {
+    if prefix.is_empty() {
+        return Vec::new();
+    }
+    let mut unimported: Vec<CompletionItem> = Vec::new();
+    for symbol in index.search(prefix) {
+        let kind = match symbol.kind {
+            SymbolKind::CLASS => CompletionItemKind::CLASS,
+            SymbolKind::INTERFACE => CompletionItemKind::INTERFACE,
+            SymbolKind::MODULE => CompletionItemKind::MODULE,
+            _ => continue,
+        };
+        let Ok(path) = symbol.location.uri.to_file_path() else {
+            continue;
+        };
+        if symbol.container_name.is_some()
+            || path == file.path
+            || items.iter().any(|item| item.label == symbol.name)
+            || unimported.iter().any(|item| item.label == symbol.name)
+        {
+            continue;
+        }
+        let Some(import) = import_path(analysis, &file.path, &path) else {
+            continue;
+        };
+        let (offset, separator) = import_offset(file);
+        let position = file.position(offset);
+        let statement = format!("{separator}import {{{}}} from \"{import}\";\n", symbol.name);
+        unimported.push(CompletionItem {
+            label: symbol.name.clone(),
+            label_details: Some(CompletionItemLabelDetails {
+                detail: None,
+                description: Some(import.clone()),
+            }),
+            kind: Some(kind),
+            detail: Some(format!("import {{{}}} from \"{import}\"", symbol.name)),
+            sort_text: Some(format!("{UNIMPORTED_RANK:04}{}", symbol.name)),
+            additional_text_edits: Some(vec![TextEdit::new(
+                Range::new(position, position),
+                statement,
+            )]),
+            ..CompletionItem::default()
+        });
+    }
+    unimported
+}
+
+/// Where a new import goes, with what separates it from the line before: after the last import,
+/// otherwise after the pragmas, otherwise at the top.
+fn import_offset(file: &SourceFile) -> (usize, &'static str) {
+    let next_line = |offset: usize| {
+        file.text[offset..]
+            .find('\n')
+            .map_or(file.text.len(), |newline| offset + newline + 1)
+    };
+    if let Some(import) = file.model.imports.last() {
+        return (next_line(import.path_span.end), "");
+    }
+    let mut pragma = None;
+    let mut offset = 0;
+    for line in file.text.split_inclusive('\n') {
+        if line.trim_start().starts_with("pragma ") {
+            pragma = Some(offset);
+        }
+        offset += line.len();
+    }
+    match pragma {
+        Some(pragma) => (next_line(pragma), "\n"),
+        None => (0, ""),
+    }
+}
+
+/// How `from` would import `target`: through a remapping for the libraries of its project, by
+/// package name in `node_modules`, otherwise relative to `from`.
+fn import_path(analysis: &mut Analysis, from: &Path, target: &Path) -> Option<String> {
+    if let Some(project) = analysis.project(from) {
+        let libs: Vec<PathBuf> = project
+            .libs
+            .iter()
+            .map(|lib| project.root.join(lib))
+            .collect();
+        let remapped = project
+            .remappings
+            .iter()
+            .filter(|remapping| remapping.context.is_none())
+            .filter_map(|remapping| {
+                let base = project::normalize(&project.root.join(&remapping.target));
+                let rest = target.strip_prefix(&base).ok()?;
+                Some((base, remapping, rest))
+            })
+            .filter(|(base, ..)| libs.iter().any(|lib| base.starts_with(lib)))
+            .max_by_key(|(base, ..)| base.components().count());
+        if let Some((_, remapping, rest)) = remapped {
+            let rest = components(rest).join("/");
+            return Some(match remapping.prefix.ends_with('/') {
+                true => format!("{}{rest}", remapping.prefix),
+                false => format!("{}/{rest}", remapping.prefix),
+            });
+        }
+    }
+
+    let parts = components(target);
+    if let Some(package) = parts.iter().rposition(|part| part == "node_modules") {
+        return Some(parts[package + 1..].join("/"));
+    }
+
+    let from = components(from.parent()?);
+    let common = from.iter().zip(&parts).take_while(|(a, b)| a == b).count();
+    let mut relative = match from.len() - common {
+        0 => vec![".".to_string()],
+        up => vec!["..".to_string(); up],
+    };
+    relative.extend(parts[common..].iter().cloned());
+    Some(relative.join("/"))
+}
+
+fn components(path: &Path) -> Vec<String> {
+    path.components()
+        .map(|component| component.as_os_str().to_string_lossy().into_owned())
+        .collect()
+}
+
+/// The directories and Solidity files that complete the path of an import the cursor is in,
+/// along with the remapped prefixes.
+fn import_paths(
+    analysis: &mut Analysis,
+    file: &SourceFile,
+    offset: usize,
+) -> Option<Vec<CompletionItem>> {
+    let line_start = file.text[..offset]
+        .rfind('\n')
+        .map_or(0, |newline| newline + 1);
+    let line = &file.text[line_start..offset];
+    let quote = line.rfind(['"', '\''])?;
+    let before = line[..quote].trim_end();
+    if !before.ends_with("import") && !before.ends_with("from") {
+        return None;
+    }
+    let typed = &line[quote + 1..];
+    let typed_start = line_start + quote + 1;
+    let directory = &typed[..typed.rfind('/').map_or(0, |slash| slash + 1)];
+    let name_range = file.span_range(Span::new(typed_start + directory.len(), offset));
+
+    let mut directories = Vec::new();
+    let mut items = Vec::new();
+    if typed.starts_with('.') {
+        directories.extend(file.path.parent().map(|parent| parent.join(directory)));
+    } else {
+        if let Some(project) = analysis.project(&file.path) {
+            for remapping in &project.remappings {
+                if let Some(rest) = directory.strip_prefix(remapping.prefix.as_str()) {
+                    directories.push(project.root.join(&remapping.target).join(rest));
+                }
+                if directory.is_empty() {
+                    let range = file.span_range(Span::new(typed_start, offset));
+                    items.push(path_item(
+                        &remapping.prefix,
+                        range,
+                        CompletionItemKind::FOLDER,
+                    ));
+                }
+            }
+            directories.push(project.root.join(directory));
+            directories.extend(
+                project
+                    .libs
+                    .iter()
+                    .map(|lib| project.root.join(lib).join(directory)),
+            );
+        }
+        directories.extend(
+            file.path
+                .ancestors()
+                .skip(1)
+                .map(|ancestor| ancestor.join("node_modules").join(directory)),
+        );
+    }
+
+    for directory in directories {
+        let Ok(entries) = std::fs::read_dir(&directory) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let path = entry.path();
+            let (label, kind) = if name.starts_with('.') {
+                continue;
+            } else if path.is_dir() {
+                (format!("{name}/"), CompletionItemKind::FOLDER)
+            } else if path.extension().is_some_and(|ext| ext == "sol") {
+                (name, CompletionItemKind::FILE)
+            } else {
+                continue;
+            };
+            items.push(path_item(&label, name_range, kind));
+        }
+    }
+    items.sort_by(|a, b| a.label.cmp(&b.label));
+    items.dedup_by(|a, b| a.label == b.label);
+    Some(items)
+}
+
+fn path_item(label: &str, range: Range, kind: CompletionItemKind) -> CompletionItem {
+    CompletionItem {
+        label: label.to_string(),
+        kind: Some(kind),
+        text_edit: Some(CompletionTextEdit::Edit(TextEdit::new(
+            range,
+            label.to_string(),
+        ))),
+        ..CompletionItem::default()
+    }
+}
+
+/// Whether the definition can be named in `context`.
+fn fits(definition: &Definition, context: Context) -> bool {
+    let Definition::Declaration(symbol) = definition else {
+        return context == Context::Body;
+    };
+    let kind = symbol.decl().kind;
+    let is_type = kind.is_type() || matches!(kind, DeclKind::Module | DeclKind::Import);
+    match context {
+        Context::Body => true,
+        Context::Header => {
+            matches!(kind, DeclKind::Modifier | DeclKind::Import) || kind.is_contract()
+        }
+        Context::File | Context::Contract | Context::Parameters => is_type,
+        Context::Yul => matches!(
+            kind,
+            DeclKind::Parameter
+                | DeclKind::LocalVariable
+                | DeclKind::YulVariable
+                | DeclKind::YulFunction
+                | DeclKind::StateVariable
+                | DeclKind::Constant
+        ),
+    }
+}
+
+fn elementary_types() -> Vec<String> {
+    let sized = (8..=256)
+        .step_by(8)
+        .flat_map(|bits| [format!("uint{bits}"), format!("int{bits}")]);
+    let bytes = (1..=32).map(|size| format!("bytes{size}"));
+    ["address", "bool", "string", "bytes", "uint", "int"]
+        .into_iter()
+        .map(str::to_string)
+        .chain(sized)
+        .chain(bytes)
+        .collect()
+}
+
+/// The members of the value before the `.` the marker at `start` follows, according to its type.
+fn members(
+    analysis: &mut Analysis,
+    file: &Rc<SourceFile>,
+    start: usize,
+) -> Option<Vec<CompletionItem>> {
+    let reference = file
+        .model
+        .references
+        .iter()
+        .find(|reference| reference.span.start == start)?;
+    let ReferenceKind::Member(receiver) = &reference.kind else {
+        return None;
+    };
+    let mut semantics = Semantics::new(analysis);
+    let ty = semantics.type_of(file, reference.scope, receiver)?;
+    let items = semantics
+        .members_in(file, reference.scope, &ty)
+        .iter()
+        .filter_map(|member| item(&mut semantics, member))
+        .collect();
+    Some(items)
+}
+
+/// The NatSpec tags, replacing the `@` at the start of `span` and what follows.
+fn tags(file: &SourceFile, span: Span) -> Vec<CompletionItem> {
+    let range = file.span_range(span);
+    NATSPEC_TAGS
+        .iter()
+        .map(|(tag, doc)| CompletionItem {
+            label: tag.to_string(),
+            kind: Some(CompletionItemKind::KEYWORD),
+            detail: Some(doc.to_string()),
+            text_edit: Some(CompletionTextEdit::Edit(TextEdit::new(
+                range,
+                tag.to_string(),
+            ))),
+            ..CompletionItem::default()
+        })
+        .collect()
+}
+
+fn item(semantics: &mut Semantics, definition: &Definition) -> Option<CompletionItem> {
+    let (kind, detail, documentation) = match definition {
+        Definition::Declaration(symbol) => {
+            let decl = symbol.decl();
+            if decl.name.is_empty()
+                || decl.name.contains(MARKER)
+                || matches!(
+                    decl.kind,
+                    DeclKind::Constructor | DeclKind::Fallback | DeclKind::Receive
+                )
+            {
+                return None;
+            }
+            (
+                item_kind(decl.kind, decl.container.is_some()),
+                decl.signature.clone(),
+                semantics
+                    .natspec(symbol)
+                    .map(|natspec| natspec.to_markdown()),
+            )
+        }
+        Definition::Builtin(builtin) => (
+            match (builtin.function, builtin.namespace.is_empty()) {
+                (true, true) => CompletionItemKind::FUNCTION,
+                (true, false) => CompletionItemKind::METHOD,
+                (false, true) => CompletionItemKind::VARIABLE,
+                (false, false) => CompletionItemKind::PROPERTY,
+            },
+            builtin.signature.to_string(),
+            Some(builtin.doc.to_string()),
+        ),
+    };
+    Some(CompletionItem {
+        label: semantics::name(definition).to_string(),
+        kind: Some(kind),
+        detail: Some(detail),
+        documentation: documentation.map(|value| {
+            Documentation::MarkupContent(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value,
+            })
+        }),
+        ..CompletionItem::default()
+    })
+}
+
+fn item_kind(kind: DeclKind, member: bool) -> CompletionItemKind {
+    match kind {
+        DeclKind::Contract => CompletionItemKind::CLASS,
+        DeclKind::Interface => CompletionItemKind::INTERFACE,
+        DeclKind::Library | DeclKind::Module => CompletionItemKind::MODULE,
+        DeclKind::Function if member => CompletionItemKind::METHOD,
+        DeclKind::Function | DeclKind::YulFunction => CompletionItemKind::FUNCTION,
+        DeclKind::Modifier => CompletionItemKind::METHOD,
+        DeclKind::Constructor | DeclKind::Fallback | DeclKind::Receive => {
+            CompletionItemKind::CONSTRUCTOR
+        }
+        DeclKind::StateVariable | DeclKind::StructMember => CompletionItemKind::FIELD,
+        DeclKind::Constant => CompletionItemKind::CONSTANT,
+        DeclKind::Struct => CompletionItemKind::STRUCT,
+        DeclKind::Enum => CompletionItemKind::ENUM,
+        DeclKind::EnumMember => CompletionItemKind::ENUM_MEMBER,
+        DeclKind::Event | DeclKind::Error => CompletionItemKind::EVENT,
+        DeclKind::UserDefinedValueType => CompletionItemKind::TYPE_PARAMETER,
+        DeclKind::Parameter | DeclKind::LocalVariable | DeclKind::YulVariable => {
+            CompletionItemKind::VARIABLE
+        }
+        DeclKind::Import => CompletionItemKind::REFERENCE,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::word_start;
+
+    #[test]
+    fn word_start_after_multibyte_characters() {
+        let text = "// café x";
+        assert_eq!(word_start(text, text.len()), text.len() - 1);
+        let text = "unicode\"naïve ✓\"";
+        assert_eq!(word_start(text, text.len()), text.len());
+        let text = "é_a$b";
+        assert_eq!(word_start(text, text.len()), 'é'.len_utf8());
+    }
+}
@@ -0,0 +1,135 @@
+use crate::analysis::{
+    model::DeclKind,
+    semantics::{Definition, Semantics},
+    source::SourceFile,
+    Analysis,
+};
+use std::rc::Rc;
+use tower_lsp::lsp_types::{GotoDefinitionResponse, Location, Position, Range, Url};
+
+/// Where the name under the cursor is declared, following imports into other files.
+///
+/// On the path of an import, or on the alias of a whole file, goes to the imported file.
+pub fn definition(
+    analysis: &mut Analysis,
+    file: &Rc<SourceFile>,
+    position: Position,
+) -> Option<GotoDefinitionResponse> {
+    let offset = file.offset(position);
+    let mut semantics = Semantics::new(analysis);
+
+    if let Some(index) = file
+        .model
+        .imports
+        .iter()
+        .position(|import| import.path_span.contains(offset))
+    {
+        let imported = semantics.import(file, index)?;
+        return Some(GotoDefinitionResponse::Scalar(file_start(&imported)?));
+    }
+
+    let (_, definitions) = semantics.definitions_at(file, offset)?;
+    let mut locations = Vec::new();
+    for definition in definitions {
+        let Definition::Declaration(symbol) = definition else {
+            continue;
+        };
+        let decl = symbol.decl();
+        let location = match (decl.kind, &decl.imported) {
+            (DeclKind::Module, Some(imported)) => semantics
+                .import(&symbol.file, imported.import)
+                .and_then(|file| file_start(&file)),
+            _ => symbol.file.location(decl.name_span),
+        };
+        locations.extend(location);
+    }
+
+    match locations.len() {
+        0 => None,
+        1 => locations.pop().map(GotoDefinitionResponse::Scalar),
+        _ => Some(GotoDefinitionResponse::Array(locations)),
+    }
+}
+
+fn file_start(file: &SourceFile) -> Option<Location> {
+    let uri = Url::from_file_path(&file.path).ok()?;
+    Some(Location::new(uri, Range::default()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::definition;
+    use crate::features::fixture::Fixture;
+    use tower_lsp::lsp_types::{GotoDefinitionResponse, Location, Position, Range};
+
+    const A: &str = "contract A {
+    function f() public virtual {}
+}
+";
+
+    /// The `(path, start)` of the definitions of the name at the first `marker` of `/src/B.sol`.
+    fn definitions(b: &str, marker: &str) -> Vec<(String, Position)> {
+        let mut fixture = Fixture::new(&[("/src/A.sol", A), ("/src/B.sol", b)]);
+        let (file, position) = fixture.at("/src/B.sol", marker);
+        let locations = match definition(&mut fixture.analysis, &file, position) {
+            Some(GotoDefinitionResponse::Scalar(location)) => vec![location],
+            Some(GotoDefinitionResponse::Array(locations)) => locations,
+            Some(response) => panic!("unexpected {response:?}"),
+            None => Vec::new(),
+        };
+        locations
+            .into_iter()
+            .map(|Location { uri, range }: Location| (uri.path().to_string(), range.start))
+            .collect()
+    }
+
+    fn at(line: u32, character: u32) -> Vec<(String, Position)> {
+        vec![("/src/A.sol".to_string(), Position::new(line, character))]
+    }
+
+    #[test]
+    fn aliases() {
+        let b = "import {A as Base} from \"./A.sol\";
+import \"./A.sol\" as Lib;
+contract B is Base {
+    Lib.A a;
+}
+";
+        assert_eq!(definitions(b, "Base}"), at(0, 9));
+        assert_eq!(definitions(b, "Base {"), at(0, 9));
+        assert_eq!(definitions(b, "A a;"), at(0, 9));
+        assert_eq!(definitions(b, "Lib.A"), at(0, 0));
+        assert_eq!(definitions(b, "./A.sol\" as"), at(0, 0));
+    }
+
+    #[test]
+    fn super_calls_go_to_the_next_base() {
+        let b = "import {A} from \"./A.sol\";
+contract B is A {
+    function f() public virtual override {
+        super.f();
+    }
+}
+contract C is A, B {
+    function f() public override(A, B) {
+        super.f();
+    }
+}
+";
+        assert_eq!(definitions(b, "f();"), at(1, 13));
+        let mut fixture = Fixture::new(&[("/src/A.sol", A), ("/src/B.sol", b)]);
+        let file = fixture.file("/src/B.sol");
+        let offset = file.text.rfind("f();").unwrap();
+        let position = file.position(offset);
+        let Some(GotoDefinitionResponse::Scalar(location)) =
+            definition(&mut fixture.analysis, &file, position)
+        else {
+            panic!("expected a single definition");
+        };
+        assert_eq!(location.uri.path(), "/src/B.sol");
+        assert_eq!(
+            location.range,
+            Range::new(Position::new(2, 13), Position::new(2, 14))
+        );
+    }
+}
@@ -0,0 +1,41 @@
+//! Files for the tests of the language features, open in an analysis rather than read from disk.
+
+use crate::{
+    analysis::{source::SourceFile, Analysis},
+    document::DocumentStore,
+};
+use std::{path::Path, rc::Rc, sync::Arc};
+use tower_lsp::lsp_types::{Position, Url};
+
+pub struct Fixture {
+    pub analysis: Analysis,
+}
+
+impl Fixture {
+    /// Opens every `(path, text)` of `files`, with absolute paths like `/src/A.sol`.
+    pub fn new(files: &[(&str, &str)]) -> Self {
+        let documents = Arc::new(DocumentStore::default());
+        for (path, text) in files {
+            documents.open(uri(path), 1, text.to_string());
+        }
+        Self {
+            analysis: Analysis::new(documents),
+        }
+    }
+
+    pub fn file(&mut self, path: &str) -> Rc<SourceFile> {
+        self.analysis.file(Path::new(path)).unwrap()
+    }
+
+    /// The file at `path` and the position of the first `marker` in it.
+    pub fn at(&mut self, path: &str, marker: &str) -> (Rc<SourceFile>, Position) {
+        let file = self.file(path);
+        let offset = file.text.find(marker).unwrap();
+        let position = file.position(offset);
+        (file, position)
+    }
+}
+
+pub fn uri(path: &str) -> Url {
+    Url::from_file_path(path).unwrap()
+}
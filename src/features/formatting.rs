@@ -0,0 +1,101 @@
+use crate::{
+    analysis::{source::ParsedSource, syntax::Span, Analysis},
+    format,
+};
+use tower_lsp::lsp_types::{Range, TextEdit};
+
+/// Past this many lines changed, the edit replaces them all rather than diffing them.
+const MAX_DIFF: usize = 2000;
+
+/// The edits formatting the file, following the `fmt` options of its `foundry.toml` rather than
+/// the editor's so that the result is the same as `forge fmt`. Only the edits touching `range`
+/// if given. `None` when the file has syntax errors.
+pub fn formatting(
+    analysis: &mut Analysis,
+    source: &ParsedSource,
+    range: Option<Range>,
+) -> Option<Vec<TextEdit>> {
+    let options = analysis
+        .project(&source.path)
+        .map(|project| project.fmt.clone())
+        .unwrap_or_default();
+    let formatted = format::format(source, &options)?;
+    let edits = diff(&source.text, &formatted)
+        .into_iter()
+        .map(|(lines, text)| (source.span_range(lines), text))
+        .filter(|(edit, _)| {
+            range.is_none_or(|range| edit.start <= range.end && range.start <= edit.end)
+        })
+        .map(|(range, new_text)| TextEdit { range, new_text })
+        .collect();
+    Some(edits)
+}
+
+/// The lines of `old` to replace to get `new`, as byte spans of `old` and their replacements.
+fn diff(old: &str, new: &str) -> Vec<(Span, String)> {
+    let old_lines: Vec<_> = old.split_inclusive('\n').collect();
+    let new_lines: Vec<_> = new.split_inclusive('\n').collect();
+    let prefix = old_lines
+        .iter()
+        .zip(&new_lines)
+        .take_while(|(old, new)| old == new)
+        .count();
+    let suffix = old_lines[prefix..]
+        .iter()
+        .rev()
+        .zip(new_lines[prefix..].iter().rev())
+        .take_while(|(old, new)| old == new)
+        .count();
+    let old_middle = &old_lines[prefix..old_lines.len() - suffix];
+    let new_middle = &new_lines[prefix..new_lines.len() - suffix];
+    let start: usize = old_lines[..prefix].iter().map(|line| line.len()).sum();
+
+    // Offsets of the lines of `old`, and one past the last.
+    let mut offsets = vec![start];
+    for line in old_middle {
+        offsets.push(offsets.last().unwrap_or(&start) + line.len());
+    }
+    let hunk = |old: (usize, usize), new: (usize, usize)| {
+        (
+            Span::new(offsets[old.0], offsets[old.1]),
+            new_middle[new.0..new.1].concat(),
+        )
+    };
+
+    if old_middle.len() * new_middle.len() > MAX_DIFF * MAX_DIFF {
+        return vec![hunk((0, old_middle.len()), (0, new_middle.len()))];
+    }
+
+    // Longest common subsequence of the lines, from the end.
+    let (n, m) = (old_middle.len(), new_middle.len());
+    let mut lengths = vec![vec![0u32; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lengths[i][j] = match old_middle[i] == new_middle[j] {
+                true => lengths[i + 1][j + 1] + 1,
+                false => lengths[i + 1][j].max(lengths[i][j + 1]),
+            };
+        }
+    }
+    let mut hunks = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    let (mut hunk_i, mut hunk_j) = (0, 0);
+    while i < n || j < m {
+        if i < n && j < m && old_middle[i] == new_middle[j] {
+            if (hunk_i, hunk_j) != (i, j) {
+                hunks.push(hunk((hunk_i, i), (hunk_j, j)));
+            }
+            i += 1;
+            j += 1;
+            (hunk_i, hunk_j) = (i, j);
+        } else if j < m && (i == n || lengths[i][j + 1] >= lengths[i + 1][j]) {
+            j += 1;
+        } else {
+            i += 1;
+        }
+    }
+    if (hunk_i, hunk_j) != (n, m) {
+        hunks.push(hunk((hunk_i, n), (hunk_j, m)));
+    }
+    hunks
+}
@@ -0,0 +1,116 @@
+use crate::analysis::{
+    semantics::{Definition, Semantics},
+    source::SourceFile,
+    Analysis,
+};
+use std::rc::Rc;
+use tower_lsp::lsp_types::{Hover, HoverContents, MarkupContent, MarkupKind, Position};
+
+/// The signature and documentation of what the name under the cursor refers to.
+pub fn hover(analysis: &mut Analysis, file: &Rc<SourceFile>, position: Position) -> Option<Hover> {
+    let mut semantics = Semantics::new(analysis);
+    let (span, definitions) = semantics.definitions_at(file, file.offset(position))?;
+    if definitions.is_empty() {
+        return None;
+    }
+    let sections: Vec<_> = definitions
+        .iter()
+        .map(|definition| render(&mut semantics, definition))
+        .collect();
+    Some(Hover {
+        contents: HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: sections.join("\n\n---\n\n"),
+        }),
+        range: Some(file.span_range(span)),
+    })
+}
+
+fn render(semantics: &mut Semantics, definition: &Definition) -> String {
+    let (signature, documentation) = match definition {
+        Definition::Declaration(symbol) => (
+            symbol.decl().signature.clone(),
+            semantics
+                .natspec(symbol)
+                .map(|natspec| natspec.to_markdown()),
+        ),
+        Definition::Builtin(builtin) => {
+            (builtin.signature.to_string(), Some(builtin.doc.to_string()))
+        }
+    };
+    let mut value = format!("```solidity\n{signature}\n```");
+    if let Some(documentation) = documentation {
+        value.push_str("\n\n");
+        value.push_str(&documentation);
+    }
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::hover;
+    use crate::features::fixture::Fixture;
+    use tower_lsp::lsp_types::HoverContents;
+
+    /// The markdown of the hover at the first `marker` of `text`.
+    fn hover_at(text: &str, marker: &str) -> Option<String> {
+        let mut fixture = Fixture::new(&[("/src/C.sol", text)]);
+        let (file, position) = fixture.at("/src/C.sol", marker);
+        let hover = hover(&mut fixture.analysis, &file, position)?;
+        match hover.contents {
+            HoverContents::Markup(markup) => Some(markup.value),
+            contents => panic!("expected markdown, got {contents:?}"),
+        }
+    }
+
+    const OVERLOADS: &str = "contract C {
+    /// @notice Sets one.
+    function f(uint256 a) public {}
+    /// @notice Sets both.
+    function f(uint256 a, uint256 b) public {}
+    function g() public {
+        f(1, 2);
+        f(1);
+        this.f;
+    }
+}
+";
+
+    #[test]
+    fn calls_pick_the_overload_of_their_arguments() {
+        let two = hover_at(OVERLOADS, "f(1, 2)").unwrap();
+        assert_eq!(
+            two,
+            "```solidity\nfunction f(uint256 a, uint256 b) public\n```\n\nSets both."
+        );
+        let one = hover_at(OVERLOADS, "f(1)").unwrap();
+        assert_eq!(
+            one,
+            "```solidity\nfunction f(uint256 a) public\n```\n\nSets one."
+        );
+    }
+
+    #[test]
+    fn every_overload_without_a_call() {
+        let any = hover_at(OVERLOADS, "f;").unwrap();
+        let sections: Vec<_> = any.split("\n\n---\n\n").collect();
+        assert_eq!(sections.len(), 2);
+        assert!(sections[0].ends_with("Sets one."), "{any}");
+        assert!(sections[1].ends_with("Sets both."), "{any}");
+    }
+
+    #[test]
+    fn builtins() {
+        let text = "contract C {
+    function g() public view returns (address) {
+        return msg.sender;
+    }
+}
+";
+        assert_eq!(
+            hover_at(text, "sender").unwrap(),
+            "```solidity\naddress msg.sender\n```\n\nSender of the message (current call)."
+        );
+        assert_eq!(hover_at(text, "    function"), None);
+    }
+}
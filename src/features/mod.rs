@@ -0,0 +1,14 @@
+pub mod code_actions;
+pub mod completion;
+pub mod definition;
+#[cfg(test)]
+pub mod fixture;
+pub mod formatting;
+pub mod hover;
+pub mod on_type_formatting;
+pub mod ranges;
+pub mod references;
+pub mod rename;
+pub mod semantic_tokens;
+pub mod signature_help;
+pub mod symbols;
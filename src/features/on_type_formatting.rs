@@ -0,0 +1,370 @@
+use crate::analysis::{model::DeclKind, source::SourceFile, syntax::Span, Analysis};
+use tower_lsp::lsp_types::{FormattingOptions, Position, TextEdit};
+
+/// The characters the edits are triggered by, the first one being `}`.
+pub const TRIGGER_CHARACTERS: [&str; 3] = ["}", "\n", "*"];
+
+/// What the text is in at some point: code, a comment or a string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Context {
+    Code,
+    LineComment,
+    /// Starting at the offset of its `/*`.
+    BlockComment(usize),
+    String(char),
+}
+
+/// The start of a line, and what's still open there.
+#[derive(Debug)]
+struct Line {
+    start: usize,
+    context: Context,
+    /// Brackets open at the start of the line.
+    depth: usize,
+}
+
+/// The edits after `ch` was typed at `position`: re-indenting the block closed by `}`, continuing
+/// a NatSpec comment on a new line, or documenting the parameters and return values of the
+/// function below a `/**`.
+pub fn on_type_formatting(
+    analysis: &mut Analysis,
+    file: &SourceFile,
+    position: Position,
+    ch: &str,
+    options: &FormattingOptions,
+) -> Vec<TextEdit> {
+    let offset = file.offset(position);
+    let edits = match ch {
+        "}" => reindent(file, offset, options),
+        "\n" => continue_comment(file, offset),
+        "*" => document(analysis, file, offset),
+        _ => None,
+    };
+    edits.unwrap_or_default()
+}
+
+/// Indents the lines of the block closed at `offset` one level more than the line of its `{`.
+fn reindent(
+    file: &SourceFile,
+    offset: usize,
+    options: &FormattingOptions,
+) -> Option<Vec<TextEdit>> {
+    let text = &file.text;
+    let close = offset
+        .checked_sub(1)
+        .filter(|&close| text[close..].starts_with('}'))?;
+    let (lines, brackets) = scan(text, close);
+    let &(open, '{') = brackets.last()? else {
+        return None;
+    };
+    let depth = brackets.len();
+    let unit = match options.insert_spaces {
+        true => " ".repeat(options.tab_size as usize),
+        false => "\t".to_string(),
+    };
+    let open_line = lines.iter().rev().find(|line| line.start <= open)?;
+    let base = indentation(&text[open_line.start..]);
+
+    let mut edits = Vec::new();
+    for line in lines.iter().filter(|line| line.start > open) {
+        let rest = &text[line.start..];
+        let content = rest.split('\n').next().unwrap_or_default().trim();
+        if line.context != Context::Code || content.is_empty() {
+            continue;
+        }
+        let closes = content.starts_with([')', ']', '}']);
+        let level = (line.depth + 1).saturating_sub(depth + usize::from(closes));
+        let indent = format!("{base}{}", unit.repeat(level));
+        let current = indentation(rest);
+        if current != indent {
+            edits.push(TextEdit {
+                range: file.span_range(Span::new(line.start, line.start + current.len())),
+                new_text: indent,
+            });
+        }
+    }
+    Some(edits)
+}
+
+/// Starts the line at `offset` with `///` or ` * ` when the previous one is in a NatSpec
+/// comment.
+fn continue_comment(file: &SourceFile, offset: usize) -> Option<Vec<TextEdit>> {
+    let text = &file.text;
+    let (lines, _) = scan(text, offset);
+    let [.., previous, line] = lines.as_slice() else {
+        return None;
+    };
+    let previous_text = &text[previous.start..line.start];
+    let indent = indentation(previous_text);
+    let previous_text = previous_text.trim();
+    let prefix = match line.context {
+        Context::BlockComment(start) if text[start..].starts_with("/**") => {
+            match previous_text.starts_with("/*") {
+                true => format!("{indent} * "),
+                false => format!("{indent}* "),
+            }
+        }
+        Context::Code if previous_text.starts_with("///") => format!("{indent}/// "),
+        _ => return None,
+    };
+
+    // What the editor indented the new line with is replaced.
+    let end = offset + indentation(&text[offset..]).len();
+    let mut new_text = prefix;
+    // Enter between `/**` and `*/` leaves `*/` on a line of its own.
+    if matches!(line.context, Context::BlockComment(_)) && text[end..].starts_with("*/") {
+        new_text = format!("{new_text}\n{indent} ");
+    }
+    Some(vec![TextEdit {
+        range: file.span_range(Span::new(line.start, end)),
+        new_text,
+    }])
+}
+
+/// Completes a `/**` ending at `offset` into a NatSpec comment with a `@param` for each
+/// parameter and a `@return` for each return value of the function below.
+fn document(analysis: &mut Analysis, file: &SourceFile, offset: usize) -> Option<Vec<TextEdit>> {
+    let text = &file.text;
+    let line_start = text[..offset].rfind('\n').map_or(0, |index| index + 1);
+    if text[line_start..offset].trim() != "/**" {
+        return None;
+    }
+    let line_end = text[offset..]
+        .find('\n')
+        .map_or(text.len(), |index| offset + index);
+    let rest = text[offset..line_end].trim();
+    let closed = rest == "*/";
+    if !closed && !rest.is_empty() {
+        return None;
+    }
+
+    // Until it's closed, the comment runs to the next `*/` and hides what follows.
+    let end = match closed {
+        true => offset + text[offset..].find("*/")? + 2,
+        false => offset,
+    };
+    let completed;
+    let source = match closed {
+        true => file,
+        false => {
+            let text = format!("{}*/{}", &text[..offset], &text[offset..]);
+            completed = analysis.parse_text(file, text).file;
+            &completed
+        }
+    };
+    let shift = usize::from(!closed) * 2;
+    let model = &source.model;
+    let decl = model
+        .declarations
+        .iter()
+        .filter(|decl| decl.span.start >= end + shift)
+        .min_by_key(|decl| decl.span.start)?;
+    let documented = matches!(
+        decl.kind,
+        DeclKind::Function
+            | DeclKind::Modifier
+            | DeclKind::Event
+            | DeclKind::Error
+            | DeclKind::Constructor
+    );
+    let between = &source.text[end + shift..decl.span.start];
+    if !documented || !between.trim().is_empty() {
+        return None;
+    }
+
+    let indent = indentation(&text[line_start..]);
+    let mut tags = vec!["@notice".to_string()];
+    for &id in &decl.parameters {
+        let name = &model.declarations[id].name;
+        if !name.is_empty() {
+            tags.push(format!("@param {name}"));
+        }
+    }
+    for &id in &decl.returns {
+        let name = &model.declarations[id].name;
+        tags.push(format!("@return {name}").trim_end().to_string());
+    }
+    let mut new_text: String = tags
+        .iter()
+        .map(|tag| format!("\n{indent} * {tag}"))
+        .collect();
+    new_text.push_str(&format!("\n{indent} */"));
+    Some(vec![TextEdit {
+        range: file.span_range(Span::new(offset, end)),
+        new_text,
+    }])
+}
+
+/// The lines of `text` up to `end`, and the brackets still open there with their offsets.
+fn scan(text: &str, end: usize) -> (Vec<Line>, Vec<(usize, char)>) {
+    let mut lines = vec![Line {
+        start: 0,
+        context: Context::Code,
+        depth: 0,
+    }];
+    let mut brackets = Vec::new();
+    let mut context = Context::Code;
+    let mut chars = text[..end].char_indices();
+    while let Some((index, c)) = chars.next() {
+        let before = context;
+        context = step(text, index, c, context, &mut chars);
+        if before == Context::Code && context == Context::Code {
+            match c {
+                '(' | '[' | '{' => brackets.push((index, c)),
+                ')' | ']' | '}' => {
+                    brackets.pop();
+                }
+                _ => {}
+            }
+        }
+        if c == '\n' {
+            // Only block comments span several lines.
+            if !matches!(context, Context::BlockComment(_)) {
+                context = Context::Code;
+            }
+            lines.push(Line {
+                start: index + 1,
+                context,
+                depth: brackets.len(),
+            });
+        }
+    }
+    (lines, brackets)
+}
+
+/// The characters of `text` up to `end` that are neither in a comment nor in a string, with their
+/// offsets.
+pub fn code_chars(text: &str, end: usize) -> Vec<(usize, char)> {
+    let mut code = Vec::new();
+    let mut context = Context::Code;
+    let mut chars = text[..end].char_indices();
+    while let Some((index, c)) = chars.next() {
+        let before = context;
+        context = step(text, index, c, context, &mut chars);
+        if before == Context::Code && context == Context::Code {
+            code.push((index, c));
+        }
+        if c == '\n' && !matches!(context, Context::BlockComment(_)) {
+            context = Context::Code;
+        }
+    }
+    code
+}
+
+/// The context after the character `c` at `index`, in `context` before it. Consumes the second
+/// character of the `//`, `/*`, `*/` and escape sequences from `chars`.
+fn step(
+    text: &str,
+    index: usize,
+    c: char,
+    context: Context,
+    chars: &mut std::str::CharIndices,
+) -> Context {
+    let next = text[index + c.len_utf8()..].chars().next();
+    match context {
+        Context::Code => match c {
+            '/' if next == Some('/') => {
+                chars.next();
+                Context::LineComment
+            }
+            '/' if next == Some('*') => {
+                chars.next();
+                Context::BlockComment(index)
+            }
+            '"' | '\'' => Context::String(c),
+            _ => context,
+        },
+        Context::BlockComment(_) if c == '*' && next == Some('/') => {
+            chars.next();
+            Context::Code
+        }
+        Context::String(_) if c == '\\' && next != Some('\n') => {
+            chars.next();
+            context
+        }
+        Context::String(quote) if c == quote => Context::Code,
+        _ => context,
+    }
+}
+
+/// The whitespace a line starts with.
+fn indentation(line: &str) -> &str {
+    let end = line
+        .find(|c: char| c == '\n' || !c.is_whitespace())
+        .unwrap_or(line.len());
+    &line[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::on_type_formatting;
+    use crate::features::fixture::Fixture;
+    use tower_lsp::lsp_types::FormattingOptions;
+
+    /// `text` without its `|` once `ch` was typed there, with the edits applied.
+    fn typed(text: &str, ch: &str) -> String {
+        let cursor = text.find('|').unwrap();
+        let text = text.replace('|', "");
+        let mut fixture = Fixture::new(&[("/src/C.sol", &text)]);
+        let file = fixture.file("/src/C.sol");
+        let options = FormattingOptions {
+            tab_size: 4,
+            insert_spaces: true,
+            ..Default::default()
+        };
+        let position = file.position(cursor);
+        let mut edits = on_type_formatting(&mut fixture.analysis, &file, position, ch, &options);
+        // Applied from the end so that the offsets of the others stay valid.
+        edits.sort_by_key(|edit| std::cmp::Reverse(edit.range.start));
+        let mut result = text.clone();
+        for edit in edits {
+            let range = file.offset(edit.range.start)..file.offset(edit.range.end);
+            result.replace_range(range, &edit.new_text);
+        }
+        result
+    }
+
+    #[test]
+    fn natspec_lines_continue() {
+        assert_eq!(
+            typed("contract C {\n    /// @notice A.\n    |\n}\n", "\n"),
+            "contract C {\n    /// @notice A.\n    /// \n}\n"
+        );
+        assert_eq!(typed("/**\n|\n", "\n"), "/**\n * \n");
+        assert_eq!(
+            typed("    /**\n     * @notice A.\n    |\n", "\n"),
+            "    /**\n     * @notice A.\n     * \n"
+        );
+    }
+
+    #[test]
+    fn enter_before_the_end_of_a_comment() {
+        assert_eq!(typed("/**\n|*/\n", "\n"), "/**\n * \n */\n");
+    }
+
+    #[test]
+    fn other_comments_dont_continue() {
+        let text = "// A.\n|\n";
+        assert_eq!(typed(text, "\n"), text.replace('|', ""));
+        let text = "/* B.\n|\n*/\n";
+        assert_eq!(typed(text, "\n"), text.replace('|', ""));
+    }
+
+    #[test]
+    fn documenting_a_function() {
+        let text = "contract C {\n    /**|\n    function f(uint256 a, uint256) public returns (uint256 b) {}\n}\n";
+        assert_eq!(
+            typed(text, "*"),
+            "contract C {\n    /**\n     * @notice\n     * @param a\n     * @return b\n     */\n    function f(uint256 a, uint256) public returns (uint256 b) {}\n}\n"
+        );
+    }
+
+    #[test]
+    fn closing_a_block_reindents_it() {
+        let text = "contract C {\n  function f() public {\nuint256 a;\n  }|\n}\n";
+        assert_eq!(
+            typed(text, "}"),
+            "contract C {\n  function f() public {\n      uint256 a;\n  }\n}\n"
+        );
+    }
+}
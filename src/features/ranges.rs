@@ -0,0 +1,291 @@
+use crate::analysis::{
+    source::ParsedSource,
+    syntax::{Span, SyntaxNode},
+};
+use slang_solidity::cst::{EdgeLabel, NonterminalKind, TerminalKind};
+use tower_lsp::lsp_types::{FoldingRange, FoldingRangeKind, Position, SelectionRange};
+
+/// What can be folded: definitions, blocks, assembly, comments spanning several lines and runs of
+/// imports.
+pub fn folding_ranges(source: &ParsedSource) -> Vec<FoldingRange> {
+    use NonterminalKind as K;
+
+    let root = SyntaxNode::root(source.tree());
+    let mut ranges = Vec::new();
+    for node in root.descendants() {
+        let foldable = matches!(
+            node.nonterminal_kind(),
+            Some(
+                K::ContractDefinition
+                    | K::InterfaceDefinition
+                    | K::LibraryDefinition
+                    | K::StructDefinition
+                    | K::EnumDefinition
+                    | K::EventDefinition
+                    | K::ErrorDefinition
+                    | K::FunctionDefinition
+                    | K::ConstructorDefinition
+                    | K::ModifierDefinition
+                    | K::FallbackFunctionDefinition
+                    | K::ReceiveFunctionDefinition
+                    | K::UnnamedFunctionDefinition
+                    | K::Block
+                    | K::AssemblyStatement
+                    | K::YulBlock
+                    | K::YulFunctionDefinition
+            )
+        );
+        if !foldable {
+            continue;
+        }
+        let span = node.span();
+        // The closing brace stays visible.
+        let end = match source.text[..span.end].ends_with('}') {
+            true => span.end - 1,
+            false => span.end,
+        };
+        let end_line = source.position(end).line;
+        let closing_alone = source.text[..end]
+            .rsplit('\n')
+            .next()
+            .is_some_and(|line| line.trim().is_empty());
+        let end_line = end_line - u32::from(closing_alone && end_line > 0);
+        push(&mut ranges, source, span.start, end_line, None);
+    }
+
+    // Line comments fold with the ones on the next lines.
+    let mut comments: Option<(TerminalKind, Span)> = None;
+    for token in root.terminals() {
+        let kind = token.terminal_kind();
+        let span = Span::new(token.offset.utf8, token.full_range().end.utf8);
+        match kind {
+            Some(TerminalKind::MultiLineComment | TerminalKind::MultiLineNatSpecComment) => {
+                let end_line = source.position(span.end).line;
+                push(
+                    &mut ranges,
+                    source,
+                    span.start,
+                    end_line,
+                    Some(FoldingRangeKind::Comment),
+                );
+            }
+            Some(
+                kind @ (TerminalKind::SingleLineComment | TerminalKind::SingleLineNatSpecComment),
+            ) => {
+                let line = source.position(span.start).line;
+                comments = match comments {
+                    Some((group, first))
+                        if group == kind && source.position(first.end).line + 1 == line =>
+                    {
+                        Some((group, Span::new(first.start, span.end)))
+                    }
+                    group => {
+                        flush_comments(&mut ranges, source, group);
+                        Some((kind, span))
+                    }
+                };
+            }
+            Some(TerminalKind::Whitespace | TerminalKind::EndOfLine) => {}
+            _ => flush_comments(&mut ranges, source, comments.take()),
+        }
+    }
+    flush_comments(&mut ranges, source, comments);
+
+    let members = root.child(EdgeLabel::Members);
+    let mut imports: Option<Span> = None;
+    let members = members.iter().flat_map(|members| members.items());
+    for member in members.map(Some).chain([None]) {
+        match member.map(|member| member.variant()) {
+            Some(member) if member.is(K::ImportDirective) => {
+                let span = member.span();
+                imports = Some(imports.map_or(span, |first| Span::new(first.start, span.end)));
+            }
+            _ => {
+                if let Some(imports) = imports.take() {
+                    let end_line = source.position(imports.end).line;
+                    push(
+                        &mut ranges,
+                        source,
+                        imports.start,
+                        end_line,
+                        Some(FoldingRangeKind::Imports),
+                    );
+                }
+            }
+        }
+    }
+
+    // A function and its body usually start on the same line, only the outer one is kept.
+    ranges.sort_by_key(|range| range.start_line);
+    ranges.dedup_by_key(|range| range.start_line);
+    ranges
+}
+
+fn flush_comments(
+    ranges: &mut Vec<FoldingRange>,
+    source: &ParsedSource,
+    comments: Option<(TerminalKind, Span)>,
+) {
+    if let Some((_, span)) = comments {
+        let end_line = source.position(span.end).line;
+        push(
+            ranges,
+            source,
+            span.start,
+            end_line,
+            Some(FoldingRangeKind::Comment),
+        );
+    }
+}
+
+/// Folds from the line of `start` to `end_line`, when that's more than one line.
+fn push(
+    ranges: &mut Vec<FoldingRange>,
+    source: &ParsedSource,
+    start: usize,
+    end_line: u32,
+    kind: Option<FoldingRangeKind>,
+) {
+    let start_line = source.position(start).line;
+    if end_line > start_line {
+        ranges.push(FoldingRange {
+            start_line,
+            start_character: None,
+            end_line,
+            end_character: None,
+            kind,
+            collapsed_text: None,
+        });
+    }
+}
+
+/// For each position, the nodes of the CST around it from the innermost, each one a selection
+/// step: identifier, expression, statement, block, function, contract.
+pub fn selection_ranges(source: &ParsedSource, positions: &[Position]) -> Vec<SelectionRange> {
+    positions
+        .iter()
+        .map(|&position| selection_range(source, source.offset(position)))
+        .collect()
+}
+
+fn selection_range(source: &ParsedSource, offset: usize) -> SelectionRange {
+    let mut node = SyntaxNode::root(source.tree());
+    let mut spans = Vec::new();
+    let is_word = |c: char| c.is_alphanumeric() || c == '_' || c == '$';
+    let after_word =
+        source.text[..offset].ends_with(is_word) && !source.text[offset..].starts_with(is_word);
+    loop {
+        let span = node.span();
+        if span.start < span.end && spans.last() != Some(&span) {
+            spans.push(span);
+        }
+        let children: Vec<_> = node
+            .children()
+            .filter(|child| !child.is_trivia())
+            .map(|child| (child.span(), child))
+            .filter(|(span, _)| span.start < span.end)
+            .collect();
+        let under = children
+            .iter()
+            .find(|(span, _)| span.start <= offset && offset < span.end);
+        let before = children.iter().find(|(span, _)| span.end == offset);
+        // A cursor right after a word selects the word rather than what follows.
+        let child = match after_word {
+            true => before.or(under),
+            false => under.or(before),
+        };
+        match child {
+            Some((_, child)) => node = child.clone(),
+            None => break,
+        }
+    }
+
+    // The outermost is the first, and the parent of the next one.
+    let mut spans = spans.into_iter();
+    let root = spans.next().unwrap_or(Span::new(offset, offset));
+    let mut range = SelectionRange {
+        range: source.span_range(root),
+        parent: None,
+    };
+    for span in spans {
+        range = SelectionRange {
+            range: source.span_range(span),
+            parent: Some(Box::new(range)),
+        };
+    }
+    range
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{folding_ranges, selection_ranges};
+    use crate::analysis::source::ParsedSource;
+    use tower_lsp::lsp_types::{FoldingRangeKind, Position, SelectionRange};
+
+    const TEXT: &str = "import {A} from \"./A.sol\";
+import {B} from \"./B.sol\";
+/**
+ * @notice C.
+ */
+contract C {
+    // One.
+    // Two.
+    function f(uint256 a) public {
+        if (a > 0) {
+            a = 1;
+        }
+    }
+}
+";
+
+    #[test]
+    fn folds_keep_the_closing_line_visible() {
+        let source = ParsedSource::from_text(TEXT);
+        let folds: Vec<_> = folding_ranges(&source)
+            .into_iter()
+            .map(|range| (range.start_line, range.end_line, range.kind))
+            .collect();
+        assert_eq!(
+            folds,
+            [
+                (0, 1, Some(FoldingRangeKind::Imports)),
+                (2, 4, Some(FoldingRangeKind::Comment)),
+                (5, 12, None),
+                (6, 7, Some(FoldingRangeKind::Comment)),
+                (8, 11, None),
+                (9, 10, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn selections_grow_to_the_enclosing_nodes() {
+        let source = ParsedSource::from_text(TEXT);
+        let mut selection = selection_ranges(&source, &[Position::new(10, 12)]).pop();
+        let mut ranges = Vec::new();
+        while let Some(SelectionRange { range, parent }) = selection {
+            ranges.push((
+                (range.start.line, range.start.character),
+                (range.end.line, range.end.character),
+            ));
+            selection = parent.map(|parent| *parent);
+        }
+        assert_eq!(
+            ranges,
+            [
+                // `a`, `a = 1`, `a = 1;`.
+                ((10, 12), (10, 13)),
+                ((10, 12), (10, 17)),
+                ((10, 12), (10, 18)),
+                // The block of the `if`, then the whole `if`.
+                ((9, 19), (11, 9)),
+                ((9, 8), (11, 9)),
+                // The body of the function, then the whole function.
+                ((8, 33), (12, 5)),
+                ((8, 4), (12, 5)),
+                ((5, 0), (13, 1)),
+                ((0, 0), (13, 1)),
+            ]
+        );
+    }
+}
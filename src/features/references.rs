@@ -0,0 +1,270 @@
+use crate::analysis::{
+    model::{Access, DeclKind},
+    semantics::{Definition, Semantics, Symbol},
+    source::SourceFile,
+    syntax::Span,
+    Analysis,
+};
+use std::rc::Rc;
+use tower_lsp::lsp_types::{DocumentHighlight, DocumentHighlightKind, Location, Position};
+
+/// A place where a symbol is named.
+#[derive(Debug)]
+pub struct Occurrence {
+    pub file: Rc<SourceFile>,
+    pub span: Span,
+    /// `None` for the declaration itself.
+    pub access: Option<Access>,
+}
+
+/// Every reference to what the name under the cursor refers to, in the whole workspace.
+pub fn references(
+    analysis: &mut Analysis,
+    file: &Rc<SourceFile>,
+    position: Position,
+    include_declaration: bool,
+) -> Option<Vec<Location>> {
+    let targets = targets(analysis, file, file.offset(position))?;
+    let files = candidates(analysis, file, &targets);
+    let locations = occurrences(&mut Semantics::new(analysis), &targets, &files)
+        .into_iter()
+        .filter(|occurrence| include_declaration || occurrence.access.is_some())
+        .filter_map(|occurrence| occurrence.file.location(occurrence.span))
+        .collect();
+    Some(locations)
+}
+
+/// The occurrences in `file` of what the name under the cursor refers to, telling reads from
+/// writes.
+pub fn highlights(
+    analysis: &mut Analysis,
+    file: &Rc<SourceFile>,
+    position: Position,
+) -> Option<Vec<DocumentHighlight>> {
+    let targets = targets(analysis, file, file.offset(position))?;
+    let highlights = occurrences(
+        &mut Semantics::new(analysis),
+        &targets,
+        std::slice::from_ref(file),
+    )
+    .into_iter()
+    .filter(|occurrence| occurrence.file.path == file.path)
+    .map(|occurrence| DocumentHighlight {
+        range: file.span_range(occurrence.span),
+        kind: Some(match occurrence.access {
+            None => DocumentHighlightKind::TEXT,
+            Some(Access::Read) => DocumentHighlightKind::READ,
+            Some(Access::Write) => DocumentHighlightKind::WRITE,
+        }),
+    })
+    .collect();
+    Some(highlights)
+}
+
+/// The declarations named at `offset`, several for unresolved overloads.
+pub fn targets(
+    analysis: &mut Analysis,
+    file: &Rc<SourceFile>,
+    offset: usize,
+) -> Option<Vec<Symbol>> {
+    let (_, definitions) = Semantics::new(analysis).definitions_at(file, offset)?;
+    let targets: Vec<_> = definitions
+        .into_iter()
+        .filter_map(|definition| match definition {
+            Definition::Declaration(symbol) => Some(symbol),
+            Definition::Builtin(_) => None,
+        })
+        .collect();
+    (!targets.is_empty()).then_some(targets)
+}
+
+/// Files that may reference `targets`: only their own file for locals, otherwise every file of the
+/// workspace that mentions their name.
+pub fn candidates(
+    analysis: &mut Analysis,
+    file: &Rc<SourceFile>,
+    targets: &[Symbol],
+) -> Vec<Rc<SourceFile>> {
+    if targets.iter().all(|target| target.decl().kind.is_local()) {
+        return vec![Rc::clone(&targets[0].file)];
+    }
+    let mut files = analysis.files_mentioning(&targets[0].decl().name);
+    for path in targets
+        .iter()
+        .map(|target| &target.file.path)
+        .chain([&file.path])
+    {
+        if !files.iter().any(|file| &file.path == path) {
+            files.extend(analysis.file(path));
+        }
+    }
+    files
+}
+
+/// Occurrences of `targets` in `files`: their declarations, then the references that resolve to
+/// them, including through `{X as Y}` aliases.
+pub fn occurrences(
+    semantics: &mut Semantics,
+    targets: &[Symbol],
+    files: &[Rc<SourceFile>],
+) -> Vec<Occurrence> {
+    let mut occurrences = Vec::new();
+    for file in files {
+        for target in targets
+            .iter()
+            .filter(|target| target.file.path == file.path)
+        {
+            occurrences.push(Occurrence {
+                file: Rc::clone(file),
+                span: target.decl().name_span,
+                access: None,
+            });
+        }
+
+        // Names that may refer to a target in this file, aliases included.
+        let mut names: Vec<&str> = targets
+            .iter()
+            .map(|target| target.decl().name.as_str())
+            .collect();
+        for decl in &file.model.declarations {
+            let imported = decl
+                .imported
+                .as_ref()
+                .and_then(|imported| imported.name.as_ref());
+            if imported.is_some_and(|imported| names.contains(&imported.as_str())) {
+                names.push(&decl.name);
+            }
+        }
+
+        let resolves_to_target = |definitions: Vec<Definition>| {
+            definitions.iter().any(|definition| {
+                matches!(definition, Definition::Declaration(symbol) if targets.contains(symbol))
+            })
+        };
+        for reference in &file.model.references {
+            if names.contains(&reference.name.as_str())
+                && resolves_to_target(semantics.resolve(file, reference))
+            {
+                occurrences.push(Occurrence {
+                    file: Rc::clone(file),
+                    span: reference.span,
+                    access: Some(reference.access),
+                });
+            }
+        }
+        // `import {X} from "..."` names `X` without an alias.
+        for decl in &file.model.declarations {
+            if decl.kind == DeclKind::Import
+                && names.contains(&decl.name.as_str())
+                && decl.imported.as_ref().and_then(|i| i.name.as_ref()) == Some(&decl.name)
+                && semantics
+                    .definitions_at(file, decl.name_span.start)
+                    .is_some_and(|(_, definitions)| resolves_to_target(definitions))
+            {
+                occurrences.push(Occurrence {
+                    file: Rc::clone(file),
+                    span: decl.name_span,
+                    access: Some(Access::Read),
+                });
+            }
+        }
+    }
+    occurrences
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{highlights, references};
+    use crate::features::fixture::Fixture;
+    use tower_lsp::lsp_types::{DocumentHighlightKind, Position};
+
+    const A: &str = "contract A {
+    uint256 public x;
+    function f(uint256 y) public {
+        x = y;
+        y += x;
+    }
+}
+";
+    const B: &str = "import {A as Base} from \"./A.sol\";
+import {A} from \"./A.sol\";
+contract B is Base {
+    function g(A a) public view returns (uint256) {
+        return a.x();
+    }
+}
+";
+
+    /// The `(path, line, character)` of the references to the name at the first `marker` of
+    /// `path`.
+    fn referenced(path: &str, marker: &str, declaration: bool) -> Vec<(String, u32, u32)> {
+        let mut fixture = Fixture::new(&[("/src/A.sol", A), ("/src/B.sol", B)]);
+        let (file, position) = fixture.at(path, marker);
+        let mut locations: Vec<_> = references(&mut fixture.analysis, &file, position, declaration)
+            .unwrap()
+            .into_iter()
+            .map(|location| {
+                let start = location.range.start;
+                (location.uri.path().to_string(), start.line, start.character)
+            })
+            .collect();
+        locations.sort();
+        locations
+    }
+
+    fn at(path: &str, line: u32, character: u32) -> (String, u32, u32) {
+        (path.to_string(), line, character)
+    }
+
+    #[test]
+    fn across_files_and_aliases() {
+        let a = "/src/A.sol";
+        let b = "/src/B.sol";
+        assert_eq!(
+            referenced(a, "A {", true),
+            [
+                at(a, 0, 9),
+                at(b, 0, 8),
+                at(b, 1, 8),
+                at(b, 2, 14),
+                at(b, 3, 15)
+            ]
+        );
+        assert_eq!(
+            referenced(b, "Base {", false),
+            [at(b, 0, 8), at(b, 1, 8), at(b, 2, 14), at(b, 3, 15)]
+        );
+        assert_eq!(
+            referenced(a, "x;", false),
+            [at(a, 3, 8), at(a, 4, 13), at(b, 4, 17)]
+        );
+    }
+
+    #[test]
+    fn locals_stay_in_their_file() {
+        let a = "/src/A.sol";
+        assert_eq!(
+            referenced(a, "y)", true),
+            [at(a, 2, 23), at(a, 3, 12), at(a, 4, 8)]
+        );
+    }
+
+    #[test]
+    fn highlights_tell_reads_from_writes() {
+        let mut fixture = Fixture::new(&[("/src/A.sol", A)]);
+        let (file, position) = fixture.at("/src/A.sol", "x;");
+        let highlights: Vec<_> = highlights(&mut fixture.analysis, &file, position)
+            .unwrap()
+            .into_iter()
+            .map(|highlight| (highlight.range.start, highlight.kind.unwrap()))
+            .collect();
+        assert_eq!(
+            highlights,
+            [
+                (Position::new(1, 19), DocumentHighlightKind::TEXT),
+                (Position::new(3, 8), DocumentHighlightKind::WRITE),
+                (Position::new(4, 13), DocumentHighlightKind::READ),
+            ]
+        );
+    }
+}
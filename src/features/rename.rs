@@ -0,0 +1,386 @@
+use super::references::{self, Occurrence};
+use crate::analysis::{
+    model::{DeclKind, ReferenceKind},
+    semantics::{Definition, Semantics, Symbol},
+    source::SourceFile,
+    Analysis,
+};
+use semver::Version;
+use slang_solidity::{cst::NonterminalKind, parser::Parser};
+use std::{collections::HashMap, rc::Rc};
+use tower_lsp::lsp_types::{Position, PrepareRenameResponse, TextEdit, Url, WorkspaceEdit};
+
+/// The range of the name under the cursor, when it can be renamed.
+pub fn prepare_rename(
+    analysis: &mut Analysis,
+    file: &Rc<SourceFile>,
+    position: Position,
+) -> Result<Option<PrepareRenameResponse>, String> {
+    let offset = file.offset(position);
+    let Some((span, definitions)) = Semantics::new(analysis).definitions_at(file, offset) else {
+        return Ok(None);
+    };
+    let targets = renamable(&file.text[span.start..span.end], definitions)?;
+    Ok(Some(PrepareRenameResponse::RangeWithPlaceholder {
+        range: file.span_range(span),
+        placeholder: targets[0].decl().name.clone(),
+    }))
+}
+
+/// Renames the symbol under the cursor in every file of the workspace, along with the functions it
+/// overrides or that override it.
+///
+/// Refuses when `new_name` would collide with another declaration of the same scope or
+/// inheritance chain, or when a reference would end up resolving to something else.
+pub fn rename(
+    analysis: &mut Analysis,
+    file: &Rc<SourceFile>,
+    position: Position,
+    new_name: &str,
+) -> Result<Option<WorkspaceEdit>, String> {
+    let offset = file.offset(position);
+    let Some((span, definitions)) = Semantics::new(analysis).definitions_at(file, offset) else {
+        return Ok(None);
+    };
+    let mut targets = renamable(&file.text[span.start..span.end], definitions)?;
+    let old_name = targets[0].decl().name.clone();
+    if !is_identifier(new_name, &file.solidity_version) {
+        return Err(format!("`{new_name}` is not a valid identifier"));
+    }
+    if new_name == old_name {
+        return Ok(None);
+    }
+
+    let files = references::candidates(analysis, file, &targets);
+    let mut semantics = Semantics::new(analysis);
+    add_overrides(&mut semantics, &mut targets, &files);
+    let occurrences = references::occurrences(&mut semantics, &targets, &files);
+    drop(semantics);
+
+    let contracts = analysis.files_mentioning(new_name);
+    let mut semantics = Semantics::new(analysis);
+    check_declarations(&mut semantics, &targets, new_name, &contracts)?;
+    check_references(&mut semantics, &targets, new_name, &occurrences)?;
+
+    let mut changes: HashMap<Url, Vec<TextEdit>> = HashMap::new();
+    for occurrence in occurrences {
+        let Occurrence { file, span, .. } = occurrence;
+        // References through an alias keep the alias.
+        if file.text[span.start..span.end] != old_name {
+            continue;
+        }
+        let Some(location) = file.location(span) else {
+            continue;
+        };
+        let edits = changes.entry(location.uri).or_default();
+        if !edits.iter().any(|edit| edit.range == location.range) {
+            edits.push(TextEdit::new(location.range, new_name.to_string()));
+        }
+    }
+    Ok(Some(WorkspaceEdit::new(changes)))
+}
+
+/// The declarations to rename, when the name at hand is really theirs rather than an alias.
+fn renamable(text: &str, definitions: Vec<Definition>) -> Result<Vec<Symbol>, String> {
+    let mut targets = Vec::new();
+    for definition in definitions {
+        match definition {
+            Definition::Declaration(symbol) => targets.push(symbol),
+            Definition::Builtin(builtin) => {
+                return Err(format!("`{}` is built into the language", builtin.name))
+            }
+        }
+    }
+    match targets.first() {
+        None => Err("nothing to rename here".to_string()),
+        Some(target) if target.decl().name != text => Err(format!(
+            "`{text}` is an alias of `{}`, rename the import instead",
+            target.decl().name
+        )),
+        Some(_) => Ok(targets),
+    }
+}
+
+/// Whether `name` is an identifier rather than a keyword of the language `version`.
+fn is_identifier(name: &str, version: &Version) -> bool {
+    let Ok(parser) = Parser::create(version.clone()) else {
+        return false;
+    };
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+        && parser
+            .parse(NonterminalKind::IdentifierPath, name)
+            .is_valid()
+}
+
+/// Adds the functions and modifiers that the targets override, or that override them.
+fn add_overrides(semantics: &mut Semantics, targets: &mut Vec<Symbol>, files: &[Rc<SourceFile>]) {
+    let overridable = |symbol: &Symbol| {
+        matches!(symbol.decl().kind, DeclKind::Function | DeclKind::Modifier)
+            && symbol.container().is_some()
+    };
+    let mut index = 0;
+    while index < targets.len() {
+        let target = targets[index].clone();
+        index += 1;
+        if !overridable(&target) {
+            continue;
+        }
+        if let Some(base) = semantics.overridden(&target, None) {
+            if !targets.contains(&base) {
+                targets.push(base);
+            }
+        }
+        for file in files {
+            for id in 0..file.model.declarations.len() {
+                let candidate = Symbol::new(file, id);
+                if targets.contains(&candidate)
+                    || !overridable(&candidate)
+                    || candidate.decl().name != target.decl().name
+                {
+                    continue;
+                }
+                if semantics.overridden(&candidate, None).as_ref() == Some(&target) {
+                    targets.push(candidate);
+                }
+            }
+        }
+    }
+}
+
+/// Looks for declarations named `new_name` that the renamed ones would collide with or shadow:
+/// in their scope and the enclosing ones, in the bases of their contract and in the contracts
+/// deriving from it.
+fn check_declarations(
+    semantics: &mut Semantics,
+    targets: &[Symbol],
+    new_name: &str,
+    contracts: &[Rc<SourceFile>],
+) -> Result<(), String> {
+    for target in targets {
+        let decl = target.decl();
+        let mut others: Vec<Symbol> = match decl.kind {
+            DeclKind::StructMember | DeclKind::EnumMember => target
+                .container()
+                .map(|container| container.members())
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|member| member.decl().name == new_name)
+                .collect(),
+            _ => declarations(semantics.lookup(
+                &target.file,
+                decl.scope,
+                new_name,
+                decl.name_span.end,
+            )),
+        };
+
+        let container = target
+            .container()
+            .filter(|container| container.decl().kind.is_contract());
+        if let Some(container) = container.filter(|_| !decl.kind.is_local()) {
+            for file in contracts {
+                for id in 0..file.model.declarations.len() {
+                    let contract = Symbol::new(file, id);
+                    if !contract.decl().kind.is_contract()
+                        || !semantics.linearization(&contract).contains(&container)
+                    {
+                        continue;
+                    }
+                    others.extend(
+                        contract
+                            .members()
+                            .into_iter()
+                            .filter(|member| member.decl().name == new_name),
+                    );
+                }
+            }
+        }
+
+        if let Some(other) = others
+            .iter()
+            .find(|other| !targets.contains(other) && !is_overload(target, other))
+        {
+            return Err(conflict(new_name, other));
+        }
+    }
+    Ok(())
+}
+
+/// Looks for references that `new_name` already resolves to something else at, which the rename
+/// would either capture or leave pointing to the other declaration.
+fn check_references(
+    semantics: &mut Semantics,
+    targets: &[Symbol],
+    new_name: &str,
+    occurrences: &[Occurrence],
+) -> Result<(), String> {
+    for occurrence in occurrences {
+        let reference = occurrence.file.model.references.iter().find(|reference| {
+            reference.span == occurrence.span && matches!(reference.kind, ReferenceKind::Name)
+        });
+        let Some(reference) = reference else {
+            continue;
+        };
+        let others = declarations(semantics.lookup(
+            &occurrence.file,
+            reference.scope,
+            new_name,
+            reference.span.start,
+        ));
+        if let Some(other) = others.iter().find(|other| {
+            !targets.contains(other) && !targets.iter().any(|target| is_overload(target, other))
+        }) {
+            return Err(conflict(new_name, other));
+        }
+    }
+    Ok(())
+}
+
+fn declarations(definitions: Vec<Definition>) -> Vec<Symbol> {
+    definitions
+        .into_iter()
+        .filter_map(|definition| match definition {
+            Definition::Declaration(symbol) => Some(symbol),
+            Definition::Builtin(_) => None,
+        })
+        .collect()
+}
+
+/// Whether both are functions that can coexist with the same name.
+fn is_overload(target: &Symbol, other: &Symbol) -> bool {
+    target.decl().kind == other.decl().kind
+        && target.decl().kind.is_overloadable()
+        && target.parameter_types() != other.parameter_types()
+}
+
+fn conflict(new_name: &str, other: &Symbol) -> String {
+    let position = other.file.position(other.decl().name_span.start);
+    let file = other
+        .file
+        .path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    format!(
+        "`{new_name}` is already declared at {file}:{}:{}",
+        position.line + 1,
+        position.character + 1
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{prepare_rename, rename};
+    use crate::features::fixture::Fixture;
+
+    const A: &str = "contract A {
+    uint256 public total;
+    function f() public virtual {}
+    function g(uint256 a) public {}
+    function g(uint256 a, uint256 b) public {
+        uint256 c = a + b + total;
+    }
+}
+";
+    const B: &str = "import {A as Base} from \"./A.sol\";
+contract B is Base {
+    uint256 public count;
+    function f() public override {
+        count = total;
+    }
+}
+";
+
+    fn renamed(
+        path: &str,
+        marker: &str,
+        new_name: &str,
+    ) -> Result<Vec<(String, u32, u32)>, String> {
+        let mut fixture = Fixture::new(&[("/src/A.sol", A), ("/src/B.sol", B)]);
+        let (file, position) = fixture.at(path, marker);
+        let edit = rename(&mut fixture.analysis, &file, position, new_name)?.unwrap();
+        let mut edits: Vec<_> = edit
+            .changes
+            .unwrap()
+            .into_iter()
+            .flat_map(|(uri, edits)| {
+                edits.into_iter().map(move |edit| {
+                    (
+                        uri.path().to_string(),
+                        edit.range.start.line,
+                        edit.range.start.character,
+                    )
+                })
+            })
+            .collect();
+        edits.sort();
+        Ok(edits)
+    }
+
+    fn at(path: &str, line: u32, character: u32) -> (String, u32, u32) {
+        (path.to_string(), line, character)
+    }
+
+    #[test]
+    fn overrides_are_renamed_together() {
+        let edits = renamed("/src/A.sol", "f()", "h").unwrap();
+        assert_eq!(edits, [at("/src/A.sol", 2, 13), at("/src/B.sol", 3, 13)]);
+        // Overloads of another name can coexist.
+        let edits = renamed("/src/A.sol", "f()", "g").unwrap();
+        assert_eq!(edits, [at("/src/A.sol", 2, 13), at("/src/B.sol", 3, 13)]);
+    }
+
+    #[test]
+    fn locals_are_renamed_in_their_function() {
+        let edits = renamed("/src/A.sol", "a) public {}", "b").unwrap();
+        assert_eq!(edits, [at("/src/A.sol", 3, 23)]);
+    }
+
+    #[test]
+    fn conflicts() {
+        let conflicts = [
+            // A member of a derived contract.
+            ("/src/A.sol", "f()", "count", "B.sol:3:20"),
+            // A member of a base.
+            ("/src/B.sol", "count;", "total", "A.sol:2:20"),
+            // A declaration in the same scope.
+            ("/src/A.sol", "c =", "b", "A.sol:5:35"),
+            // A state variable the local would shadow.
+            ("/src/A.sol", "c =", "total", "A.sol:2:20"),
+        ];
+        for (path, marker, new_name, location) in conflicts {
+            assert_eq!(
+                renamed(path, marker, new_name),
+                Err(format!("`{new_name}` is already declared at {location}"))
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_names() {
+        for new_name in ["uint", "1x", "a-b", ""] {
+            assert_eq!(
+                renamed("/src/A.sol", "total;", new_name),
+                Err(format!("`{new_name}` is not a valid identifier"))
+            );
+        }
+    }
+
+    #[test]
+    fn aliases_are_renamed_at_the_import() {
+        let mut fixture = Fixture::new(&[("/src/A.sol", A), ("/src/B.sol", B)]);
+        let (file, position) = fixture.at("/src/B.sol", "Base {");
+        assert_eq!(
+            prepare_rename(&mut fixture.analysis, &file, position),
+            Err("`Base` is an alias of `A`, rename the import instead".to_string())
+        );
+        let (file, position) = fixture.at("/src/B.sol", "total");
+        assert!(matches!(
+            prepare_rename(&mut fixture.analysis, &file, position),
+            Ok(Some(_))
+        ));
+    }
+}
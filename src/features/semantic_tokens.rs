@@ -0,0 +1,301 @@
+use crate::analysis::{
+    model::{Access, DeclKind},
+    semantics::{Definition, Semantics, Symbol},
+    source::SourceFile,
+    syntax::Span,
+    Analysis,
+};
+use std::rc::Rc;
+use tower_lsp::lsp_types::{
+    Position, Range, SemanticToken, SemanticTokenModifier, SemanticTokenType, SemanticTokensLegend,
+};
+
+/// Solidity errors have no standard token type.
+const ERROR: SemanticTokenType = SemanticTokenType::new("error");
+
+const TYPES: &[SemanticTokenType] = &[
+    SemanticTokenType::NAMESPACE,
+    SemanticTokenType::CLASS,
+    SemanticTokenType::INTERFACE,
+    SemanticTokenType::STRUCT,
+    SemanticTokenType::ENUM,
+    SemanticTokenType::ENUM_MEMBER,
+    SemanticTokenType::TYPE,
+    SemanticTokenType::EVENT,
+    ERROR,
+    SemanticTokenType::FUNCTION,
+    SemanticTokenType::METHOD,
+    SemanticTokenType::MODIFIER,
+    SemanticTokenType::PARAMETER,
+    SemanticTokenType::VARIABLE,
+    SemanticTokenType::PROPERTY,
+];
+
+/// The standard modifiers, then the keywords that tell variables and functions apart.
+const MODIFIERS: &[SemanticTokenModifier] = &[
+    SemanticTokenModifier::DECLARATION,
+    SemanticTokenModifier::READONLY,
+    SemanticTokenModifier::MODIFICATION,
+    SemanticTokenModifier::DEFAULT_LIBRARY,
+    SemanticTokenModifier::new("constant"),
+    SemanticTokenModifier::new("immutable"),
+    SemanticTokenModifier::new("transient"),
+    SemanticTokenModifier::new("storage"),
+    SemanticTokenModifier::new("memory"),
+    SemanticTokenModifier::new("calldata"),
+    SemanticTokenModifier::new("public"),
+    SemanticTokenModifier::new("external"),
+    SemanticTokenModifier::new("internal"),
+    SemanticTokenModifier::new("private"),
+];
+
+pub fn legend() -> SemanticTokensLegend {
+    SemanticTokensLegend {
+        token_types: TYPES.to_vec(),
+        token_modifiers: MODIFIERS.to_vec(),
+    }
+}
+
+/// How a name is highlighted: an index in `TYPES` and a bit per index in `MODIFIERS`.
+#[derive(Debug, Clone, Copy)]
+struct Class {
+    ty: u32,
+    modifiers: u32,
+}
+
+impl Class {
+    fn new(ty: SemanticTokenType) -> Self {
+        let ty = TYPES.iter().position(|t| *t == ty).unwrap_or_default();
+        Self {
+            ty: ty as u32,
+            modifiers: 0,
+        }
+    }
+
+    fn with(mut self, modifier: &str) -> Self {
+        if let Some(index) = MODIFIERS.iter().position(|m| m.as_str() == modifier) {
+            self.modifiers |= 1 << index;
+        }
+        self
+    }
+}
+
+/// The names of the declarations and the references of `file`, classified by what they refer
+/// to, only the ones in `range` if given.
+pub fn semantic_tokens(
+    analysis: &mut Analysis,
+    file: &Rc<SourceFile>,
+    range: Option<Range>,
+) -> Vec<SemanticToken> {
+    let span = match range {
+        Some(range) => Span::new(file.offset(range.start), file.offset(range.end)),
+        None => Span::new(0, file.text.len()),
+    };
+    let in_range = |name: Span| name.start < span.end && name.end > span.start;
+
+    let mut semantics = Semantics::new(analysis);
+    let mut tokens = Vec::new();
+    for (id, decl) in file.model.declarations.iter().enumerate() {
+        if decl.name.is_empty() || !in_range(decl.name_span) {
+            continue;
+        }
+        // Imported names look like what they import.
+        let class = match decl.kind {
+            DeclKind::Import => semantics
+                .definitions_at(file, decl.name_span.start)
+                .and_then(|(_, definitions)| definitions.first().and_then(classify)),
+            _ => classify(&Definition::Declaration(Symbol::new(file, id))),
+        };
+        if let Some(class) = class {
+            tokens.push((decl.name_span, class.with("declaration")));
+        }
+    }
+    for reference in &file.model.references {
+        if !in_range(reference.span) {
+            continue;
+        }
+        let definitions = semantics.resolve(file, reference);
+        let Some(mut class) = definitions.first().and_then(classify) else {
+            continue;
+        };
+        if reference.access == Access::Write {
+            class = class.with("modification");
+        }
+        tokens.push((reference.span, class));
+    }
+    tokens.sort_by_key(|(span, _)| span.start);
+    tokens.dedup_by_key(|(span, _)| span.start);
+
+    let mut previous = Position::default();
+    let mut data = Vec::new();
+    for (span, class) in tokens {
+        let range = file.span_range(span);
+        if range.start.line != range.end.line {
+            continue;
+        }
+        let delta_line = range.start.line - previous.line;
+        let delta_start = match delta_line {
+            0 => range.start.character - previous.character,
+            _ => range.start.character,
+        };
+        data.push(SemanticToken {
+            delta_line,
+            delta_start,
+            length: range.end.character - range.start.character,
+            token_type: class.ty,
+            token_modifiers_bitset: class.modifiers,
+        });
+        previous = range.start;
+    }
+    data
+}
+
+fn classify(definition: &Definition) -> Option<Class> {
+    let symbol = match definition {
+        Definition::Builtin(builtin) => {
+            let ty = match (builtin.function, builtin.namespace.is_empty()) {
+                (true, true) => SemanticTokenType::FUNCTION,
+                (true, false) => SemanticTokenType::METHOD,
+                (false, true) => SemanticTokenType::VARIABLE,
+                (false, false) => SemanticTokenType::PROPERTY,
+            };
+            return Some(Class::new(ty).with("defaultLibrary"));
+        }
+        Definition::Declaration(symbol) => symbol,
+    };
+
+    let decl = symbol.decl();
+    let class = match decl.kind {
+        DeclKind::Contract => Class::new(SemanticTokenType::CLASS),
+        DeclKind::Interface => Class::new(SemanticTokenType::INTERFACE),
+        DeclKind::Library | DeclKind::Module => Class::new(SemanticTokenType::NAMESPACE),
+        DeclKind::Struct => Class::new(SemanticTokenType::STRUCT),
+        DeclKind::StructMember => Class::new(SemanticTokenType::PROPERTY),
+        DeclKind::Enum => Class::new(SemanticTokenType::ENUM),
+        DeclKind::EnumMember => Class::new(SemanticTokenType::ENUM_MEMBER),
+        DeclKind::UserDefinedValueType => Class::new(SemanticTokenType::TYPE),
+        DeclKind::Event => Class::new(SemanticTokenType::EVENT),
+        DeclKind::Error => Class::new(ERROR),
+        DeclKind::Modifier => Class::new(SemanticTokenType::MODIFIER),
+        DeclKind::Function => {
+            let container = symbol.container();
+            let written = ["public", "external", "internal", "private"]
+                .into_iter()
+                .find(|visibility| decl.has_attribute(visibility));
+            let visibility = written.unwrap_or(match &container {
+                None => "internal",
+                Some(container) if container.decl().kind == DeclKind::Interface => "external",
+                Some(_) => "public",
+            });
+            let ty = match container {
+                Some(_) => SemanticTokenType::METHOD,
+                None => SemanticTokenType::FUNCTION,
+            };
+            Class::new(ty).with(visibility)
+        }
+        DeclKind::StateVariable => {
+            let class = Class::new(SemanticTokenType::PROPERTY);
+            if decl.has_attribute("constant") {
+                class.with("readonly").with("constant")
+            } else if decl.has_attribute("immutable") {
+                class.with("readonly").with("immutable")
+            } else if decl.has_attribute("transient") {
+                class.with("transient")
+            } else {
+                class.with("storage")
+            }
+        }
+        DeclKind::Constant => Class::new(SemanticTokenType::VARIABLE)
+            .with("readonly")
+            .with("constant"),
+        DeclKind::Parameter | DeclKind::LocalVariable => {
+            let ty = match decl.kind {
+                DeclKind::Parameter => SemanticTokenType::PARAMETER,
+                _ => SemanticTokenType::VARIABLE,
+            };
+            decl.attributes
+                .iter()
+                .fold(Class::new(ty), |class, location| class.with(location))
+        }
+        DeclKind::YulVariable => Class::new(SemanticTokenType::VARIABLE),
+        DeclKind::YulFunction => Class::new(SemanticTokenType::FUNCTION),
+        DeclKind::Constructor | DeclKind::Fallback | DeclKind::Receive | DeclKind::Import => {
+            return None
+        }
+    };
+    Some(class)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{semantic_tokens, MODIFIERS, TYPES};
+    use crate::features::fixture::Fixture;
+    use tower_lsp::lsp_types::{Position, Range, SemanticToken};
+
+    const TEXT: &str = "contract C {
+    uint256 public total;
+    function f(uint256 a) public {
+        total = a;
+    }
+}
+";
+
+    fn tokens(range: Option<Range>) -> Vec<SemanticToken> {
+        let mut fixture = Fixture::new(&[("/src/C.sol", TEXT)]);
+        let file = fixture.file("/src/C.sol");
+        semantic_tokens(&mut fixture.analysis, &file, range)
+    }
+
+    /// `(delta_line, delta_start, length, type, modifiers)` of each token.
+    fn decoded(
+        tokens: Vec<SemanticToken>,
+    ) -> Vec<(u32, u32, u32, &'static str, Vec<&'static str>)> {
+        tokens
+            .into_iter()
+            .map(|token| {
+                let modifiers = MODIFIERS
+                    .iter()
+                    .enumerate()
+                    .filter(|(index, _)| token.token_modifiers_bitset & (1 << index) != 0)
+                    .map(|(_, modifier)| modifier.as_str())
+                    .collect();
+                let ty = TYPES[token.token_type as usize].as_str();
+                (
+                    token.delta_line,
+                    token.delta_start,
+                    token.length,
+                    ty,
+                    modifiers,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn deltas_from_the_previous_token() {
+        assert_eq!(
+            decoded(tokens(None)),
+            [
+                (0, 9, 1, "class", vec!["declaration"]),
+                (1, 19, 5, "property", vec!["declaration", "storage"]),
+                (1, 13, 1, "method", vec!["declaration", "public"]),
+                // On the same line, from the start of the previous token.
+                (0, 10, 1, "parameter", vec!["declaration"]),
+                (1, 8, 5, "property", vec!["modification", "storage"]),
+                (0, 8, 1, "parameter", vec![]),
+            ]
+        );
+    }
+
+    #[test]
+    fn ranges_start_from_the_top_of_the_file() {
+        let range = Range::new(Position::new(3, 0), Position::new(4, 0));
+        assert_eq!(
+            decoded(tokens(Some(range))),
+            [
+                (3, 8, 5, "property", vec!["modification", "storage"]),
+                (0, 8, 1, "parameter", vec![]),
+            ]
+        );
+    }
+}
@@ -0,0 +1,352 @@
+use super::{completion::MARKER, on_type_formatting::code_chars};
+use crate::analysis::{
+    model::DeclKind,
+    semantics::{Definition, Semantics, Symbol},
+    source::SourceFile,
+    Analysis,
+};
+use std::rc::Rc;
+use tower_lsp::lsp_types::{
+    Documentation, MarkupContent, MarkupKind, ParameterInformation, ParameterLabel, Position,
+    SignatureHelp, SignatureInformation,
+};
+
+/// The arguments of a call the cursor is in, up to the cursor.
+#[derive(Debug)]
+struct Call {
+    /// Offset of the `(`.
+    open: usize,
+    /// Commas before the cursor, which is the index of the argument being typed.
+    commas: usize,
+    /// Name of the argument being typed in `f({name: value})`.
+    named: Option<String>,
+}
+
+/// The signatures of what the call around the cursor calls, every overload of a function, with
+/// the parameter of the argument being typed.
+///
+/// Covers functions, events after `emit`, errors after `revert`, modifiers, constructors after
+/// `new` or of base contracts, structs and the builtins.
+pub fn signature_help(
+    analysis: &mut Analysis,
+    file: &Rc<SourceFile>,
+    position: Position,
+) -> Option<SignatureHelp> {
+    let offset = file.offset(position);
+    let call = enclosing_call(&file.text, offset)?;
+    let callee_end = callee_end(&file.text, call.open)?;
+    let is_new = file.text[..callee_end]
+        .trim_end_matches(|c: char| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+        .trim_end()
+        .ends_with("new");
+
+    // The argument being typed may be empty and the calls unclosed, which the parser recovers
+    // from by flattening the expression.
+    let text = format!(
+        "{}{MARKER}{}{}",
+        &file.text[..offset],
+        closers(&file.text, offset),
+        &file.text[offset..]
+    );
+    let completed = analysis.parse_text(file, text).file;
+    let mut reference = completed
+        .model
+        .references
+        .iter()
+        .find(|reference| reference.span.end == callee_end)?
+        .clone();
+    reference.arguments = None;
+
+    let mut semantics = Semantics::new(analysis);
+    let definitions = semantics.resolve(&completed, &reference);
+    let signatures: Vec<_> = definitions
+        .iter()
+        .flat_map(|definition| signatures(&mut semantics, definition, is_new))
+        .map(|(mut signature, names)| {
+            // No parameter of an overload that takes fewer arguments.
+            let parameters = signature.parameters.as_ref().map_or(0, Vec::len);
+            let active = match &call.named {
+                Some(named) => names.iter().position(|name| name == named),
+                None => Some(call.commas).filter(|&commas| commas < parameters),
+            };
+            signature.active_parameter = active.map(|active| active as u32);
+            signature
+        })
+        .collect();
+    if signatures.is_empty() {
+        return None;
+    }
+
+    // The first overload that has the parameter being typed.
+    let active_signature = signatures
+        .iter()
+        .position(|signature| signature.active_parameter.is_some())
+        .unwrap_or_default();
+    let active_parameter = signatures[active_signature].active_parameter;
+    Some(SignatureHelp {
+        signatures,
+        active_signature: Some(active_signature as u32),
+        active_parameter,
+    })
+}
+
+/// Finds the `(` of the call around `offset`, stopping at the end of the statement. Strings and
+/// comments are skipped.
+fn enclosing_call(text: &str, offset: usize) -> Option<Call> {
+    let mut depth = 0;
+    let mut commas = 0;
+    // Start of the argument being typed, when it isn't the first one.
+    let mut argument_start = None;
+    let mut named = None;
+    for (index, c) in code_chars(text, offset).into_iter().rev() {
+        match c {
+            ')' | ']' | '}' => depth += 1,
+            '(' | '[' | '{' if depth > 0 => depth -= 1,
+            '(' => {
+                return Some(Call {
+                    open: index,
+                    commas,
+                    named,
+                })
+            }
+            '{' if named.is_none() && text[..index].trim_end().ends_with('(') => {
+                let argument = &text[argument_start.unwrap_or(index + 1)..offset];
+                named = Some(match argument.split_once(':') {
+                    Some((name, _)) => name.trim().to_string(),
+                    None => String::new(),
+                });
+            }
+            ',' if depth == 0 => {
+                argument_start.get_or_insert(index + 1);
+                commas += 1;
+            }
+            '[' | '{' | ';' => return None,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// What closes the brackets still open at `offset` in the statement, innermost first.
+fn closers(text: &str, offset: usize) -> String {
+    let mut depth = 0;
+    let mut closers = String::new();
+    for (index, c) in code_chars(text, offset).into_iter().rev() {
+        match c {
+            ')' | ']' | '}' => depth += 1,
+            '(' | '[' | '{' if depth > 0 => depth -= 1,
+            '(' => closers.push(')'),
+            '[' => closers.push(']'),
+            '{' if text[..index].trim_end().ends_with('(') => closers.push('}'),
+            '{' | ';' => break,
+            _ => {}
+        }
+    }
+    closers
+}
+
+/// End of the name that is called with the `(` at `open`, looking through call options like
+/// `{value: 1}`.
+fn callee_end(text: &str, open: usize) -> Option<usize> {
+    let before = text[..open].trim_end();
+    if !before.ends_with('}') {
+        return Some(before.len());
+    }
+    let mut depth = 0;
+    for (index, c) in before.char_indices().rev() {
+        match c {
+            '}' => depth += 1,
+            '{' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(text[..index].trim_end().len());
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// The signatures a call of `definition` can have, with the names of their parameters.
+fn signatures(
+    semantics: &mut Semantics,
+    definition: &Definition,
+    is_new: bool,
+) -> Vec<(SignatureInformation, Vec<String>)> {
+    let symbol = match definition {
+        Definition::Builtin(builtin) if builtin.function => {
+            let parameters = builtin_parameters(builtin.signature);
+            let names = parameters
+                .iter()
+                .map(|parameter| parameter.rsplit(' ').next().unwrap_or_default().to_string())
+                .collect();
+            let signature = SignatureInformation {
+                label: builtin.signature.to_string(),
+                documentation: Some(markdown(builtin.doc.to_string())),
+                parameters: Some(
+                    parameters
+                        .into_iter()
+                        .map(|parameter| ParameterInformation {
+                            label: ParameterLabel::Simple(parameter),
+                            documentation: None,
+                        })
+                        .collect(),
+                ),
+                active_parameter: None,
+            };
+            return vec![(signature, names)];
+        }
+        Definition::Builtin(_) => return Vec::new(),
+        Definition::Declaration(symbol) => symbol,
+    };
+
+    let decl = symbol.decl();
+    match decl.kind {
+        DeclKind::Function
+        | DeclKind::Event
+        | DeclKind::Error
+        | DeclKind::Modifier
+        | DeclKind::Constructor => {
+            vec![signature(
+                semantics,
+                symbol,
+                decl.signature.clone(),
+                symbol.parameters(),
+            )]
+        }
+        kind if kind.is_contract() => {
+            let constructors: Vec<_> = symbol
+                .members()
+                .into_iter()
+                .filter(|member| member.decl().kind == DeclKind::Constructor)
+                .map(|constructor| {
+                    let label = constructor.decl().signature.clone();
+                    signature(semantics, &constructor, label, constructor.parameters())
+                })
+                .collect();
+            if constructors.is_empty() && is_new {
+                let label = "constructor()".to_string();
+                return vec![signature(semantics, symbol, label, Vec::new())];
+            }
+            constructors
+        }
+        DeclKind::Struct => {
+            let members = symbol.members();
+            let fields: Vec<_> = members
+                .iter()
+                .map(|member| member.decl().signature.as_str())
+                .collect();
+            let label = format!("{}({})", decl.name, fields.join(", "));
+            vec![signature(semantics, symbol, label, members)]
+        }
+        _ => Vec::new(),
+    }
+}
+
+fn signature(
+    semantics: &mut Semantics,
+    symbol: &Symbol,
+    label: String,
+    parameters: Vec<Symbol>,
+) -> (SignatureInformation, Vec<String>) {
+    let natspec = semantics.natspec(symbol).unwrap_or_default();
+    let summary: Vec<_> = [natspec.notice.as_str(), natspec.dev.as_str()]
+        .into_iter()
+        .filter(|text| !text.is_empty())
+        .collect();
+    let names = parameters
+        .iter()
+        .map(|parameter| parameter.decl().name.clone())
+        .collect();
+    let parameters = parameters
+        .iter()
+        .map(|parameter| {
+            let decl = parameter.decl();
+            let documentation = natspec
+                .params
+                .iter()
+                .find(|(name, _)| *name == decl.name)
+                .map(|(_, text)| markdown(text.clone()));
+            ParameterInformation {
+                label: ParameterLabel::Simple(decl.signature.clone()),
+                documentation,
+            }
+        })
+        .collect();
+    let signature = SignatureInformation {
+        label,
+        documentation: (!summary.is_empty()).then(|| markdown(summary.join("\n\n"))),
+        parameters: Some(parameters),
+        active_parameter: None,
+    };
+    (signature, names)
+}
+
+/// The parameters between the first parentheses of a builtin signature.
+fn builtin_parameters(signature: &str) -> Vec<String> {
+    let Some((_, rest)) = signature.split_once('(') else {
+        return Vec::new();
+    };
+    let Some((parameters, _)) = rest.split_once(')') else {
+        return Vec::new();
+    };
+    parameters
+        .split(',')
+        .map(str::trim)
+        .filter(|parameter| !parameter.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn markdown(value: String) -> Documentation {
+    Documentation::MarkupContent(MarkupContent {
+        kind: MarkupKind::Markdown,
+        value,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{closers, enclosing_call, signature_help};
+    use crate::features::fixture::Fixture;
+
+    #[test]
+    fn strings_and_comments_are_skipped() {
+        let text = "g(\"x,y\", ";
+        let call = enclosing_call(text, text.len()).unwrap();
+        assert_eq!((call.open, call.commas), (1, 1));
+        let text = "g(\"x,y";
+        let call = enclosing_call(text, text.len()).unwrap();
+        assert_eq!((call.open, call.commas), (1, 0));
+        let text = "g(\"(\" /* , ) */ 'a,' // ,\n";
+        let call = enclosing_call(text, text.len()).unwrap();
+        assert_eq!((call.open, call.commas), (1, 0));
+        assert_eq!(closers(text, text.len()), ")");
+    }
+
+    #[test]
+    fn overload_with_the_parameter_being_typed() {
+        let text = "contract C {
+    function f(uint256 a) public {}
+    function f(uint256 a, uint256 b) public {}
+    function g() public {
+        f(1, 2);
+    }
+}
+";
+        let mut fixture = Fixture::new(&[("/src/C.sol", text)]);
+        let (file, position) = fixture.at("/src/C.sol", "2);");
+        let help = signature_help(&mut fixture.analysis, &file, position).unwrap();
+        assert_eq!(help.signatures.len(), 2);
+        let active = &help.signatures[help.active_signature.unwrap() as usize];
+        assert!(active.label.contains("uint256 b"), "{}", active.label);
+        assert_eq!(help.active_parameter, Some(1));
+        let other = help
+            .signatures
+            .iter()
+            .find(|signature| !signature.label.contains("uint256 b"))
+            .unwrap();
+        assert_eq!(other.active_parameter, None);
+    }
+}
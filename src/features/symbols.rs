@@ -0,0 +1,156 @@
+use crate::analysis::{
+    model::{DeclId, DeclKind, Declaration},
+    source::SourceFile,
+};
+use tower_lsp::lsp_types::{DocumentSymbol, SymbolKind};
+
+/// The outline of a file: its contracts with their members, and the top-level declarations.
+pub fn document_symbols(file: &SourceFile) -> Vec<DocumentSymbol> {
+    let top_level: Vec<_> = file.model.scopes[0]
+        .declarations
+        .iter()
+        .copied()
+        .filter(|&id| file.model.declarations[id].container.is_none())
+        .collect();
+    symbols(file, &top_level)
+}
+
+fn symbols(file: &SourceFile, ids: &[DeclId]) -> Vec<DocumentSymbol> {
+    ids.iter()
+        .filter_map(|&id| {
+            let decl = &file.model.declarations[id];
+            let kind = symbol_kind(decl)?;
+            let children = symbols(file, &decl.members);
+            #[allow(deprecated)]
+            Some(DocumentSymbol {
+                name: decl.name.clone(),
+                detail: decl.ty.as_ref().map(ToString::to_string),
+                kind,
+                tags: None,
+                deprecated: None,
+                range: file.span_range(decl.span),
+                selection_range: file.span_range(decl.name_span),
+                children: (!children.is_empty()).then_some(children),
+            })
+        })
+        .collect()
+}
+
+/// How a declaration is shown in outlines, `None` for the ones that aren't.
+pub fn symbol_kind(decl: &Declaration) -> Option<SymbolKind> {
+    if decl.name.is_empty() {
+        return None;
+    }
+    let kind = match decl.kind {
+        DeclKind::Contract => SymbolKind::CLASS,
+        DeclKind::Interface => SymbolKind::INTERFACE,
+        DeclKind::Library => SymbolKind::MODULE,
+        DeclKind::Function if decl.container.is_none() => SymbolKind::FUNCTION,
+        DeclKind::Function | DeclKind::Fallback | DeclKind::Receive | DeclKind::Modifier => {
+            SymbolKind::METHOD
+        }
+        DeclKind::Constructor => SymbolKind::CONSTRUCTOR,
+        DeclKind::StateVariable | DeclKind::StructMember => SymbolKind::FIELD,
+        DeclKind::Constant => SymbolKind::CONSTANT,
+        DeclKind::Struct => SymbolKind::STRUCT,
+        DeclKind::Enum => SymbolKind::ENUM,
+        DeclKind::EnumMember => SymbolKind::ENUM_MEMBER,
+        DeclKind::Event | DeclKind::Error => SymbolKind::EVENT,
+        DeclKind::UserDefinedValueType => SymbolKind::TYPE_PARAMETER,
+        DeclKind::Parameter
+        | DeclKind::LocalVariable
+        | DeclKind::YulFunction
+        | DeclKind::YulVariable
+        | DeclKind::Module
+        | DeclKind::Import => return None,
+    };
+    Some(kind)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::document_symbols;
+    use crate::features::fixture::Fixture;
+    use tower_lsp::lsp_types::{DocumentSymbol, Position, Range};
+
+    /// The outline as `name: kind` lines, indented under their parents.
+    fn outline(symbols: &[DocumentSymbol], depth: usize, lines: &mut Vec<String>) {
+        for symbol in symbols {
+            let detail = symbol
+                .detail
+                .as_ref()
+                .map(|detail| format!(" {detail}"))
+                .unwrap_or_default();
+            lines.push(format!(
+                "{}{}: {:?}{detail}",
+                "  ".repeat(depth),
+                symbol.name,
+                symbol.kind
+            ));
+            outline(
+                symbol.children.as_deref().unwrap_or_default(),
+                depth + 1,
+                lines,
+            );
+        }
+    }
+
+    #[test]
+    fn contracts_and_top_level_declarations() {
+        let text = "import {X} from \"./X.sol\";
+uint256 constant LIMIT = 10;
+function helper(uint256 a) pure returns (uint256) {
+    uint256 b = a;
+    return b;
+}
+struct Point {
+    uint256 x;
+}
+contract C {
+    enum Side { Left, Right }
+    event Moved(address who);
+    uint256 public total;
+    constructor() {}
+    modifier only() { _; }
+    function f() public only {}
+    receive() external payable {}
+}
+";
+        let mut fixture = Fixture::new(&[("/src/C.sol", text)]);
+        let file = fixture.file("/src/C.sol");
+        let mut lines = Vec::new();
+        outline(&document_symbols(&file), 0, &mut lines);
+        let expected = "LIMIT: Constant uint256
+helper: Function
+Point: Struct
+  x: Field uint256
+C: Class
+  Side: Enum
+    Left: EnumMember
+    Right: EnumMember
+  Moved: Event
+  total: Field uint256
+  constructor: Constructor
+  only: Method
+  f: Method
+  receive: Method";
+        assert_eq!(lines.join("\n"), expected);
+    }
+
+    #[test]
+    fn ranges_span_the_declaration_and_select_the_name() {
+        let text = "contract C {\n    uint256 public total;\n}\n";
+        let mut fixture = Fixture::new(&[("/src/C.sol", text)]);
+        let file = fixture.file("/src/C.sol");
+        let symbols = document_symbols(&file);
+        let total = &symbols[0].children.as_ref().unwrap()[0];
+        assert_eq!(
+            total.range,
+            Range::new(Position::new(1, 4), Position::new(1, 25))
+        );
+        assert_eq!(
+            total.selection_range,
+            Range::new(Position::new(1, 19), Position::new(1, 24))
+        );
+    }
+}
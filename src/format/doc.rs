@@ -0,0 +1,143 @@
+/// What the formatter lays out: text, and the places where lines may break.
+///
+/// A group is printed on one line when it fits, otherwise its lines break, the outermost group
+/// first.
+#[derive(Debug, Clone)]
+pub enum Doc {
+    Text(String),
+    /// A space, or a newline when the group breaks.
+    Line,
+    /// Nothing, or a newline when the group breaks.
+    SoftLine,
+    /// Always a newline, which breaks the groups around it.
+    HardLine,
+    /// A newline unless nothing was printed on the line yet. Breaks the groups around it too.
+    FreshLine,
+    /// Text printed right before the next newline, like a comment at the end of a line. Breaks
+    /// the groups around it too.
+    LineSuffix(String),
+    Indent(Vec<Doc>),
+    Group(Vec<Doc>),
+    Concat(Vec<Doc>),
+}
+
+impl Doc {
+    pub fn text(text: impl Into<String>) -> Self {
+        Self::Text(text.into())
+    }
+
+    pub fn nil() -> Self {
+        Self::Concat(Vec::new())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Flat,
+    Break,
+}
+
+/// Prints `doc` within `width` columns, indenting by `indent` per level.
+pub fn print(doc: &Doc, width: usize, indent: &str) -> String {
+    let mut out = String::new();
+    let mut column = 0;
+    let mut suffixes: Vec<&str> = Vec::new();
+    let mut stack = vec![(0, Mode::Break, doc)];
+    while let Some((level, mode, doc)) = stack.pop() {
+        match doc {
+            Doc::Text(text) => {
+                out.push_str(text);
+                column += text.chars().count();
+            }
+            Doc::Line if mode == Mode::Flat => {
+                out.push(' ');
+                column += 1;
+            }
+            Doc::SoftLine if mode == Mode::Flat => {}
+            Doc::FreshLine if suffixes.is_empty() && at_line_start(&out) => {}
+            Doc::Line | Doc::SoftLine | Doc::HardLine | Doc::FreshLine => {
+                let trimmed = out.trim_end_matches([' ', '\t']).len();
+                out.truncate(trimmed);
+                for suffix in suffixes.drain(..) {
+                    out.push_str(suffix);
+                }
+                out.push('\n');
+                out.push_str(&indent.repeat(level));
+                column = indent.len() * level;
+            }
+            Doc::LineSuffix(suffix) => suffixes.push(suffix),
+            Doc::Indent(docs) => {
+                stack.extend(docs.iter().rev().map(|doc| (level + 1, mode, doc)));
+            }
+            Doc::Concat(docs) => stack.extend(docs.iter().rev().map(|doc| (level, mode, doc))),
+            Doc::Group(docs) => {
+                let mode = match mode {
+                    Mode::Flat => Mode::Flat,
+                    Mode::Break if fits(docs, &stack, width as isize - column as isize) => {
+                        Mode::Flat
+                    }
+                    Mode::Break => Mode::Break,
+                };
+                stack.extend(docs.iter().rev().map(|doc| (level, mode, doc)));
+            }
+        }
+    }
+    let trimmed = out.trim_end_matches([' ', '\t']).len();
+    out.truncate(trimmed);
+    for suffix in suffixes {
+        out.push_str(suffix);
+    }
+    let trimmed = out.trim_end().len();
+    out.truncate(trimmed);
+    out.push('\n');
+    out
+}
+
+/// Whether only indentation was printed since the last newline.
+fn at_line_start(out: &str) -> bool {
+    let start = out.rfind('\n').map_or(0, |index| index + 1);
+    out[start..].trim().is_empty()
+}
+
+/// Whether `docs` fit on the rest of the line when printed flat, along with what follows them
+/// up to the next newline.
+fn fits(docs: &[Doc], rest: &[(usize, Mode, &Doc)], mut width: isize) -> bool {
+    // The flag tells what follows the group apart from the group itself.
+    let mut stack: Vec<(Mode, &Doc, bool)> = docs
+        .iter()
+        .rev()
+        .map(|doc| (Mode::Flat, doc, false))
+        .collect();
+    let mut rest = rest.iter().rev();
+    loop {
+        let (mode, doc, after) = match stack.pop() {
+            Some(next) => next,
+            None => match rest.next() {
+                Some(&(_, mode, doc)) => (mode, doc, true),
+                None => return true,
+            },
+        };
+        match doc {
+            Doc::Text(text) => {
+                width -= text.chars().count() as isize;
+                if width < 0 {
+                    return false;
+                }
+            }
+            Doc::Line | Doc::SoftLine if mode == Mode::Break => return true,
+            Doc::Line => {
+                width -= 1;
+                if width < 0 {
+                    return false;
+                }
+            }
+            Doc::SoftLine => {}
+            Doc::HardLine | Doc::FreshLine | Doc::LineSuffix(_) if !after => return false,
+            Doc::HardLine | Doc::FreshLine => return true,
+            Doc::LineSuffix(_) => {}
+            Doc::Indent(docs) | Doc::Group(docs) | Doc::Concat(docs) => {
+                stack.extend(docs.iter().rev().map(|doc| (mode, doc, after)));
+            }
+        }
+    }
+}
@@ -0,0 +1,1058 @@
+mod doc;
+
+use crate::analysis::{source::ParsedSource, syntax::SyntaxNode};
+use doc::Doc;
+use slang_solidity::cst::{EdgeLabel, NonterminalKind, TerminalKind};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+};
+
+/// The `[fmt]` options of `foundry.toml` that the formatter follows, with `forge fmt` defaults.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormatOptions {
+    pub line_length: usize,
+    pub tab_width: usize,
+    /// Spaces inside braces, as in `import { A } from "a.sol";`.
+    pub bracket_spacing: bool,
+    pub int_types: IntTypes,
+    pub quote_style: QuoteStyle,
+    pub multiline_func_header: MultilineFuncHeader,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self {
+            line_length: 120,
+            tab_width: 4,
+            bracket_spacing: false,
+            int_types: IntTypes::Long,
+            quote_style: QuoteStyle::Double,
+            multiline_func_header: MultilineFuncHeader::AttributesFirst,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IntTypes {
+    /// `uint256` rather than `uint`.
+    Long,
+    /// `uint` rather than `uint256`.
+    Short,
+    Preserve,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuoteStyle {
+    Double,
+    Single,
+    Preserve,
+}
+
+/// Which part of a function header breaks first when it doesn't fit on a line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MultilineFuncHeader {
+    AttributesFirst,
+    ParamsFirst,
+    /// Parameters first when there are several, attributes first otherwise.
+    ParamsFirstMulti,
+    /// Both parameters and attributes as soon as one of them breaks.
+    All,
+    AllParams,
+}
+
+impl FormatOptions {
+    /// Reads the `fmt` table of `foundry.toml`, keeping the defaults for what isn't set.
+    pub fn from_table(table: &toml::Table) -> Self {
+        let mut options = Self::default();
+        let number = |key: &str| {
+            table
+                .get(key)
+                .and_then(|value| value.as_integer())
+                .and_then(|value| usize::try_from(value).ok())
+        };
+        let string = |key: &str| table.get(key).and_then(|value| value.as_str());
+        if let Some(line_length) = number("line_length") {
+            options.line_length = line_length;
+        }
+        if let Some(tab_width) = number("tab_width") {
+            options.tab_width = tab_width;
+        }
+        if let Some(bracket_spacing) = table.get("bracket_spacing").and_then(|v| v.as_bool()) {
+            options.bracket_spacing = bracket_spacing;
+        }
+        match string("int_types") {
+            Some("long") => options.int_types = IntTypes::Long,
+            Some("short") => options.int_types = IntTypes::Short,
+            Some("preserve") => options.int_types = IntTypes::Preserve,
+            Some(other) => log::warn!("unknown fmt.int_types {other:?}"),
+            None => {}
+        }
+        match string("quote_style") {
+            Some("double") => options.quote_style = QuoteStyle::Double,
+            Some("single") => options.quote_style = QuoteStyle::Single,
+            Some("preserve") => options.quote_style = QuoteStyle::Preserve,
+            Some(other) => log::warn!("unknown fmt.quote_style {other:?}"),
+            None => {}
+        }
+        match string("multiline_func_header") {
+            Some("attributes_first") => {
+                options.multiline_func_header = MultilineFuncHeader::AttributesFirst
+            }
+            Some("params_first") => {
+                options.multiline_func_header = MultilineFuncHeader::ParamsFirst
+            }
+            Some("params_first_multi") => {
+                options.multiline_func_header = MultilineFuncHeader::ParamsFirstMulti
+            }
+            Some("all") => options.multiline_func_header = MultilineFuncHeader::All,
+            Some("all_params") => options.multiline_func_header = MultilineFuncHeader::AllParams,
+            Some(other) => log::warn!("unknown fmt.multiline_func_header {other:?}"),
+            None => {}
+        }
+        options
+    }
+}
+
+/// Formats a whole file, keeping its comments and the line ending of its first line. `None` when
+/// the file has syntax errors, which the formatter could make worse.
+pub fn format(source: &ParsedSource, options: &FormatOptions) -> Option<String> {
+    if !source.errors().is_empty() {
+        return None;
+    }
+    let root = SyntaxNode::root(source.tree());
+    let formatter = Formatter::new(&root, options);
+    let doc = formatter.source_unit(&root);
+    let formatted = doc::print(&doc, options.line_length, &" ".repeat(options.tab_width));
+    let crlf = source
+        .text
+        .split_once('\n')
+        .is_some_and(|(line, _)| line.ends_with('\r'));
+    Some(match crlf {
+        true => formatted.replace('\n', "\r\n"),
+        false => formatted,
+    })
+}
+
+#[derive(Debug)]
+struct Comment {
+    text: String,
+    /// `//` comments, which end the line.
+    line: bool,
+    /// An empty line separates the comment from what's before it.
+    blank_before: bool,
+    /// The comment starts its line.
+    newline_before: bool,
+    /// The comment ends its line, rather than being followed by code.
+    newline_after: bool,
+    /// Column of the comment in the original text, which the lines of a block comment are
+    /// indented relative to.
+    column: usize,
+}
+
+/// The comments around a token.
+#[derive(Debug, Default)]
+struct Token {
+    /// On the lines before the token.
+    leading: Vec<Comment>,
+    /// After the token on the same line.
+    trailing: Vec<Comment>,
+    /// An empty line separates the token from its leading comments or the previous token.
+    blank_before: bool,
+}
+
+struct Formatter<'a> {
+    options: &'a FormatOptions,
+    /// Tokens by offset.
+    tokens: HashMap<usize, Token>,
+    /// Comments after the last token.
+    end: Vec<Comment>,
+    /// Tokens whose leading comments were laid out before the item they start, outside of its
+    /// groups so that they don't break them.
+    hoisted: RefCell<HashSet<usize>>,
+}
+
+/// Lists whose items are separated by commas.
+const COMMA_LISTS: &[NonterminalKind] = &[
+    NonterminalKind::Parameters,
+    NonterminalKind::PositionalArguments,
+    NonterminalKind::NamedArguments,
+    NonterminalKind::CallOptions,
+    NonterminalKind::TupleValues,
+    NonterminalKind::ArrayValues,
+    NonterminalKind::ImportDeconstructionSymbols,
+    NonterminalKind::UsingDeconstructionSymbols,
+    NonterminalKind::EventParameters,
+    NonterminalKind::ErrorParameters,
+    NonterminalKind::OverridePaths,
+    NonterminalKind::InheritanceTypes,
+    NonterminalKind::TupleDeconstructionElements,
+    NonterminalKind::EnumMembers,
+    NonterminalKind::AssemblyFlags,
+    NonterminalKind::YulArguments,
+    NonterminalKind::YulParameters,
+    NonterminalKind::YulVariableNames,
+    NonterminalKind::YulPaths,
+];
+
+/// Lists whose items go on their own lines.
+const LINE_LISTS: &[NonterminalKind] = &[
+    NonterminalKind::ContractMembers,
+    NonterminalKind::InterfaceMembers,
+    NonterminalKind::LibraryMembers,
+    NonterminalKind::StructMembers,
+    NonterminalKind::Statements,
+    NonterminalKind::YulStatements,
+];
+
+impl<'a> Formatter<'a> {
+    fn new(root: &SyntaxNode, options: &'a FormatOptions) -> Self {
+        let mut tokens: HashMap<usize, Token> = HashMap::new();
+        let mut last = None;
+        let mut pending: Vec<Comment> = Vec::new();
+        let mut newlines = 0;
+        // Whether a comment would still be on the line of the last token.
+        let mut same_line = false;
+        let mut column = 0;
+        for terminal in root.terminals() {
+            let text = terminal.terminal_text().unwrap_or_default();
+            let kind = terminal.terminal_kind();
+            let start_column = column;
+            match text.rfind('\n') {
+                Some(index) => column = text[index + 1..].chars().count(),
+                None => column += text.chars().count(),
+            }
+            if !terminal.is_trivia() {
+                tokens.insert(
+                    terminal.offset.utf8,
+                    Token {
+                        leading: std::mem::take(&mut pending),
+                        trailing: Vec::new(),
+                        blank_before: newlines >= 2,
+                    },
+                );
+                last = Some(terminal.offset.utf8);
+                newlines = 0;
+                same_line = true;
+                continue;
+            }
+            match kind {
+                Some(TerminalKind::EndOfLine) => {
+                    newlines += 1;
+                    same_line = false;
+                    if let Some(comment) = pending.last_mut() {
+                        comment.newline_after = true;
+                    }
+                }
+                Some(
+                    TerminalKind::SingleLineComment
+                    | TerminalKind::SingleLineNatSpecComment
+                    | TerminalKind::MultiLineComment
+                    | TerminalKind::MultiLineNatSpecComment,
+                ) => {
+                    let line = matches!(
+                        kind,
+                        Some(
+                            TerminalKind::SingleLineComment
+                                | TerminalKind::SingleLineNatSpecComment
+                        )
+                    );
+                    let comment = Comment {
+                        text: text.trim_end().to_string(),
+                        line,
+                        blank_before: newlines >= 2,
+                        newline_before: newlines > 0 || last.is_none(),
+                        newline_after: line,
+                        column: start_column,
+                    };
+                    match last.and_then(|last| tokens.get_mut(&last)) {
+                        Some(token) if same_line => token.trailing.push(comment),
+                        _ => pending.push(comment),
+                    }
+                    newlines = 0;
+                }
+                _ => {}
+            }
+        }
+        Self {
+            options,
+            tokens,
+            end: pending,
+            hoisted: RefCell::default(),
+        }
+    }
+
+    fn source_unit(&self, root: &SyntaxNode) -> Doc {
+        let mut docs = Vec::new();
+        if let Some(members) = root.child(EdgeLabel::Members) {
+            docs.push(self.lines(&members));
+        }
+        for (index, comment) in self.end.iter().enumerate() {
+            if index > 0 || !docs.is_empty() {
+                docs.push(Doc::HardLine);
+            }
+            if comment.blank_before && !docs.is_empty() {
+                docs.push(Doc::HardLine);
+            }
+            docs.push(self.comment(comment));
+        }
+        Doc::Concat(docs)
+    }
+
+    fn node(&self, node: &SyntaxNode) -> Doc {
+        use NonterminalKind as K;
+
+        let Some(kind) = node.nonterminal_kind() else {
+            return match node.is_trivia() {
+                true => Doc::nil(),
+                false => self.token(node),
+            };
+        };
+        match kind {
+            K::FunctionDefinition
+            | K::ConstructorDefinition
+            | K::ModifierDefinition
+            | K::FallbackFunctionDefinition
+            | K::ReceiveFunctionDefinition
+            | K::UnnamedFunctionDefinition => self.function(node),
+            K::AdditiveExpression
+            | K::MultiplicativeExpression
+            | K::ExponentiationExpression
+            | K::ShiftExpression
+            | K::BitwiseAndExpression
+            | K::BitwiseOrExpression
+            | K::BitwiseXorExpression
+            | K::ComparisonExpression
+            | K::EqualityExpression
+            | K::AndExpression
+            | K::OrExpression => {
+                let (Some(left), Some(operator), Some(right)) = (
+                    node.child(EdgeLabel::LeftOperand),
+                    node.child(EdgeLabel::Operator),
+                    node.child(EdgeLabel::RightOperand),
+                ) else {
+                    return self.sequence(node);
+                };
+                Doc::Group(vec![
+                    self.node(&left),
+                    Doc::Indent(vec![
+                        Doc::Line,
+                        self.node(&operator),
+                        Doc::text(" "),
+                        self.node(&right),
+                    ]),
+                ])
+            }
+            K::ConditionalExpression => {
+                let (Some(operand), Some(question), Some(yes), Some(colon), Some(no)) = (
+                    node.child(EdgeLabel::Operand),
+                    node.child(EdgeLabel::QuestionMark),
+                    node.child(EdgeLabel::TrueExpression),
+                    node.child(EdgeLabel::Colon),
+                    node.child(EdgeLabel::FalseExpression),
+                ) else {
+                    return self.sequence(node);
+                };
+                Doc::Group(vec![
+                    self.node(&operand),
+                    Doc::Indent(vec![
+                        Doc::Line,
+                        self.node(&question),
+                        Doc::text(" "),
+                        self.node(&yes),
+                        Doc::Line,
+                        self.node(&colon),
+                        Doc::text(" "),
+                        self.node(&no),
+                    ]),
+                ])
+            }
+            // `case`s and `default` of a `switch` go on their own lines, the first one too.
+            K::YulSwitchCases => Doc::Concat(
+                node.items()
+                    .flat_map(|case| [Doc::HardLine, self.node(&case)])
+                    .collect(),
+            ),
+            kind if COMMA_LISTS.contains(&kind) => Doc::Group(vec![Doc::Indent(self.items(node))]),
+            _ => self.sequence(node),
+        }
+    }
+
+    /// The children of `node` separated by spaces where Solidity code has them, with the lists
+    /// between brackets laid out.
+    fn sequence(&self, node: &SyntaxNode) -> Doc {
+        let context = node.nonterminal_kind();
+        let children: Vec<_> = node
+            .children()
+            .filter(|child| !child.is_trivia())
+            .filter(|child| edge_token(child, false).is_some() || is_list(child))
+            .collect();
+        let mut docs = Vec::new();
+        let mut previous: Option<TerminalKind> = None;
+        let mut index = 0;
+        while index < children.len() {
+            let child = &children[index];
+            let list = children.get(index + 1).zip(children.get(index + 2));
+            let (doc, first, last, consumed) = match list {
+                Some((list, close)) if is_open(child) && is_list(list) && is_close(close) => {
+                    let doc = self.delimited(child, list, close);
+                    (doc, child.terminal_kind(), close.terminal_kind(), 3)
+                }
+                _ => {
+                    let first = edge_token(child, false).and_then(|t| t.terminal_kind());
+                    let last = edge_token(child, true).and_then(|t| t.terminal_kind());
+                    (self.node(child), first, last, 1)
+                }
+            };
+            // Empty lists like the parameters of `f()` are still open and close tokens.
+            if let (Some(previous), Some(first)) = (previous, first) {
+                // `else` follows the `}` of a block, but starts a line after a single statement.
+                if child.label == Some(EdgeLabel::ElseBranch) && previous == TerminalKind::Semicolon
+                {
+                    docs.push(Doc::HardLine);
+                } else if space(context, previous, first) {
+                    docs.push(Doc::text(" "));
+                }
+            }
+            docs.push(doc);
+            previous = last.or(previous);
+            index += consumed;
+        }
+        Doc::Concat(docs)
+    }
+
+    /// A list between brackets: on separate lines for statements and members, otherwise on one
+    /// line if it fits.
+    fn delimited(&self, open: &SyntaxNode, list: &SyntaxNode, close: &SyntaxNode) -> Doc {
+        let kind = list.nonterminal_kind();
+        let close_token = self.tokens.get(&close.offset.utf8);
+        let open_token = self.tokens.get(&open.offset.utf8);
+        let empty = list.items().next().is_none()
+            && close_token.is_none_or(|token| token.leading.is_empty())
+            && open_token.is_none_or(|token| token.trailing.is_empty());
+        if empty {
+            return Doc::Concat(vec![self.token(open), self.token(close)]);
+        }
+        let inner = self.dangling(close_token);
+        let close_doc = self.hoisted_token(close);
+
+        let lines = kind.is_some_and(|kind| LINE_LISTS.contains(&kind));
+        if lines || kind == Some(NonterminalKind::EnumMembers) {
+            let mut body = match (list.items().next(), lines) {
+                (None, _) => Vec::new(),
+                (Some(_), true) => vec![Doc::HardLine, self.lines(list)],
+                (Some(_), false) => vec![
+                    Doc::HardLine,
+                    Doc::Concat(self.separated(list, Doc::HardLine)),
+                ],
+            };
+            body.extend(inner);
+            return Doc::Concat(vec![
+                self.token(open),
+                Doc::Indent(body),
+                Doc::HardLine,
+                close_doc,
+            ]);
+        }
+
+        let braces = open.terminal_kind() == Some(TerminalKind::OpenBrace);
+        let padding = match braces && self.options.bracket_spacing {
+            true => Doc::Line,
+            false => Doc::SoftLine,
+        };
+        let mut body = match list.items().next() {
+            Some(_) => vec![padding.clone()],
+            None => Vec::new(),
+        };
+        body.extend(self.items(list));
+        body.extend(inner);
+        Doc::Group(vec![
+            self.token(open),
+            Doc::Indent(body),
+            padding,
+            close_doc,
+        ])
+    }
+
+    /// The items of a comma separated list, each comma followed by a line.
+    fn items(&self, list: &SyntaxNode) -> Vec<Doc> {
+        self.separated(list, Doc::Line)
+    }
+
+    fn separated(&self, list: &SyntaxNode, line: Doc) -> Vec<Doc> {
+        let mut docs = Vec::new();
+        let mut children = list
+            .children()
+            .filter(|child| !child.is_trivia())
+            .peekable();
+        while let Some(child) = children.next() {
+            match child.label {
+                Some(EdgeLabel::Separator) => {
+                    docs.push(self.token(&child));
+                    // Nothing follows the comma of an omitted item, as in `(bool ok,)`.
+                    let next = children.peek();
+                    if next.is_none_or(|next| edge_token(next, false).is_some()) {
+                        docs.push(line.clone());
+                    }
+                }
+                _ => docs.push(self.node(&child)),
+            }
+        }
+        docs
+    }
+
+    /// Items on their own lines, keeping one empty line where there was one and adding one around
+    /// definitions with a body.
+    fn lines(&self, list: &SyntaxNode) -> Doc {
+        let spaced = list.nonterminal_kind().is_some_and(|kind| {
+            matches!(
+                kind,
+                NonterminalKind::SourceUnitMembers
+                    | NonterminalKind::ContractMembers
+                    | NonterminalKind::InterfaceMembers
+                    | NonterminalKind::LibraryMembers
+            )
+        });
+        let mut docs = Vec::new();
+        let mut previous: Option<SyntaxNode> = None;
+        for item in list.items() {
+            let first = edge_token(&item, false);
+            let token = first
+                .as_ref()
+                .and_then(|first| self.tokens.get(&first.offset.utf8));
+            if let Some(previous) = &previous {
+                docs.push(Doc::HardLine);
+                let blank = token.is_some_and(|token| match token.leading.first() {
+                    Some(comment) => comment.blank_before,
+                    None => token.blank_before,
+                });
+                if blank || (spaced && (has_body(previous) || has_body(&item))) {
+                    docs.push(Doc::HardLine);
+                }
+            }
+            docs.extend(self.leading(token));
+            if let Some(first) = first {
+                self.hoisted.borrow_mut().insert(first.offset.utf8);
+            }
+            docs.push(self.node(&item));
+            previous = Some(item);
+        }
+        Doc::Concat(docs)
+    }
+
+    /// A function header, broken as `multiline_func_header` says when it doesn't fit, and its
+    /// body.
+    fn function(&self, node: &SyntaxNode) -> Doc {
+        let children: Vec<_> = node
+            .children()
+            .filter(|child| !child.is_trivia() && edge_token(child, false).is_some())
+            .collect();
+        let mut head = Vec::new();
+        let mut parameters = None;
+        let mut attributes = Vec::new();
+        let mut body = None;
+        for child in &children {
+            match child.label {
+                Some(EdgeLabel::Parameters) => parameters = Some(child),
+                Some(EdgeLabel::Attributes) => {
+                    attributes.extend(child.items().map(|attribute| self.node(&attribute)))
+                }
+                Some(EdgeLabel::Returns) => attributes.push(self.node(child)),
+                Some(EdgeLabel::Body) => body = Some(child.variant()),
+                _ if parameters.is_none() => {
+                    if !head.is_empty() {
+                        head.push(Doc::text(" "));
+                    }
+                    head.push(self.node(child));
+                }
+                _ => {}
+            }
+        }
+
+        // `(`, the parameters and `)`.
+        let mut open = Doc::nil();
+        let mut list = Vec::new();
+        let mut close = Doc::nil();
+        let mut count = 0;
+        if let Some(parameters) = parameters {
+            let (Some(open_node), Some(list_node), Some(close_node)) = (
+                parameters.child(EdgeLabel::OpenParen),
+                parameters.child(EdgeLabel::Parameters),
+                parameters.child(EdgeLabel::CloseParen),
+            ) else {
+                return self.sequence(node);
+            };
+            count = list_node.items().count();
+            let close_token = self.tokens.get(&close_node.offset.utf8);
+            open = self.token(&open_node);
+            if count > 0 || close_token.is_some_and(|token| !token.leading.is_empty()) {
+                list.push(Doc::SoftLine);
+                list.extend(self.items(&list_node));
+                list.extend(self.dangling(close_token));
+            }
+            close = self.hoisted_token(&close_node);
+        }
+        let softline = match list.is_empty() {
+            true => Doc::nil(),
+            false => Doc::SoftLine,
+        };
+
+        let block = body.as_ref().filter(|body| body.is(NonterminalKind::Block));
+        // Before `{`: the line of the attributes when they break, so that `{` gets its own line.
+        let before_body = match (block, attributes.is_empty()) {
+            (Some(_), false) => Doc::Line,
+            (Some(_), true) => Doc::text(" "),
+            (None, _) => Doc::nil(),
+        };
+        let attributes: Vec<_> = attributes
+            .into_iter()
+            .flat_map(|attribute| [Doc::Line, attribute])
+            .collect();
+        let body = body.map_or_else(Doc::nil, |body| self.node(&body));
+
+        let style = match self.options.multiline_func_header {
+            MultilineFuncHeader::ParamsFirstMulti if count > 1 => MultilineFuncHeader::ParamsFirst,
+            MultilineFuncHeader::ParamsFirstMulti => MultilineFuncHeader::AttributesFirst,
+            style => style,
+        };
+        let header = match style {
+            MultilineFuncHeader::ParamsFirst => Doc::Group(vec![
+                Doc::Concat(head),
+                open,
+                Doc::Indent(list),
+                softline,
+                close,
+                Doc::Group(vec![Doc::Indent(attributes), before_body]),
+            ]),
+            MultilineFuncHeader::All | MultilineFuncHeader::AllParams => Doc::Group(vec![
+                Doc::Concat(head),
+                open,
+                Doc::Indent(list),
+                softline,
+                close,
+                Doc::Indent(attributes),
+                before_body,
+            ]),
+            // The attributes stay after `)` when the parameters had to break but they fit there.
+            _ => Doc::Group(vec![
+                Doc::Concat(head),
+                Doc::Group(vec![open, Doc::Indent(list), softline, close]),
+                Doc::Group(vec![Doc::Indent(attributes), before_body]),
+            ]),
+        };
+        Doc::Concat(vec![header, body])
+    }
+
+    /// A token whose leading comments are laid out by the caller.
+    fn hoisted_token(&self, node: &SyntaxNode) -> Doc {
+        self.hoisted.borrow_mut().insert(node.offset.utf8);
+        self.token(node)
+    }
+
+    /// A token with its comments.
+    fn token(&self, node: &SyntaxNode) -> Doc {
+        let token = self.tokens.get(&node.offset.utf8);
+        let mut docs = match self.hoisted.borrow().contains(&node.offset.utf8) {
+            true => Vec::new(),
+            false => self.leading(token),
+        };
+        docs.push(self.bare(node));
+        for comment in token.iter().flat_map(|token| &token.trailing) {
+            match comment.line {
+                true => docs.push(Doc::LineSuffix(format!(" {}", comment.text))),
+                false => docs.extend([Doc::text(" "), self.comment(comment)]),
+            }
+        }
+        Doc::Concat(docs)
+    }
+
+    /// The comments before a token, each on its own line unless it was followed by code. The
+    /// first one starts a line when it did, rather than following the previous token and its
+    /// trailing comments.
+    fn leading(&self, token: Option<&Token>) -> Vec<Doc> {
+        let Some(token) = token else {
+            return Vec::new();
+        };
+        let mut docs = Vec::new();
+        for (index, comment) in token.leading.iter().enumerate() {
+            if index == 0 && comment.newline_before {
+                docs.push(Doc::FreshLine);
+            }
+            if index > 0 && comment.blank_before {
+                docs.push(Doc::HardLine);
+            }
+            docs.push(self.comment(comment));
+            docs.push(match comment.newline_after {
+                true => Doc::HardLine,
+                false => Doc::text(" "),
+            });
+        }
+        if !docs.is_empty() && token.blank_before {
+            docs.push(Doc::HardLine);
+        }
+        docs
+    }
+
+    /// The comments before a closing bracket, on their own lines after the last item.
+    fn dangling(&self, token: Option<&Token>) -> Vec<Doc> {
+        let mut docs = Vec::new();
+        for comment in token.iter().flat_map(|token| &token.leading) {
+            docs.push(Doc::HardLine);
+            if comment.blank_before {
+                docs.push(Doc::HardLine);
+            }
+            docs.push(self.comment(comment));
+        }
+        docs
+    }
+
+    /// A token without its comments, with the types and quotes the options ask for.
+    fn bare(&self, node: &SyntaxNode) -> Doc {
+        let text = node.terminal_text().unwrap_or_default();
+        let text = match node.terminal_kind() {
+            Some(TerminalKind::UintKeyword | TerminalKind::IntKeyword) => {
+                match (self.options.int_types, text) {
+                    (IntTypes::Long, "uint" | "int") => format!("{text}256"),
+                    (IntTypes::Short, "uint256" | "int256") => text.trim_end_matches("256").into(),
+                    _ => text.to_string(),
+                }
+            }
+            Some(
+                TerminalKind::DoubleQuotedStringLiteral
+                | TerminalKind::SingleQuotedStringLiteral
+                | TerminalKind::DoubleQuotedUnicodeStringLiteral
+                | TerminalKind::SingleQuotedUnicodeStringLiteral
+                | TerminalKind::DoubleQuotedHexStringLiteral
+                | TerminalKind::SingleQuotedHexStringLiteral,
+            ) => requote(text, self.options.quote_style),
+            _ => text.to_string(),
+        };
+        Doc::Text(text)
+    }
+
+    /// A comment, the lines of a block comment indented as they were relative to its start.
+    fn comment(&self, comment: &Comment) -> Doc {
+        let mut docs = Vec::new();
+        for (index, line) in comment.text.lines().enumerate() {
+            if index > 0 {
+                docs.push(Doc::HardLine);
+                let indent = line
+                    .char_indices()
+                    .take(comment.column)
+                    .find(|(_, c)| !c.is_whitespace())
+                    .map_or(line.len().min(comment.column), |(index, _)| index);
+                docs.push(Doc::text(line[indent..].trim_end()));
+            } else {
+                docs.push(Doc::text(line.trim_end()));
+            }
+        }
+        Doc::Concat(docs)
+    }
+}
+
+/// Whether Solidity code has a space between tokens `previous` and `next` in a `context` node.
+fn space(context: Option<NonterminalKind>, previous: TerminalKind, next: TerminalKind) -> bool {
+    use NonterminalKind as K;
+    use TerminalKind as T;
+
+    if matches!(previous, T::OpenParen | T::OpenBracket | T::Period)
+        || matches!(
+            next,
+            T::CloseParen | T::CloseBracket | T::Comma | T::Semicolon | T::Period | T::Colon
+        )
+        || (previous == T::OpenBrace && next == T::CloseBrace)
+    {
+        return false;
+    }
+    let Some(context) = context else {
+        return true;
+    };
+    match context {
+        // A call, or a declaration and its parameters.
+        K::FunctionCallExpression
+        | K::YulFunctionCallExpression
+        | K::EmitStatement
+        | K::RevertStatement
+        | K::ModifierInvocation
+        | K::InheritanceType
+        | K::OverrideSpecifier
+        | K::EventDefinition
+        | K::ErrorDefinition
+        | K::CatchClauseError
+        | K::FunctionType
+        | K::YulFunctionDefinition
+            if next == T::OpenParen =>
+        {
+            false
+        }
+        K::MappingType | K::TypeExpression if next == T::OpenParen => false,
+        K::CallOptionsExpression if next == T::OpenBrace => false,
+        K::IndexAccessExpression | K::ArrayTypeName if next == T::OpenBracket => false,
+        K::IndexAccessExpression | K::IndexAccessEnd => false,
+        K::PrefixExpression => previous == T::DeleteKeyword,
+        K::PostfixExpression => false,
+        K::VersionTerm => false,
+        _ => true,
+    }
+}
+
+/// Converts the quotes of a string literal, unless the string contains the other quote.
+fn requote(text: &str, style: QuoteStyle) -> String {
+    let (quote, other) = match style {
+        QuoteStyle::Double => ('"', '\''),
+        QuoteStyle::Single => ('\'', '"'),
+        QuoteStyle::Preserve => return text.to_string(),
+    };
+    let Some(start) = text.find(other) else {
+        return text.to_string();
+    };
+    if !text.ends_with(other) || text.len() < start + 2 {
+        return text.to_string();
+    }
+    let (prefix, body) = (&text[..start], &text[start + 1..text.len() - 1]);
+    if body.contains(quote) {
+        return text.to_string();
+    }
+    let body = body.replace(&format!("\\{other}"), &other.to_string());
+    format!("{prefix}{quote}{body}{quote}")
+}
+
+fn is_list(node: &SyntaxNode) -> bool {
+    node.nonterminal_kind()
+        .is_some_and(|kind| COMMA_LISTS.contains(&kind) || LINE_LISTS.contains(&kind))
+}
+
+fn is_open(node: &SyntaxNode) -> bool {
+    matches!(
+        node.terminal_kind(),
+        Some(TerminalKind::OpenParen | TerminalKind::OpenBracket | TerminalKind::OpenBrace)
+    )
+}
+
+fn is_close(node: &SyntaxNode) -> bool {
+    matches!(
+        node.terminal_kind(),
+        Some(TerminalKind::CloseParen | TerminalKind::CloseBracket | TerminalKind::CloseBrace)
+    )
+}
+
+/// Definitions that get an empty line around them.
+fn has_body(item: &SyntaxNode) -> bool {
+    use NonterminalKind as K;
+
+    let item = item.variant();
+    match item.nonterminal_kind() {
+        Some(
+            K::ContractDefinition
+            | K::InterfaceDefinition
+            | K::LibraryDefinition
+            | K::StructDefinition
+            | K::EnumDefinition
+            | K::ConstructorDefinition,
+        ) => true,
+        Some(
+            K::FunctionDefinition
+            | K::ModifierDefinition
+            | K::FallbackFunctionDefinition
+            | K::ReceiveFunctionDefinition
+            | K::UnnamedFunctionDefinition,
+        ) => item
+            .child(EdgeLabel::Body)
+            .is_some_and(|body| body.variant().is(K::Block)),
+        _ => false,
+    }
+}
+
+/// The first or last token of `node`.
+fn edge_token(node: &SyntaxNode, last: bool) -> Option<SyntaxNode> {
+    if node.node.is_terminal() {
+        return (!node.is_trivia()).then(|| node.clone());
+    }
+    let children: Vec<_> = node.children().collect();
+    match last {
+        true => children
+            .iter()
+            .rev()
+            .find_map(|child| edge_token(child, true)),
+        false => children.iter().find_map(|child| edge_token(child, false)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format, FormatOptions};
+    use crate::analysis::source::ParsedSource;
+
+    fn formatted(text: &str) -> String {
+        format(&ParsedSource::from_text(text), &FormatOptions::default()).unwrap()
+    }
+
+    /// Formats `text`, checking that formatting the result changes nothing.
+    fn stable(text: &str) -> String {
+        let once = formatted(text);
+        assert_eq!(
+            formatted(&once),
+            once,
+            "formatting again changed the result"
+        );
+        once
+    }
+
+    #[test]
+    fn comments_stay_with_their_tokens() {
+        let text = "contract C {
+    function f(uint256 a) public {
+        if (a > 1) {
+            a = 2;
+        } // after if
+        // before else
+        else {
+            a = 3;
+        }
+    }
+}
+";
+        assert_eq!(stable(text), text);
+        let text = "contract C {
+    function f(uint256 a) public {
+        if (a > 1) {
+            a = 2;
+        }
+        // before else
+        else {
+            a = 3;
+        }
+    }
+}
+";
+        assert_eq!(stable(text), text);
+    }
+
+    #[test]
+    fn comments_are_stable() {
+        let text = "// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.0; // the version
+
+/// @title A contract
+/* a block
+   comment */
+contract C is /* first */ A, B {
+    // a member
+    uint256 x; // the x
+
+    /// @notice Does things.
+    function f(
+        uint256 a, // the a
+        /* the b */ uint256 b
+    ) external returns (uint256) {
+        // nothing before
+        x = a /* plus */ + b;
+        return x; // done
+        // nothing after
+    }
+
+    enum E {
+        A, // first
+        B
+    }
+}
+
+// the end
+";
+        stable(text);
+    }
+
+    #[test]
+    fn line_endings_are_kept() {
+        let text = "contract C {\r\n    uint256 x; // x\r\n}\r\n";
+        assert_eq!(stable(text), text);
+        let text = "contract C {\r\n  uint x;\r\n}";
+        assert_eq!(stable(text), "contract C {\r\n    uint256 x;\r\n}\r\n");
+    }
+
+    #[test]
+    fn function_headers() {
+        // The attributes break first when the header is too long.
+        let text = format!(
+            "contract C {{
+    function {}(uint256 a, uint256 b) external view returns (uint256) {{}}
+}}
+",
+            "f".repeat(60)
+        );
+        let expected = format!(
+            "contract C {{
+    function {}(uint256 a, uint256 b)
+        external
+        view
+        returns (uint256)
+    {{}}
+}}
+",
+            "f".repeat(60)
+        );
+        assert_eq!(stable(&text), expected);
+        // A comment breaks the parameters, the attributes still fit after them.
+        let text = "contract C {
+    function g(
+        uint256 a // the a
+    ) external {}
+}
+";
+        assert_eq!(stable(text), text);
+    }
+
+    #[test]
+    fn statements() {
+        let text = "contract C {
+    function f(uint256 a) public returns (uint256) {
+        if (a == 1) return 1; else if (a == 2) return 0; else { a = 4; }
+        (bool ok, ) = address(this).call(\"\");
+        (, uint256 b, , uint256 c) = g();
+        return a;
+    }
+}
+";
+        let expected = "contract C {
+    function f(uint256 a) public returns (uint256) {
+        if (a == 1) return 1;
+        else if (a == 2) return 0;
+        else {
+            a = 4;
+        }
+        (bool ok,) = address(this).call(\"\");
+        (, uint256 b,, uint256 c) = g();
+        return a;
+    }
+}
+";
+        assert_eq!(stable(text), expected);
+    }
+
+    #[test]
+    fn yul_switch_cases() {
+        let text = "contract C {
+    function f(uint256 x) public {
+        assembly {
+            switch x case 1 { x := 2 } default { x := 3 }
+        }
+    }
+}
+";
+        let expected = "contract C {
+    function f(uint256 x) public {
+        assembly {
+            switch x
+            case 1 {
+                x := 2
+            }
+            default {
+                x := 3
+            }
+        }
+    }
+}
+";
+        assert_eq!(stable(text), expected);
+    }
+}
@@ -0,0 +1,571 @@
+use crate::{
+    analysis::{source::SourceFile, Analysis},
+    document::{DocumentStore, PositionEncoding},
+    features::symbols,
+    project::Project,
+};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use serde_json::Value;
+use std::{
+    collections::{BTreeSet, HashMap},
+    path::{Path, PathBuf},
+    sync::{mpsc, Arc, RwLock},
+    thread,
+};
+use tower_lsp::lsp_types::{Location, SymbolInformation, SymbolKind};
+use walkdir::WalkDir;
+
+/// Most symbols returned for a query, the best matches first.
+const MAX_RESULTS: usize = 256;
+
+/// Which dependencies are indexed on top of the sources of the workspace.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexOptions {
+    /// The `libs` directories of Foundry projects, `lib/` by default.
+    pub libraries: bool,
+    pub node_modules: bool,
+}
+
+impl Default for IndexOptions {
+    fn default() -> Self {
+        Self {
+            libraries: true,
+            node_modules: true,
+        }
+    }
+}
+
+impl IndexOptions {
+    /// Reads `{"index": {"libraries": bool, "nodeModules": bool}}` from the initialization options.
+    pub fn from_initialization_options(options: Option<&Value>) -> Self {
+        let default = Self::default();
+        let flag = |pointer: &str, default: bool| {
+            options
+                .and_then(|options| options.pointer(pointer))
+                .and_then(Value::as_bool)
+                .unwrap_or(default)
+        };
+        Self {
+            libraries: flag("/index/libraries", default.libraries),
+            node_modules: flag("/index/nodeModules", default.node_modules),
+        }
+    }
+}
+
+/// The Solidity files under `folder`, skipping hidden directories and the dependencies that
+/// `options` leaves out.
+pub fn solidity_files(folder: &Path, options: &IndexOptions) -> Vec<PathBuf> {
+    let libraries = excluded_libraries(folder, options);
+    walk(folder, options, &libraries)
+}
+
+/// The Solidity files under `dir`, skipping the excluded directories below it.
+fn walk(dir: &Path, options: &IndexOptions, libraries: &[PathBuf]) -> Vec<PathBuf> {
+    WalkDir::new(dir)
+        .into_iter()
+        .filter_entry(|entry| entry.depth() == 0 || !is_excluded(entry.path(), options, libraries))
+        .flatten()
+        .map(|entry| entry.into_path())
+        .filter(|path| is_solidity(path))
+        .collect()
+}
+
+fn is_solidity(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "sol")
+}
+
+/// The library directories of the project at `folder` when `options` leaves them out.
+fn excluded_libraries(folder: &Path, options: &IndexOptions) -> Vec<PathBuf> {
+    if options.libraries {
+        return Vec::new();
+    }
+    match Project::find(&folder.join("foundry.toml")) {
+        Some(project) => project
+            .libs
+            .iter()
+            .map(|lib| project.root.join(lib))
+            .collect(),
+        None => vec![folder.join("lib")],
+    }
+}
+
+fn is_excluded(path: &Path, options: &IndexOptions, libraries: &[PathBuf]) -> bool {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    name.starts_with('.')
+        || (!options.node_modules && name == "node_modules")
+        || libraries.iter().any(|library| library == path)
+}
+
+/// A declaration found by workspace symbol search.
+#[derive(Debug, Clone)]
+pub struct IndexedSymbol {
+    pub name: String,
+    /// Name of the contract, struct or enum it belongs to.
+    pub container: Option<String>,
+    pub kind: SymbolKind,
+    pub location: Location,
+}
+
+impl IndexedSymbol {
+    fn of(file: &SourceFile) -> Vec<Self> {
+        let declarations = &file.model.declarations;
+        declarations
+            .iter()
+            .filter_map(|decl| {
+                let kind = symbols::symbol_kind(decl)?;
+                Some(Self {
+                    name: decl.name.clone(),
+                    container: decl
+                        .container
+                        .map(|container| declarations[container].name.clone()),
+                    kind,
+                    location: file.location(decl.name_span)?,
+                })
+            })
+            .collect()
+    }
+
+    /// `Container.name`, or just the name at the top level.
+    fn qualified_name(&self) -> String {
+        match &self.container {
+            Some(container) => format!("{container}.{}", self.name),
+            None => self.name.clone(),
+        }
+    }
+}
+
+/// The symbols declared by every Solidity file of the workspace.
+#[derive(Debug, Default)]
+pub struct SymbolIndex {
+    files: HashMap<PathBuf, Vec<IndexedSymbol>>,
+}
+
+impl SymbolIndex {
+    /// Symbols fuzzily matching `query`, which matches the qualified name when it contains a `.`
+    /// like `IERC20.transferFrom`.
+    pub fn search(&self, query: &str) -> Vec<SymbolInformation> {
+        let mut matches: Vec<_> = self
+            .files
+            .values()
+            .flatten()
+            .filter_map(|symbol| {
+                let candidate = match query.contains('.') {
+                    true => symbol.qualified_name(),
+                    false => symbol.name.clone(),
+                };
+                Some((fuzzy_score(query, &candidate)?, symbol))
+            })
+            .collect();
+        matches.sort_by(|(a, a_symbol), (b, b_symbol)| {
+            b.cmp(a).then_with(|| a_symbol.name.cmp(&b_symbol.name))
+        });
+        matches
+            .into_iter()
+            .take(MAX_RESULTS)
+            .map(|(_, symbol)| {
+                #[allow(deprecated)]
+                SymbolInformation {
+                    name: symbol.name.clone(),
+                    kind: symbol.kind,
+                    tags: None,
+                    deprecated: None,
+                    location: symbol.location.clone(),
+                    container_name: symbol.container.clone(),
+                }
+            })
+            .collect()
+    }
+}
+
+/// How well `candidate` matches `query`, whose characters must all appear in order, ignoring case.
+///
+/// Consecutive characters and the starts of words score higher, an exact match highest.
+fn fuzzy_score(query: &str, candidate: &str) -> Option<u32> {
+    if query.is_empty() {
+        return Some(0);
+    }
+    if query.eq_ignore_ascii_case(candidate) {
+        return Some(u32::MAX);
+    }
+    let candidate: Vec<char> = candidate.chars().collect();
+    let mut score = 0;
+    let mut next = 0;
+    let mut previous: Option<usize> = None;
+    for q in query.chars() {
+        let found = (next..candidate.len()).find(|&i| candidate[i].eq_ignore_ascii_case(&q))?;
+        let word_start = found == 0
+            || candidate[found].is_ascii_uppercase() && !candidate[found - 1].is_ascii_uppercase()
+            || matches!(candidate[found - 1], '_' | '.' | '$');
+        score += 1;
+        if previous.is_some_and(|previous| previous + 1 == found) {
+            score += 4;
+        }
+        if word_start {
+            score += 8;
+        }
+        if found == 0 {
+            score += 16;
+        }
+        previous = Some(found);
+        next = found + 1;
+    }
+    Some(score)
+}
+
+enum IndexTask {
+    /// Reindexes everything, with the encoding of the positions the client negotiated.
+    Rebuild {
+        folders: Vec<PathBuf>,
+        options: IndexOptions,
+        encoding: PositionEncoding,
+    },
+    ChangeFolders {
+        added: Vec<PathBuf>,
+        removed: Vec<PathBuf>,
+    },
+    /// The files changed, were created or deleted, on disk or in the editor.
+    Changed(Vec<PathBuf>),
+}
+
+/// Handle to the indexing thread, which keeps a [`SymbolIndex`] of the workspace up to date.
+///
+/// Indexing has its own thread and [`Analysis`] so that it never delays requests, and watches
+/// the workspace folders to reindex files changed outside of the editor.
+#[derive(Debug, Clone)]
+pub struct IndexHandle {
+    index: Arc<RwLock<SymbolIndex>>,
+    sender: mpsc::Sender<IndexTask>,
+}
+
+impl IndexHandle {
+    pub fn spawn(documents: Arc<DocumentStore>) -> Self {
+        let index = Arc::new(RwLock::new(SymbolIndex::default()));
+        let (sender, receiver) = mpsc::channel();
+        let shared = Arc::clone(&index);
+        let watcher_sender = sender.clone();
+        thread::Builder::new()
+            .name("slap-index".to_string())
+            .spawn(move || {
+                let mut indexer = Indexer {
+                    index: shared,
+                    analysis: Analysis::new(documents),
+                    sender: watcher_sender,
+                    watcher: None,
+                    folders: Vec::new(),
+                    options: IndexOptions::default(),
+                    libraries: Vec::new(),
+                };
+                indexer.run(receiver)
+            })
+            .expect("failed to spawn the index thread");
+        Self { index, sender }
+    }
+
+    pub fn rebuild(
+        &self,
+        folders: Vec<PathBuf>,
+        options: IndexOptions,
+        encoding: PositionEncoding,
+    ) {
+        self.send(IndexTask::Rebuild {
+            folders,
+            options,
+            encoding,
+        });
+    }
+
+    pub fn change_folders(&self, added: Vec<PathBuf>, removed: Vec<PathBuf>) {
+        self.send(IndexTask::ChangeFolders { added, removed });
+    }
+
+    pub fn changed(&self, paths: Vec<PathBuf>) {
+        self.send(IndexTask::Changed(paths));
+    }
+
+    pub fn search(&self, query: &str) -> Vec<SymbolInformation> {
+        self.index.read().unwrap().search(query)
+    }
+
+    fn send(&self, task: IndexTask) {
+        if self.sender.send(task).is_err() {
+            log::error!("index thread is gone");
+        }
+    }
+}
+
+struct Indexer {
+    index: Arc<RwLock<SymbolIndex>>,
+    analysis: Analysis,
+    /// To send the changes the watcher reports to this thread.
+    sender: mpsc::Sender<IndexTask>,
+    watcher: Option<RecommendedWatcher>,
+    folders: Vec<PathBuf>,
+    options: IndexOptions,
+    libraries: Vec<PathBuf>,
+}
+
+impl Indexer {
+    fn run(&mut self, receiver: mpsc::Receiver<IndexTask>) {
+        while let Ok(task) = receiver.recv() {
+            // Coalesces the bursts of changes, like a branch checkout or fast typing.
+            let mut changed = BTreeSet::new();
+            let mut next = Some(task);
+            while let Some(task) = next {
+                match task {
+                    IndexTask::Rebuild {
+                        folders,
+                        options,
+                        encoding,
+                    } => {
+                        changed.clear();
+                        self.analysis.set_position_encoding(encoding);
+                        self.options = options;
+                        self.folders = folders;
+                        self.rebuild();
+                    }
+                    IndexTask::ChangeFolders { added, removed } => {
+                        changed.clear();
+                        self.folders.retain(|folder| !removed.contains(folder));
+                        self.folders.extend(added);
+                        self.rebuild();
+                    }
+                    IndexTask::Changed(paths) => changed.extend(paths),
+                }
+                next = receiver.try_recv().ok();
+            }
+            self.changed(changed);
+        }
+    }
+
+    fn changed(&mut self, changed: BTreeSet<PathBuf>) {
+        // The `libs` of a Foundry project decide what's excluded from all of it.
+        let manifest = changed
+            .iter()
+            .any(|path| path.file_name().is_some_and(|name| name == "foundry.toml"));
+        if manifest && self.libraries != self.excluded_libraries() {
+            self.reindex();
+            return;
+        }
+        for path in changed {
+            self.update(&path);
+        }
+    }
+
+    fn rebuild(&mut self) {
+        self.watch();
+        self.reindex();
+    }
+
+    fn excluded_libraries(&self) -> Vec<PathBuf> {
+        self.folders
+            .iter()
+            .flat_map(|folder| excluded_libraries(folder, &self.options))
+            .collect()
+    }
+
+    /// Indexes every file of the workspace folders again.
+    fn reindex(&mut self) {
+        self.libraries = self.excluded_libraries();
+        let paths: Vec<_> = self
+            .folders
+            .iter()
+            .flat_map(|folder| solidity_files(folder, &self.options))
+            .collect();
+        let mut files = HashMap::new();
+        for path in paths {
+            if let Some(file) = self.analysis.file_uncached(&path) {
+                files.insert(path, IndexedSymbol::of(&file));
+            }
+        }
+        log::info!("indexed {} files", files.len());
+        self.index.write().unwrap().files = files;
+    }
+
+    fn watch(&mut self) {
+        let sender = self.sender.clone();
+        let watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            match event {
+                // Reading the files to index them is an access too.
+                Ok(event) if event.kind.is_access() => {}
+                Ok(event) => {
+                    let _ = sender.send(IndexTask::Changed(event.paths));
+                }
+                Err(err) => log::error!("file watcher: {err}"),
+            }
+        });
+        let mut watcher = match watcher {
+            Ok(watcher) => watcher,
+            Err(err) => {
+                log::error!("failed to create the file watcher: {err}");
+                return;
+            }
+        };
+        for folder in &self.folders {
+            if let Err(err) = watcher.watch(folder, RecursiveMode::Recursive) {
+                log::error!("failed to watch {}: {err}", folder.display());
+            }
+        }
+        self.watcher = Some(watcher);
+    }
+
+    /// Reindexes the file or directory at `path`, or forgets it if it's gone.
+    fn update(&mut self, path: &Path) {
+        let included = self.folders.iter().any(|folder| {
+            path.starts_with(folder)
+                && !path
+                    .ancestors()
+                    .take_while(|dir| dir != folder)
+                    .any(|dir| is_excluded(dir, &self.options, &self.libraries))
+        });
+
+        let mut index = self.index.write().unwrap();
+        index.files.retain(|file, _| !file.starts_with(path));
+        if !included {
+            return;
+        }
+        drop(index);
+
+        let paths = match path.is_dir() {
+            true => walk(path, &self.options, &self.libraries),
+            false if is_solidity(path) && path.is_file() => vec![path.to_path_buf()],
+            false => Vec::new(),
+        };
+        for path in paths {
+            if let Some(file) = self.analysis.file_uncached(&path) {
+                let symbols = IndexedSymbol::of(&file);
+                self.index.write().unwrap().files.insert(path, symbols);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fuzzy_score, solidity_files, IndexOptions, Indexer, SymbolIndex};
+    use crate::{analysis::Analysis, document::DocumentStore};
+    use std::{
+        collections::BTreeSet,
+        fs,
+        path::{Path, PathBuf},
+        sync::{mpsc, Arc, RwLock},
+    };
+
+    const NO_DEPENDENCIES: IndexOptions = IndexOptions {
+        libraries: false,
+        node_modules: false,
+    };
+
+    /// A folder in the temporary directory, removed when dropped.
+    struct Folder(PathBuf);
+
+    impl Folder {
+        fn new(name: &str, files: &[&str]) -> Self {
+            let root = std::env::temp_dir().join(format!("slap-{name}-{}", std::process::id()));
+            let _ = fs::remove_dir_all(&root);
+            let folder = Self(root);
+            for path in files {
+                folder.write(path, "contract C {}");
+            }
+            folder
+        }
+
+        fn write(&self, path: &str, text: &str) {
+            let path = self.0.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, text).unwrap();
+        }
+
+        /// `paths` relative to the folder, sorted.
+        fn relative<'a>(&self, paths: impl IntoIterator<Item = &'a PathBuf>) -> Vec<String> {
+            let mut paths: Vec<_> = paths
+                .into_iter()
+                .map(|path| path.strip_prefix(&self.0).unwrap().display().to_string())
+                .collect();
+            paths.sort();
+            paths
+        }
+    }
+
+    impl Drop for Folder {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn indexer(folder: &Path, options: IndexOptions) -> Indexer {
+        let mut indexer = Indexer {
+            index: Arc::new(RwLock::new(SymbolIndex::default())),
+            analysis: Analysis::new(Arc::new(DocumentStore::default())),
+            sender: mpsc::channel().0,
+            watcher: None,
+            folders: vec![folder.to_path_buf()],
+            options,
+            libraries: Vec::new(),
+        };
+        indexer.reindex();
+        indexer
+    }
+
+    fn indexed(folder: &Folder, indexer: &Indexer) -> Vec<String> {
+        folder.relative(indexer.index.read().unwrap().files.keys())
+    }
+
+    #[test]
+    fn excluded_directories() {
+        let folder = Folder::new(
+            "excluded",
+            &[
+                "src/A.sol",
+                "lib/B.sol",
+                "deps/C.sol",
+                "node_modules/D.sol",
+                ".git/E.sol",
+            ],
+        );
+        let all = solidity_files(&folder.0, &IndexOptions::default());
+        assert_eq!(
+            folder.relative(&all),
+            ["deps/C.sol", "lib/B.sol", "node_modules/D.sol", "src/A.sol"]
+        );
+        let sources = solidity_files(&folder.0, &NO_DEPENDENCIES);
+        assert_eq!(folder.relative(&sources), ["deps/C.sol", "src/A.sol"]);
+
+        folder.write("foundry.toml", "[profile.default]\nlibs = [\"deps\"]\n");
+        let sources = solidity_files(&folder.0, &NO_DEPENDENCIES);
+        assert_eq!(folder.relative(&sources), ["lib/B.sol", "src/A.sol"]);
+    }
+
+    #[test]
+    fn new_directories_skip_the_excluded_ones() {
+        let folder = Folder::new("new-directories", &["A.sol"]);
+        let mut indexer = indexer(&folder.0, NO_DEPENDENCIES);
+        for path in ["pkg/B.sol", "pkg/node_modules/C.sol", "pkg/.cache/D.sol"] {
+            folder.write(path, "contract C {}");
+        }
+        indexer.changed(BTreeSet::from([folder.0.join("pkg")]));
+        assert_eq!(indexed(&folder, &indexer), ["A.sol", "pkg/B.sol"]);
+
+        folder.write("lib/E.sol", "contract C {}");
+        indexer.changed(BTreeSet::from([folder.0.join("lib")]));
+        assert_eq!(indexed(&folder, &indexer), ["A.sol", "pkg/B.sol"]);
+    }
+
+    #[test]
+    fn libraries_follow_the_manifest() {
+        let folder = Folder::new("manifest", &["lib/A.sol", "deps/B.sol"]);
+        let mut indexer = indexer(&folder.0, NO_DEPENDENCIES);
+        assert_eq!(indexed(&folder, &indexer), ["deps/B.sol"]);
+
+        folder.write("foundry.toml", "[profile.default]\nlibs = [\"deps\"]\n");
+        indexer.changed(BTreeSet::from([folder.0.join("foundry.toml")]));
+        assert_eq!(indexed(&folder, &indexer), ["lib/A.sol"]);
+    }
+
+    #[test]
+    fn fuzzy_matches() {
+        assert_eq!(fuzzy_score("transfer", "Transfer"), Some(u32::MAX));
+        assert_eq!(fuzzy_score("tf", "balanceOf"), None);
+        let word_starts = fuzzy_score("tF", "transferFrom").unwrap();
+        assert!(word_starts > fuzzy_score("tr", "stream").unwrap());
+    }
+}
@@ -1,80 +1,412 @@
 use crate::{
+    analysis::{source::ParsedSource, Analysis, AnalysisHandle},
     cli::Transport,
-    detectors::{Detectors, LspMessage},
+    detectors::{Category, Confidence, Detectors, LspMessage, Metadata},
+    document::{DocumentStore, PositionEncoding},
+    features::{
+        code_actions, completion, definition, formatting, hover, on_type_formatting, ranges,
+        references, rename, semantic_tokens, signature_help, symbols,
+    },
+    index::{IndexHandle, IndexOptions},
 };
 use serde_json::Value;
-use std::{path::PathBuf, str::FromStr};
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    rc::Rc,
+    sync::{Arc, OnceLock, RwLock},
+};
 use tokio::net::{TcpListener, TcpStream, UnixListener};
-use tower_lsp::jsonrpc::Result;
+use tower_lsp::jsonrpc::{self, ErrorCode, Result};
 use tower_lsp::lsp_types::*;
 use tower_lsp::{Client, LanguageServer, LspService, Server};
 
-#[derive(Debug)]
-pub enum LspError {
-    //
-}
-
 #[derive(Debug)]
 pub struct Backend {
     client: Client,
-    detectors: Detectors,
+    detectors: Arc<Detectors>,
+    documents: Arc<DocumentStore>,
+    analysis: AnalysisHandle,
+    index: IndexHandle,
+    position_encoding: OnceLock<PositionEncoding>,
+    /// Whether the client answers `workspace/configuration`, and registers for
+    /// `workspace/didChangeConfiguration`.
+    configuration_support: OnceLock<(bool, bool)>,
+    /// Whether the client registers for `workspace/didChangeWatchedFiles`.
+    watch_support: OnceLock<bool>,
+    /// Published for each file, which code actions are built from.
+    diagnostics: RwLock<HashMap<Url, Vec<Diagnostic>>>,
 }
 
 impl Backend {
     pub fn new(client: Client, detectors: Detectors) -> Self {
-        Self { client, detectors }
-    }
-
-    async fn update_lsp(&self, uri: Url) {
-        let uri_string = uri.to_string();
-        let (prefix, suffix) = uri_string.split_at(7);
-        let path = PathBuf::from_str(suffix).unwrap();
-        if prefix == "file://" && path.extension().is_some_and(|ext| ext == "sol") {
-            if let Ok(content) = std::fs::read_to_string(&path) {
-                let diags = self.detectors.run(&path, content).await;
-                let diags = diags
-                    .into_iter()
-                    .filter_map(|diag| match diag {
-                        LspMessage::Diagnostics { path: _, diags } => Some(diags),
-                        _ => unimplemented!(),
-                    })
-                    .flatten()
-                    .collect();
-                self.client
-                    .publish_diagnostics(uri.clone(), diags, None)
+        let documents = Arc::new(DocumentStore::default());
+        Self {
+            client,
+            detectors: Arc::new(detectors),
+            analysis: AnalysisHandle::spawn(Arc::clone(&documents)),
+            index: IndexHandle::spawn(Arc::clone(&documents)),
+            documents,
+            position_encoding: OnceLock::new(),
+            configuration_support: OnceLock::new(),
+            watch_support: OnceLock::new(),
+            diagnostics: RwLock::default(),
+        }
+    }
+
+    fn position_encoding(&self) -> PositionEncoding {
+        self.position_encoding.get().copied().unwrap_or_default()
+    }
+
+    /// Runs `f` on the analysis thread with the parse of the buffer content of `uri`, or of the file
+    /// on disk if it isn't open. `None` for anything but a readable Solidity file, and an internal
+    /// error if `f` panicked.
+    async fn with_source<R, F>(&self, uri: &Url, f: F) -> Result<Option<R>>
+    where
+        F: FnOnce(&mut Analysis, Rc<ParsedSource>) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let Ok(path) = uri.to_file_path() else {
+            return Ok(None);
+        };
+        if path.extension().is_none_or(|ext| ext != "sol") {
+            return Ok(None);
+        }
+
+        let (version, content) = match self.documents.get(uri) {
+            Some(document) => (Some(document.version), document.text),
+            None => match std::fs::read_to_string(&path) {
+                Ok(content) => (None, content),
+                Err(err) => {
+                    log::error!("failed to read {}: {err}", path.display());
+                    return Ok(None);
+                }
+            },
+        };
+
+        let uri = uri.clone();
+        let result = self
+            .analysis
+            .with(move |analysis| {
+                let source = analysis.parse(&uri, path, version, content);
+                f(analysis, source)
+            })
+            .await;
+        result.map(Some).ok_or_else(analysis_failed)
+    }
+
+    /// Asks the client for its `slap` settings, and applies them.
+    async fn pull_settings(&self) {
+        let item = ConfigurationItem {
+            scope_uri: None,
+            section: Some(SETTINGS_SECTION.to_string()),
+        };
+        match self.client.configuration(vec![item]).await {
+            Ok(mut settings) => {
+                self.apply_settings(settings.pop().unwrap_or_default())
                     .await
             }
+            Err(err) => log::warn!("failed to get the {SETTINGS_SECTION} settings: {err}"),
         }
     }
+
+    /// Applies the `slap` settings of the client over the configuration files, which take the
+    /// same keys, and runs the detectors again if they changed.
+    async fn apply_settings(&self, settings: Value) {
+        let settings = match settings {
+            Value::Null => toml::Table::new(),
+            settings => match serde_json::from_value(without_nulls(settings)) {
+                Ok(settings) => settings,
+                Err(err) => {
+                    log::error!("invalid {SETTINGS_SECTION} settings: {err}");
+                    return;
+                }
+            },
+        };
+        let changed = self
+            .analysis
+            .with(move |analysis| analysis.set_settings(settings))
+            .await;
+        if changed == Some(true) {
+            self.update_all().await;
+        }
+    }
+
+    /// Runs the detectors again on every open document, after their configuration changed.
+    async fn update_all(&self) {
+        for uri in self.documents.uris() {
+            self.update_lsp(uri, false).await;
+        }
+    }
+
+    /// Runs the detectors on the buffer content of `uri`, or on the file on disk if it isn't open.
+    /// The ones that run on save only run when it was `saved`.
+    async fn update_lsp(&self, uri: Url, saved: bool) {
+        let detectors = Arc::clone(&self.detectors);
+        let previous = self
+            .diagnostics
+            .read()
+            .unwrap()
+            .get(&uri)
+            .cloned()
+            .unwrap_or_default();
+        let Ok(Some((path, version, parse_diags, messages))) = self
+            .with_source(&uri, move |analysis, source| {
+                let config = analysis.config(&source.path);
+                (
+                    source.path.clone(),
+                    source.version,
+                    source.error_diagnostics(),
+                    detectors.run(&source, config, saved, &previous),
+                )
+            })
+            .await
+        else {
+            return;
+        };
+        let messages = messages.await;
+        let diags = messages
+            .into_iter()
+            .filter_map(|message| match message {
+                LspMessage::Diagnostics {
+                    path: diags_path,
+                    diags,
+                } if diags_path == path => Some(diags),
+                LspMessage::Diagnostics { .. } => None,
+                LspMessage::Error => None,
+            })
+            .flatten();
+        let diags: Vec<_> = parse_diags.into_iter().chain(diags).collect();
+        {
+            // The document may have changed or closed while the detectors ran, then these
+            // diagnostics are stale.
+            let mut diagnostics = self.diagnostics.write().unwrap();
+            let current = self.documents.version(&uri);
+            if current.is_none() || current != version {
+                log::debug!("dropping the diagnostics of {uri} at version {version:?}");
+                return;
+            }
+            diagnostics.insert(uri.clone(), diags.clone());
+        }
+        self.client.publish_diagnostics(uri, diags, version).await
+    }
+
+    /// `slap/rules`: the metadata of the detectors, only the ones of `category` or at least as
+    /// confident as `minConfidence` when the params have them. With a `uri`, the rules the
+    /// configuration of that file declares come along.
+    async fn rules(&self, params: Value) -> Result<Vec<Value>> {
+        log::debug!("rules");
+
+        let filter = |key: &str| params.get(key).and_then(Value::as_str);
+        let category = match filter("category") {
+            Some(category) => match Category::parse(category) {
+                Some(category) => Some(category),
+                None => {
+                    let message = format!("unknown category {category:?}");
+                    return Err(jsonrpc::Error::invalid_params(message));
+                }
+            },
+            None => None,
+        };
+        let confidence = match filter("minConfidence") {
+            Some(confidence) => match Confidence::parse(confidence) {
+                Some(confidence) => Some(confidence),
+                None => {
+                    let message = format!("unknown confidence {confidence:?}");
+                    return Err(jsonrpc::Error::invalid_params(message));
+                }
+            },
+            None => None,
+        };
+        let path = match filter("uri").map(Url::parse) {
+            Some(Ok(uri)) => uri.to_file_path().ok(),
+            Some(Err(err)) => return Err(jsonrpc::Error::invalid_params(err.to_string())),
+            None => None,
+        };
+        let declared = match path {
+            Some(path) => self
+                .analysis
+                .with(move |analysis| {
+                    let detectors = &analysis.config(&path).detectors;
+                    let declared = detectors.iter().map(|detector| detector.metadata.clone());
+                    declared.collect()
+                })
+                .await
+                .ok_or_else(analysis_failed)?,
+            None => Vec::new(),
+        };
+        let rules = self
+            .detectors
+            .rules()
+            .chain(&declared)
+            .filter(|rule| category.is_none_or(|category| rule.category == category))
+            .filter(|rule| confidence.is_none_or(|confidence| rule.confidence >= confidence))
+            .map(Metadata::to_json)
+            .collect();
+        Ok(rules)
+    }
+}
+
+/// Files whose changes reload the projects and the detector configuration.
+const CONFIG_FILES: [&str; 2] = ["foundry.toml", "slap.toml"];
+
+/// Section of the client settings configuring the detectors.
+const SETTINGS_SECTION: &str = "slap";
+
+/// `value` without the `null`s TOML has no equivalent for, which the client sends for unset
+/// settings.
+fn without_nulls(value: Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .filter(|(_, value)| !value.is_null())
+                .map(|(key, value)| (key, without_nulls(value)))
+                .collect(),
+        ),
+        Value::Array(values) => Value::Array(
+            values
+                .into_iter()
+                .filter(|value| !value.is_null())
+                .map(without_nulls)
+                .collect(),
+        ),
+        value => value,
+    }
+}
+
+/// For requests whose analysis panicked.
+fn analysis_failed() -> jsonrpc::Error {
+    jsonrpc::Error {
+        code: ErrorCode::InternalError,
+        message: "the analysis of the request failed".into(),
+        data: None,
+    }
+}
+
+/// The `RequestFailed` error of LSP 3.17, for requests that were valid but couldn't be carried out.
+fn request_failed(message: String) -> jsonrpc::Error {
+    jsonrpc::Error {
+        code: ErrorCode::ServerError(-32803),
+        message: message.into(),
+        data: None,
+    }
 }
 
 #[tower_lsp::async_trait]
 impl LanguageServer for Backend {
-    async fn initialize(&self, _: InitializeParams) -> Result<InitializeResult> {
+    async fn initialize(&self, params: InitializeParams) -> Result<InitializeResult> {
         log::info!("initialize");
 
+        let position_encoding = PositionEncoding::negotiate(&params.capabilities);
+        let _ = self.position_encoding.set(position_encoding);
+        let workspace = params.capabilities.workspace.as_ref();
+        let pull = workspace.and_then(|workspace| workspace.configuration);
+        let register = workspace
+            .and_then(|workspace| workspace.did_change_configuration.as_ref())
+            .and_then(|capability| capability.dynamic_registration);
+        let _ = self
+            .configuration_support
+            .set((pull.unwrap_or(false), register.unwrap_or(false)));
+        let watch = workspace
+            .and_then(|workspace| workspace.did_change_watched_files.as_ref())
+            .and_then(|capability| capability.dynamic_registration);
+        let _ = self.watch_support.set(watch.unwrap_or(false));
+        #[allow(deprecated)]
+        let folders: Vec<PathBuf> = match (params.workspace_folders, params.root_uri) {
+            (Some(folders), _) => folders
+                .iter()
+                .filter_map(|folder| folder.uri.to_file_path().ok())
+                .collect(),
+            (None, Some(root)) => root.to_file_path().into_iter().collect(),
+            (None, None) => Vec::new(),
+        };
+        let index_options =
+            IndexOptions::from_initialization_options(params.initialization_options.as_ref());
+        self.index
+            .rebuild(folders.clone(), index_options, position_encoding);
+        self.analysis
+            .with(move |analysis| {
+                analysis.set_position_encoding(position_encoding);
+                analysis.set_workspace_folders(folders);
+            })
+            .await
+            .ok_or_else(analysis_failed)?;
+
         Ok(InitializeResult {
             server_info: None,
             capabilities: ServerCapabilities {
-                text_document_sync: Some(TextDocumentSyncCapability::Kind(
-                    TextDocumentSyncKind::INCREMENTAL,
+                position_encoding: Some(position_encoding.kind()),
+                // Saves run the detectors that don't run on every change.
+                text_document_sync: Some(TextDocumentSyncCapability::Options(
+                    TextDocumentSyncOptions {
+                        open_close: Some(true),
+                        change: Some(TextDocumentSyncKind::INCREMENTAL),
+                        save: Some(TextDocumentSyncSaveOptions::Supported(true)),
+                        ..Default::default()
+                    },
                 )),
                 completion_provider: Some(CompletionOptions {
                     resolve_provider: Some(false),
-                    trigger_characters: Some(vec![".".to_string()]),
+                    trigger_characters: Some(
+                        [".", "@", "\"", "'", "/"]
+                            .into_iter()
+                            .map(str::to_string)
+                            .collect(),
+                    ),
                     work_done_progress_options: Default::default(),
                     all_commit_characters: None,
                     ..Default::default()
                 }),
+                signature_help_provider: Some(SignatureHelpOptions {
+                    trigger_characters: Some(vec!["(".to_string(), ",".to_string()]),
+                    retrigger_characters: None,
+                    work_done_progress_options: Default::default(),
+                }),
                 execute_command_provider: Some(ExecuteCommandOptions {
-                    commands: vec![
-                        "dummy.do_something".to_string(),
-                        "format.execute".to_string(),
-                        "linter.some_lint.execute".to_string(),
-                        "linter.ai_sec.execute".to_string(),
-                    ],
+                    // The ones `execute_command` handles.
+                    commands: vec!["format.execute".to_string()],
                     work_done_progress_options: Default::default(),
                 }),
+                hover_provider: Some(HoverProviderCapability::Simple(true)),
+                definition_provider: Some(OneOf::Left(true)),
+                references_provider: Some(OneOf::Left(true)),
+                document_highlight_provider: Some(OneOf::Left(true)),
+                document_symbol_provider: Some(OneOf::Left(true)),
+                workspace_symbol_provider: Some(OneOf::Left(true)),
+                rename_provider: Some(OneOf::Right(RenameOptions {
+                    prepare_provider: Some(true),
+                    work_done_progress_options: Default::default(),
+                })),
+                code_action_provider: Some(CodeActionProviderCapability::Options(
+                    CodeActionOptions {
+                        code_action_kinds: Some(vec![CodeActionKind::QUICKFIX]),
+                        work_done_progress_options: Default::default(),
+                        resolve_provider: None,
+                    },
+                )),
+                document_formatting_provider: Some(OneOf::Left(true)),
+                document_range_formatting_provider: Some(OneOf::Left(true)),
+                document_on_type_formatting_provider: Some(DocumentOnTypeFormattingOptions {
+                    first_trigger_character: on_type_formatting::TRIGGER_CHARACTERS[0].to_string(),
+                    more_trigger_character: Some(
+                        on_type_formatting::TRIGGER_CHARACTERS[1..]
+                            .iter()
+                            .map(|c| c.to_string())
+                            .collect(),
+                    ),
+                }),
+                folding_range_provider: Some(FoldingRangeProviderCapability::Simple(true)),
+                selection_range_provider: Some(SelectionRangeProviderCapability::Simple(true)),
+                semantic_tokens_provider: Some(
+                    SemanticTokensServerCapabilities::SemanticTokensOptions(
+                        SemanticTokensOptions {
+                            work_done_progress_options: Default::default(),
+                            legend: semantic_tokens::legend(),
+                            range: Some(true),
+                            full: Some(SemanticTokensFullOptions::Bool(true)),
+                        },
+                    ),
+                ),
                 workspace: Some(WorkspaceServerCapabilities {
                     workspace_folders: Some(WorkspaceFoldersServerCapabilities {
                         supported: Some(true),
@@ -90,6 +422,45 @@ impl LanguageServer for Backend {
     async fn initialized(&self, _: InitializedParams) {
         log::debug!("initialized");
 
+        // The configuration is reloaded when these change.
+        if self.watch_support.get().copied().unwrap_or_default() {
+            let watchers = CONFIG_FILES
+                .iter()
+                .map(|name| FileSystemWatcher {
+                    glob_pattern: GlobPattern::String(format!("**/{name}")),
+                    kind: None,
+                })
+                .collect();
+            let options = DidChangeWatchedFilesRegistrationOptions { watchers };
+            let registration = Registration {
+                id: "slap-config-files".to_string(),
+                method: "workspace/didChangeWatchedFiles".to_string(),
+                register_options: serde_json::to_value(options).ok(),
+            };
+            if let Err(err) = self.client.register_capability(vec![registration]).await {
+                log::warn!("failed to watch the configuration files: {err}");
+            }
+        }
+
+        let (pull, register) = self
+            .configuration_support
+            .get()
+            .copied()
+            .unwrap_or_default();
+        if register {
+            let registration = Registration {
+                id: "slap-settings".to_string(),
+                method: "workspace/didChangeConfiguration".to_string(),
+                register_options: None,
+            };
+            if let Err(err) = self.client.register_capability(vec![registration]).await {
+                log::warn!("failed to watch the {SETTINGS_SECTION} settings: {err}");
+            }
+        }
+        if pull {
+            self.pull_settings().await;
+        }
+
         self.client
             .log_message(MessageType::INFO, "initialized!")
             .await;
@@ -105,28 +476,58 @@ impl LanguageServer for Backend {
         Ok(())
     }
 
-    async fn did_change_workspace_folders(&self, _: DidChangeWorkspaceFoldersParams) {
+    async fn did_change_workspace_folders(&self, params: DidChangeWorkspaceFoldersParams) {
         log::debug!("did_change_workspace_folders");
 
+        let paths = |folders: Vec<WorkspaceFolder>| -> Vec<PathBuf> {
+            folders
+                .iter()
+                .filter_map(|folder| folder.uri.to_file_path().ok())
+                .collect()
+        };
+        let added = paths(params.event.added);
+        let removed = paths(params.event.removed);
+        self.index.change_folders(added.clone(), removed.clone());
+        self.analysis
+            .with(move |analysis| analysis.change_workspace_folders(added, removed))
+            .await;
+
         self.client
             .log_message(MessageType::INFO, "workspace folders changed!")
             .await;
     }
 
-    async fn did_change_configuration(&self, _: DidChangeConfigurationParams) {
+    async fn did_change_configuration(&self, params: DidChangeConfigurationParams) {
         log::debug!("did_change_configuration");
 
-        self.client
-            .log_message(MessageType::INFO, "configuration changed!")
-            .await;
+        // Clients pulled from send no settings, the others push all of them.
+        match self.configuration_support.get() {
+            Some((true, _)) => self.pull_settings().await,
+            _ => {
+                let settings = match params.settings {
+                    Value::Object(mut settings) => settings.remove(SETTINGS_SECTION),
+                    _ => None,
+                };
+                self.apply_settings(settings.unwrap_or_default()).await;
+            }
+        }
     }
 
-    async fn did_change_watched_files(&self, _: DidChangeWatchedFilesParams) {
+    async fn did_change_watched_files(&self, params: DidChangeWatchedFilesParams) {
         log::debug!("did_change_watched_files");
 
-        self.client
-            .log_message(MessageType::INFO, "watched files have changed!")
-            .await;
+        let configuration = params.changes.iter().any(|change| {
+            change.uri.to_file_path().is_ok_and(|path| {
+                path.file_name()
+                    .is_some_and(|name| CONFIG_FILES.iter().any(|config| name == *config))
+            })
+        });
+        if configuration {
+            self.analysis
+                .with(|analysis| analysis.reload_configuration())
+                .await;
+            self.update_all().await;
+        }
     }
 
     async fn execute_command(&self, params: ExecuteCommandParams) -> Result<Option<Value>> {
@@ -137,12 +538,35 @@ impl LanguageServer for Backend {
             .await;
 
         match params.command.as_str() {
-            "linter.ai_sec.execute" => {
-                // log::info!("{:?}", &params);
-                // self.update_lsp(uri).await;
-                // TODO need to get current file and range from params, don't know how to pass from neovim.
+            // Formats the file whose URI is the first argument.
+            "format.execute" => {
+                let uri = params
+                    .arguments
+                    .first()
+                    .and_then(|uri| serde_json::from_value::<Url>(uri.clone()).ok())
+                    .ok_or_else(|| jsonrpc::Error::invalid_params("expected a document URI"))?;
+                let edits = self
+                    .with_source(&uri, |analysis, source| {
+                        formatting::formatting(analysis, &source, None)
+                    })
+                    .await?
+                    .flatten()
+                    .ok_or_else(|| request_failed(format!("cannot format {uri}")))?;
+                if !edits.is_empty() {
+                    let edit = WorkspaceEdit {
+                        changes: Some([(uri, edits)].into_iter().collect()),
+                        ..Default::default()
+                    };
+                    if let Err(err) = self.client.apply_edit(edit).await {
+                        log::error!("failed to apply formatting: {err}");
+                    }
+                }
+            }
+            command => {
+                return Err(jsonrpc::Error::invalid_params(format!(
+                    "unknown command {command}"
+                )))
             }
-            _ => unimplemented!(),
         }
 
         Ok(None)
@@ -151,26 +575,42 @@ impl LanguageServer for Backend {
     async fn did_open(&self, params: DidOpenTextDocumentParams) {
         log::debug!("did_open");
 
+        // Applied before anything is awaited, so that requests that follow see the new content.
+        let TextDocumentItem {
+            uri, version, text, ..
+        } = params.text_document;
+        self.documents.open(uri.clone(), version, text);
+
         self.client
             .log_message(MessageType::INFO, "file opened!")
             .await;
 
-        let uri = params.text_document.uri;
-        self.update_lsp(uri).await;
+        self.update_lsp(uri, false).await;
     }
 
     async fn did_change(&self, params: DidChangeTextDocumentParams) {
         log::debug!("did_change");
 
+        // Applied before anything is awaited, so that requests that follow see the new content.
+        let DidChangeTextDocumentParams {
+            text_document,
+            content_changes,
+        } = params;
+        self.documents.change(
+            &text_document.uri,
+            text_document.version,
+            content_changes,
+            self.position_encoding(),
+        );
+
         self.client
             .log_message(MessageType::INFO, "file changed!")
             .await;
 
-        let DidChangeTextDocumentParams {
-            text_document,
-            content_changes: _,
-        } = params;
-        self.update_lsp(text_document.uri).await;
+        if let Ok(path) = text_document.uri.to_file_path() {
+            self.index.changed(vec![path]);
+        }
+        self.update_lsp(text_document.uri, false).await;
     }
 
     async fn did_save(&self, params: DidSaveTextDocumentParams) {
@@ -182,22 +622,288 @@ impl LanguageServer for Backend {
             text_document,
             text: _,
         } = params;
-        self.update_lsp(text_document.uri).await;
+        self.update_lsp(text_document.uri, true).await;
     }
 
-    async fn did_close(&self, _: DidCloseTextDocumentParams) {
+    async fn did_close(&self, params: DidCloseTextDocumentParams) {
         self.client
             .log_message(MessageType::INFO, "file closed!")
             .await;
+
+        let uri = params.text_document.uri;
+        self.documents.close(&uri);
+        // The index goes back to the content on disk.
+        if let Ok(path) = uri.to_file_path() {
+            self.index.changed(vec![path]);
+        }
+        let closed = uri.clone();
+        self.analysis
+            .with(move |analysis| analysis.remove(&closed))
+            .await;
+        self.diagnostics.write().unwrap().remove(&uri);
+        self.client.publish_diagnostics(uri, vec![], None).await;
     }
 
-    async fn completion(&self, _: CompletionParams) -> Result<Option<CompletionResponse>> {
-        // Ok(Some(CompletionResponse::Array(vec![
-        //     CompletionItem::new_simple("Hello".to_string(), "Some detail".to_string()),
-        //     CompletionItem::new_simple("Bye".to_string(), "More detail".to_string()),
-        // ])))
+    async fn hover(&self, params: HoverParams) -> Result<Option<Hover>> {
+        let TextDocumentPositionParams {
+            text_document,
+            position,
+        } = params.text_document_position_params;
+        let hover = self
+            .with_source(&text_document.uri, move |analysis, source| {
+                hover::hover(analysis, &source.file, position)
+            })
+            .await?;
+        Ok(hover.flatten())
+    }
 
-        Ok(None)
+    async fn goto_definition(
+        &self,
+        params: GotoDefinitionParams,
+    ) -> Result<Option<GotoDefinitionResponse>> {
+        let TextDocumentPositionParams {
+            text_document,
+            position,
+        } = params.text_document_position_params;
+        let definition = self
+            .with_source(&text_document.uri, move |analysis, source| {
+                definition::definition(analysis, &source.file, position)
+            })
+            .await?;
+        Ok(definition.flatten())
+    }
+
+    async fn references(&self, params: ReferenceParams) -> Result<Option<Vec<Location>>> {
+        let TextDocumentPositionParams {
+            text_document,
+            position,
+        } = params.text_document_position;
+        let include_declaration = params.context.include_declaration;
+        let references = self
+            .with_source(&text_document.uri, move |analysis, source| {
+                references::references(analysis, &source.file, position, include_declaration)
+            })
+            .await?;
+        Ok(references.flatten())
+    }
+
+    async fn document_highlight(
+        &self,
+        params: DocumentHighlightParams,
+    ) -> Result<Option<Vec<DocumentHighlight>>> {
+        let TextDocumentPositionParams {
+            text_document,
+            position,
+        } = params.text_document_position_params;
+        let highlights = self
+            .with_source(&text_document.uri, move |analysis, source| {
+                references::highlights(analysis, &source.file, position)
+            })
+            .await?;
+        Ok(highlights.flatten())
+    }
+
+    async fn document_symbol(
+        &self,
+        params: DocumentSymbolParams,
+    ) -> Result<Option<DocumentSymbolResponse>> {
+        let symbols = self
+            .with_source(&params.text_document.uri, |_, source| {
+                symbols::document_symbols(&source.file)
+            })
+            .await?;
+        Ok(symbols.map(DocumentSymbolResponse::Nested))
+    }
+
+    async fn symbol(
+        &self,
+        params: WorkspaceSymbolParams,
+    ) -> Result<Option<Vec<SymbolInformation>>> {
+        Ok(Some(self.index.search(&params.query)))
+    }
+
+    async fn prepare_rename(
+        &self,
+        params: TextDocumentPositionParams,
+    ) -> Result<Option<PrepareRenameResponse>> {
+        let TextDocumentPositionParams {
+            text_document,
+            position,
+        } = params;
+        let response = self
+            .with_source(&text_document.uri, move |analysis, source| {
+                rename::prepare_rename(analysis, &source.file, position)
+            })
+            .await?;
+        match response {
+            Some(response) => response.map_err(request_failed),
+            None => Ok(None),
+        }
+    }
+
+    async fn rename(&self, params: RenameParams) -> Result<Option<WorkspaceEdit>> {
+        let TextDocumentPositionParams {
+            text_document,
+            position,
+        } = params.text_document_position;
+        let new_name = params.new_name;
+        let edit = self
+            .with_source(&text_document.uri, move |analysis, source| {
+                rename::rename(analysis, &source.file, position, &new_name)
+            })
+            .await?;
+        match edit {
+            Some(edit) => edit.map_err(request_failed),
+            None => Ok(None),
+        }
+    }
+
+    async fn signature_help(&self, params: SignatureHelpParams) -> Result<Option<SignatureHelp>> {
+        let TextDocumentPositionParams {
+            text_document,
+            position,
+        } = params.text_document_position_params;
+        let help = self
+            .with_source(&text_document.uri, move |analysis, source| {
+                signature_help::signature_help(analysis, &source.file, position)
+            })
+            .await?;
+        Ok(help.flatten())
+    }
+
+    async fn code_action(&self, params: CodeActionParams) -> Result<Option<CodeActionResponse>> {
+        let diags = self
+            .diagnostics
+            .read()
+            .unwrap()
+            .get(&params.text_document.uri)
+            .cloned()
+            .unwrap_or_default();
+        let uri = params.text_document.uri;
+        let Some(text) = self
+            .with_source(&uri, |_, source| source.text.clone())
+            .await?
+        else {
+            return Ok(None);
+        };
+        let actions = code_actions::code_actions(&uri, &text, &diags, params.range);
+        Ok(Some(actions))
+    }
+
+    async fn formatting(&self, params: DocumentFormattingParams) -> Result<Option<Vec<TextEdit>>> {
+        let edits = self
+            .with_source(&params.text_document.uri, |analysis, source| {
+                formatting::formatting(analysis, &source, None)
+            })
+            .await?;
+        Ok(edits.flatten())
+    }
+
+    async fn range_formatting(
+        &self,
+        params: DocumentRangeFormattingParams,
+    ) -> Result<Option<Vec<TextEdit>>> {
+        let range = params.range;
+        let edits = self
+            .with_source(&params.text_document.uri, move |analysis, source| {
+                formatting::formatting(analysis, &source, Some(range))
+            })
+            .await?;
+        Ok(edits.flatten())
+    }
+
+    async fn on_type_formatting(
+        &self,
+        params: DocumentOnTypeFormattingParams,
+    ) -> Result<Option<Vec<TextEdit>>> {
+        let TextDocumentPositionParams {
+            text_document,
+            position,
+        } = params.text_document_position;
+        let (ch, options) = (params.ch, params.options);
+        let edits = self
+            .with_source(&text_document.uri, move |analysis, source| {
+                on_type_formatting::on_type_formatting(
+                    analysis,
+                    &source.file,
+                    position,
+                    &ch,
+                    &options,
+                )
+            })
+            .await?;
+        Ok(edits.filter(|edits| !edits.is_empty()))
+    }
+
+    async fn folding_range(&self, params: FoldingRangeParams) -> Result<Option<Vec<FoldingRange>>> {
+        let ranges = self
+            .with_source(&params.text_document.uri, |_, source| {
+                ranges::folding_ranges(&source)
+            })
+            .await?;
+        Ok(ranges)
+    }
+
+    async fn selection_range(
+        &self,
+        params: SelectionRangeParams,
+    ) -> Result<Option<Vec<SelectionRange>>> {
+        let positions = params.positions;
+        let ranges = self
+            .with_source(&params.text_document.uri, move |_, source| {
+                ranges::selection_ranges(&source, &positions)
+            })
+            .await?;
+        Ok(ranges)
+    }
+
+    async fn semantic_tokens_full(
+        &self,
+        params: SemanticTokensParams,
+    ) -> Result<Option<SemanticTokensResult>> {
+        let tokens = self
+            .with_source(&params.text_document.uri, |analysis, source| {
+                semantic_tokens::semantic_tokens(analysis, &source.file, None)
+            })
+            .await?;
+        Ok(tokens.map(|data| {
+            SemanticTokensResult::Tokens(SemanticTokens {
+                result_id: None,
+                data,
+            })
+        }))
+    }
+
+    async fn semantic_tokens_range(
+        &self,
+        params: SemanticTokensRangeParams,
+    ) -> Result<Option<SemanticTokensRangeResult>> {
+        let range = params.range;
+        let tokens = self
+            .with_source(&params.text_document.uri, move |analysis, source| {
+                semantic_tokens::semantic_tokens(analysis, &source.file, Some(range))
+            })
+            .await?;
+        Ok(tokens.map(|data| {
+            SemanticTokensRangeResult::Tokens(SemanticTokens {
+                result_id: None,
+                data,
+            })
+        }))
+    }
+
+    async fn completion(&self, params: CompletionParams) -> Result<Option<CompletionResponse>> {
+        let TextDocumentPositionParams {
+            text_document,
+            position,
+        } = params.text_document_position;
+        let index = self.index.clone();
+        let completion = self
+            .with_source(&text_document.uri, move |analysis, source| {
+                completion::completion(analysis, &source.file, position, &index)
+            })
+            .await?;
+        Ok(completion.flatten())
     }
 }
 
@@ -223,7 +929,9 @@ impl SlapServer {
     // pub async fn serve(&self, detectors: Vec<Box<dyn Detector>>) {
     pub async fn serve(&self, detectors: Detectors) {
         // TODO dyn
-        let (service, socket) = LspService::new(|client| Backend::new(client, detectors));
+        let (service, socket) = LspService::build(|client| Backend::new(client, detectors))
+            .custom_method("slap/rules", Backend::rules)
+            .finish();
 
         let transport = self.transport.clone();
         // tokio::spawn(async move {
@@ -1,13 +1,20 @@
 use clap::Parser;
-use detectors::{ai_sec::AIDetector, structs::StructsDetector, Detector, Detectors};
+use detectors::Detectors;
 use log::LevelFilter;
 use log4rs::append::file::FileAppender;
 use log4rs::config::{Appender, Config, Root};
 use log4rs::encode::pattern::PatternEncoder;
 
+mod analysis;
 mod cli;
+mod config;
 mod detectors;
+mod document;
+mod features;
+mod format;
+mod index;
 mod lsp;
+mod project;
 
 #[tokio::main]
 async fn main() {
@@ -34,7 +41,5 @@ async fn main() {
     let args = cli::Args::parse();
     let path = std::env::current_dir().expect("failed to get getcwd");
     let server = lsp::SlapServer::new(path, args.transport);
-    let detectors: Vec<Box<dyn Detector>> = vec![Box::new(AIDetector), Box::new(StructsDetector)];
-    let detectors = Detectors(detectors);
-    server.serve(detectors).await;
+    server.serve(Detectors::builtin()).await;
 }
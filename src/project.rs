@@ -0,0 +1,204 @@
+use crate::format::FormatOptions;
+use semver::Version;
+use std::path::{Component, Path, PathBuf};
+
+/// The Foundry project a file belongs to, as described by its `foundry.toml`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Project {
+    pub root: PathBuf,
+    /// `solc` (or `solc_version`) of the default profile, when it's a version rather than a path.
+    pub solc: Option<Version>,
+    /// From `foundry.toml`, `remappings.txt` and the libraries found in `libs`.
+    pub remappings: Vec<Remapping>,
+    /// Directories searched for imports, relative to the root.
+    pub libs: Vec<PathBuf>,
+    /// `[fmt]`, or `fmt` of the default profile.
+    pub fmt: FormatOptions,
+}
+
+/// An import remapping like `@openzeppelin/=lib/openzeppelin-contracts/`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Remapping {
+    /// Only applies to files under this directory, relative to the project root.
+    pub context: Option<String>,
+    pub prefix: String,
+    pub target: String,
+}
+
+impl Remapping {
+    /// Parses `[context:]prefix=target`.
+    pub fn parse(remapping: &str) -> Option<Self> {
+        let (prefix, target) = remapping.trim().split_once('=')?;
+        let (context, prefix) = match prefix.split_once(':') {
+            Some((context, prefix)) => (Some(context.to_string()), prefix),
+            None => (None, prefix),
+        };
+        if prefix.is_empty() {
+            return None;
+        }
+        Some(Self {
+            context,
+            prefix: prefix.to_string(),
+            target: target.to_string(),
+        })
+    }
+}
+
+impl Project {
+    /// Finds the closest `foundry.toml` above `path`.
+    pub fn find(path: &Path) -> Option<Self> {
+        let root = path
+            .ancestors()
+            .skip(1)
+            .find(|dir| dir.join("foundry.toml").is_file())?;
+        Some(Self::load(root))
+    }
+
+    pub fn load(root: &Path) -> Self {
+        let manifest = root.join("foundry.toml");
+        let table = match std::fs::read_to_string(&manifest).map(|s| s.parse::<toml::Table>()) {
+            Ok(Ok(table)) => table,
+            Ok(Err(err)) => {
+                log::error!("invalid {}: {err}", manifest.display());
+                toml::Table::new()
+            }
+            Err(err) => {
+                log::error!("failed to read {}: {err}", manifest.display());
+                toml::Table::new()
+            }
+        };
+
+        let profile = table
+            .get("profile")
+            .and_then(|profiles| profiles.get("default"));
+        let solc = profile
+            .and_then(|profile| profile.get("solc").or_else(|| profile.get("solc_version")))
+            .and_then(|solc| solc.as_str())
+            .and_then(|solc| Version::parse(solc).ok());
+        let strings = |key: &str| -> Option<Vec<String>> {
+            let values = profile?.get(key)?.as_array()?;
+            Some(
+                values
+                    .iter()
+                    .filter_map(|value| value.as_str().map(str::to_string))
+                    .collect(),
+            )
+        };
+
+        let libs: Vec<PathBuf> = strings("libs")
+            .unwrap_or_else(|| vec!["lib".to_string()])
+            .into_iter()
+            .map(PathBuf::from)
+            .collect();
+        // Explicit remappings come last so that they win over the inferred ones.
+        let mut remappings = auto_remappings(root, &libs);
+        let remappings_txt =
+            std::fs::read_to_string(root.join("remappings.txt")).unwrap_or_default();
+        remappings.extend(
+            strings("remappings")
+                .unwrap_or_default()
+                .iter()
+                .map(String::as_str)
+                .chain(remappings_txt.lines())
+                .filter_map(Remapping::parse),
+        );
+
+        let fmt = table
+            .get("fmt")
+            .or_else(|| profile.and_then(|profile| profile.get("fmt")))
+            .and_then(|fmt| fmt.as_table())
+            .map(FormatOptions::from_table)
+            .unwrap_or_default();
+
+        Self {
+            root: root.to_path_buf(),
+            solc,
+            remappings,
+            libs,
+            fmt,
+        }
+    }
+
+    /// Resolves the path of an `import` in `from` the way `forge` does: relative to the
+    /// importing file, through the remappings, then from the root and the library directories.
+    pub fn resolve_import(&self, from: &Path, import: &str) -> Option<PathBuf> {
+        if import.starts_with("./") || import.starts_with("../") {
+            return Some(normalize(&from.parent()?.join(import)));
+        }
+
+        let context = from
+            .strip_prefix(&self.root)
+            .map(|relative| relative.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let remapped = self
+            .remappings
+            .iter()
+            .filter(|remapping| import.starts_with(&remapping.prefix))
+            .filter(|remapping| {
+                remapping
+                    .context
+                    .as_ref()
+                    .is_none_or(|prefix| context.starts_with(prefix.as_str()))
+            })
+            // The most specific remapping wins, like in solc, and the last one among equals.
+            .max_by_key(|remapping| {
+                (
+                    remapping.context.as_ref().map_or(0, String::len),
+                    remapping.prefix.len(),
+                )
+            })
+            .map(|remapping| format!("{}{}", remapping.target, &import[remapping.prefix.len()..]));
+
+        let candidates = remapped
+            .iter()
+            .map(|remapped| self.root.join(remapped))
+            .chain(std::iter::once(self.root.join(import)))
+            .chain(self.libs.iter().map(|lib| self.root.join(lib).join(import)));
+        candidates
+            .map(|candidate| normalize(&candidate))
+            .find(|candidate| candidate.is_file())
+    }
+}
+
+/// The remappings `forge` infers for the libraries: `name/` for every `lib/name`, pointing to its
+/// `src` directory when it has one.
+fn auto_remappings(root: &Path, libs: &[PathBuf]) -> Vec<Remapping> {
+    let mut remappings = Vec::new();
+    for lib in libs {
+        let Ok(entries) = std::fs::read_dir(root.join(lib)) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if !path.is_dir() {
+                continue;
+            }
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let mut target = lib.join(&name);
+            if path.join("src").is_dir() {
+                target = target.join("src");
+            }
+            remappings.push(Remapping {
+                context: None,
+                prefix: format!("{name}/"),
+                target: format!("{}/", target.display()),
+            });
+        }
+    }
+    remappings
+}
+
+/// Removes `.` and `..` components without touching the file system.
+pub fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}